@@ -0,0 +1,332 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::{collections::HashMap, fs::{File, OpenOptions}, io::{BufWriter, Write}, path::{Path, PathBuf}};
+use async_channel::{Receiver, Sender};
+use chrono::{DateTime, NaiveDate, Utc};
+use egui::Context;
+use tokio::{runtime::{Handle, Runtime}, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{emotes::cache_path_from_app_name, provider::{ChatMessage, IncomingMessage, MessageType, ProviderName, UserProfile}};
+
+/// Daily chat log files older than this are deleted when the archive is opened
+pub const ARCHIVE_RETENTION_DAYS : i64 = 30;
+/// Max number of logged messages loaded back into a channel tab when it is opened
+pub const ARCHIVE_RESTORE_MESSAGE_COUNT : usize = 200;
+
+const ARCHIVE_DIR : &str = "chatlogs";
+const ARCHIVE_FILE_EXT : &str = "jsonl";
+
+/// One line of a chat log file. Messages that are removed by moderation after being logged
+/// are appended a second time with `is_removed` set.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArchivedMessage {
+  pub provider: ProviderName,
+  pub channel: String,
+  pub username: String,
+  pub display_name: Option<String>,
+  pub timestamp_ms: i64,
+  pub message: String,
+  pub msg_type: MessageType,
+  pub is_removed: Option<String>,
+  #[serde(default)]
+  pub badges: Option<Vec<String>>,
+  #[serde(default)]
+  pub color: Option<(u8, u8, u8)>,
+  /// Empty for messages logged before ids were recorded
+  #[serde(default)]
+  pub id: String
+}
+
+impl From<&ChatMessage> for ArchivedMessage {
+  fn from(msg: &ChatMessage) -> Self {
+    Self {
+      provider: msg.provider.to_owned(),
+      channel: msg.channel.to_owned(),
+      username: msg.username.to_owned(),
+      display_name: msg.profile.display_name.to_owned(),
+      timestamp_ms: msg.timestamp.timestamp_millis(),
+      message: msg.message.to_owned(),
+      msg_type: msg.msg_type.to_owned(),
+      is_removed: msg.is_removed.to_owned(),
+      badges: msg.profile.badges.to_owned(),
+      color: msg.profile.color,
+      id: msg.id.to_owned()
+    }
+  }
+}
+
+impl From<ArchivedMessage> for ChatMessage {
+  fn from(msg: ArchivedMessage) -> Self {
+    let timestamp = msg.timestamp();
+    Self {
+      provider: msg.provider,
+      channel: msg.channel,
+      username: msg.username,
+      timestamp,
+      message: msg.message,
+      profile: UserProfile {
+        badges: msg.badges,
+        display_name: msg.display_name,
+        color: msg.color
+      },
+      is_removed: msg.is_removed,
+      msg_type: msg.msg_type,
+      from_archive: true,
+      id: if msg.id.is_empty() { crate::provider::new_message_id() } else { msg.id },
+      ..Default::default()
+    }
+  }
+}
+
+impl ArchivedMessage {
+  pub fn timestamp(&self) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(self.timestamp_ms).unwrap_or_default()
+  }
+
+  fn is_same_message(&self, other: &ArchivedMessage) -> bool {
+    if !self.id.is_empty() && !other.id.is_empty() {
+      return self.id == other.id;
+    }
+    self.timestamp_ms == other.timestamp_ms && self.username == other.username && self.message == other.message
+  }
+}
+
+/// Which daily log files to read: all of them unless limited to one provider/channel and a date range
+pub struct LogFileQuery {
+  pub provider: Option<ProviderName>,
+  pub channel: Option<String>,
+  pub from: Option<NaiveDate>,
+  pub to: Option<NaiveDate>
+}
+
+enum ArchiveRequest {
+  Append { entry: ArchivedMessage }
+}
+
+pub struct ChatArchive {
+  pub base_path: PathBuf,
+  tx: Sender<ArchiveRequest>,
+  handle: JoinHandle<()>,
+  runtime: Handle,
+  ctx: Context,
+  /// Results of log reads started with `load_recent` and `search`
+  out_tx: Sender<IncomingMessage>,
+  pub out_rx: Receiver<IncomingMessage>
+}
+
+impl ChatArchive {
+  pub fn new(app_name: &str, runtime: &Runtime, ctx: &Context) -> Option<Self> {
+    let base_path = cache_path_from_app_name(app_name)?.join(ARCHIVE_DIR);
+    if let Err(e) = std::fs::create_dir_all(&base_path) {
+      warn!("Chat archive disabled: failed to create {:?}: {}", base_path, e);
+      return None;
+    }
+    prune_old_logs(&base_path, ARCHIVE_RETENTION_DAYS);
+
+    let (tx, rx) = async_channel::bounded::<ArchiveRequest>(10000);
+    let path = base_path.to_owned();
+    let handle = runtime.spawn(async move {
+      // one open file per channel, reopened when the date rolls over
+      let mut writers : HashMap<(ProviderName, String), (NaiveDate, BufWriter<File>)> = Default::default();
+      while let Ok(request) = rx.recv().await {
+        match request {
+          ArchiveRequest::Append { entry } => {
+            let date = entry.timestamp().date_naive();
+            let key = (entry.provider.to_owned(), entry.channel.to_owned());
+            if writers.get(&key).is_none_or(|(d, _)| *d != date) {
+              match open_log_file(&log_file_path(&path, &entry.provider, &entry.channel, &date)) {
+                Ok(file) => { writers.insert(key.to_owned(), (date, BufWriter::new(file))); },
+                Err(e) => { warn!("Failed to open chat log for {}: {}", entry.channel, e); continue; }
+              }
+            }
+            if let Some((_, writer)) = writers.get_mut(&key) {
+              match serde_json::to_string(&entry) {
+                Ok(line) => if let Err(e) = writeln!(writer, "{line}") { warn!("Failed to write chat log for {}: {}", entry.channel, e); },
+                Err(e) => warn!("Failed to serialize chat log entry: {}", e)
+              }
+            }
+          }
+        }
+        if rx.is_empty() {
+          for (_, writer) in writers.values_mut() {
+            if let Err(e) = writer.flush() {
+              warn!("Failed to flush chat log: {}", e);
+            }
+          }
+        }
+      }
+      info!("exiting chat archive thread");
+    });
+
+    let (out_tx, out_rx) = async_channel::unbounded::<IncomingMessage>();
+    Some(Self {
+      base_path,
+      tx,
+      handle,
+      runtime: runtime.handle().to_owned(),
+      ctx: ctx.to_owned(),
+      out_tx,
+      out_rx
+    })
+  }
+
+  pub fn append(&self, message: &ChatMessage) {
+    if message.channel.is_empty() {
+      return;
+    }
+    if let Err(e) = self.tx.try_send(ArchiveRequest::Append { entry: ArchivedMessage::from(message) }) {
+      info!("Error sending chat archive request: {}", e);
+    }
+  }
+
+  /// Stops accepting new messages. The writer task exits after flushing what is already queued.
+  pub fn close(&self) {
+    self.tx.close();
+  }
+
+  pub fn is_running(&self) -> bool {
+    !self.handle.is_finished()
+  }
+
+  /// Reads the last `count` messages logged for a channel off the UI thread and sends them back, oldest first,
+  /// as `IncomingMessage::ArchiveHistory`
+  pub fn load_recent(&self, provider: &ProviderName, channel: &str, count: usize) {
+    let (base_path, provider, channel) = (self.base_path.to_owned(), provider.to_owned(), channel.to_owned());
+    let (tx, ctx) = (self.out_tx.to_owned(), self.ctx.to_owned());
+    self.runtime.spawn_blocking(move || {
+      let messages = load_recent(&base_path, &provider, &channel, count).into_iter().map(ChatMessage::from).collect();
+      if let Err(e) = tx.try_send(IncomingMessage::ArchiveHistory { channel, messages }) {
+        info!("Error sending restored chat history: {}", e);
+      }
+      ctx.request_repaint();
+    });
+  }
+
+  /// Reads the log files matching `query` off the UI thread and sends the messages `filter` accepts
+  /// back as `IncomingMessage::ArchiveSearchResults`
+  pub fn search(&self, search_id: usize, query: LogFileQuery, filter: impl Fn(&ChatMessage) -> bool + Send + 'static) {
+    let base_path = self.base_path.to_owned();
+    let (tx, ctx) = (self.out_tx.to_owned(), self.ctx.to_owned());
+    self.runtime.spawn_blocking(move || {
+      let messages = log_files(&base_path, &query).into_iter()
+        .flat_map(|path| read_log_file(&path))
+        .map(ChatMessage::from)
+        .filter(|msg| filter(msg))
+        .collect();
+      if let Err(e) = tx.try_send(IncomingMessage::ArchiveSearchResults { search_id, messages }) {
+        info!("Error sending chat archive search results: {}", e);
+      }
+      ctx.request_repaint();
+    });
+  }
+}
+
+/// Last `count` messages logged for a channel, oldest first
+pub fn load_recent(base_path: &Path, provider: &ProviderName, channel: &str, count: usize) -> Vec<ArchivedMessage> {
+  let mut result : Vec<ArchivedMessage> = Default::default();
+  for path in list_log_files(&channel_log_dir(base_path, provider, channel)).into_iter().rev() {
+    let mut entries = read_log_file(&path);
+    entries.append(&mut result);
+    result = entries;
+    if result.len() >= count {
+      break;
+    }
+  }
+  result.split_off(result.len().saturating_sub(count))
+}
+
+/// Daily log files matching the query, oldest first
+pub fn log_files(base_path: &Path, query: &LogFileQuery) -> Vec<PathBuf> {
+  let provider_dir = query.provider.as_ref().map(|p| p.info().archive_dir()).unwrap_or("*");
+  let channel_dir = query.channel.as_deref().map(sanitize_file_name).unwrap_or_else(|| "*".to_owned());
+  let Some(base_path) = base_path.to_str() else { return Default::default(); };
+  let pattern = format!("{}/{provider_dir}/{channel_dir}/*.{ARCHIVE_FILE_EXT}", glob::Pattern::escape(base_path));
+  let mut files = match glob::glob(&pattern) {
+    Ok(paths) => paths.flatten()
+      .filter_map(|path| log_file_date(&path).map(|date| (date, path)))
+      .filter(|(date, _)| query.from.is_none_or(|from| *date >= from) && query.to.is_none_or(|to| *date <= to))
+      .collect::<Vec<(NaiveDate, PathBuf)>>(),
+    Err(e) => { warn!("Failed to list chat logs: {}", e); Default::default() }
+  };
+  files.sort_by_key(|(date, _)| *date);
+  files.into_iter().map(|(_, path)| path).collect()
+}
+
+pub fn channel_log_dir(base_path: &Path, provider: &ProviderName, channel: &str) -> PathBuf {
+  base_path.join(provider.info().archive_dir()).join(sanitize_file_name(channel))
+}
+
+pub fn log_file_path(base_path: &Path, provider: &ProviderName, channel: &str, date: &NaiveDate) -> PathBuf {
+  channel_log_dir(base_path, provider, channel).join(format!("{}.{ARCHIVE_FILE_EXT}", date.format("%Y-%m-%d")))
+}
+
+fn open_log_file(path: &Path) -> Result<File, std::io::Error> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+  name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+/// Daily log files in a channel's log folder, oldest first
+fn list_log_files(dir: &Path) -> Vec<PathBuf> {
+  let mut files = match std::fs::read_dir(dir) {
+    Ok(entries) => entries.flatten().map(|entry| entry.path()).filter_map(|path| log_file_date(&path).map(|date| (date, path))).collect::<Vec<(NaiveDate, PathBuf)>>(),
+    Err(_) => Default::default()
+  };
+  files.sort_by_key(|(date, _)| *date);
+  files.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Parses one log file, folding later "message removed" entries into the original message
+pub fn read_log_file(path: &Path) -> Vec<ArchivedMessage> {
+  let contents = match std::fs::read_to_string(path) {
+    Ok(contents) => contents,
+    Err(e) => { warn!("Failed to read chat log {:?}: {}", path, e); return Default::default(); }
+  };
+  let mut result : Vec<ArchivedMessage> = Default::default();
+  for line in contents.lines() {
+    // the last line may be partially written
+    let Ok(entry) = serde_json::from_str::<ArchivedMessage>(line) else { continue; };
+    if entry.is_removed.is_some() && let Some(original) = result.iter_mut().rev().find(|m| m.is_same_message(&entry)) {
+      original.is_removed = entry.is_removed;
+    } else {
+      result.push(entry);
+    }
+  }
+  result
+}
+
+/// Date of a daily log file, parsed from its file name
+fn log_file_date(path: &Path) -> Option<NaiveDate> {
+  if path.extension().is_some_and(|ext| ext == ARCHIVE_FILE_EXT) {
+    path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok())
+  } else {
+    None
+  }
+}
+
+/// Deletes daily log files older than `retention_days`
+pub fn prune_old_logs(base_path: &Path, retention_days: i64) {
+  let cutoff = Utc::now().date_naive() - chrono::Duration::days(retention_days);
+  let Some(base_path) = base_path.to_str() else { return; };
+  let pattern = format!("{}/*/*/*.{ARCHIVE_FILE_EXT}", glob::Pattern::escape(base_path));
+  match glob::glob(&pattern) {
+    Ok(paths) => {
+      for path in paths.flatten() {
+        if log_file_date(&path).is_some_and(|date| date < cutoff) && let Err(e) = std::fs::remove_file(&path) {
+          warn!("Failed to remove old chat log {:?}: {}", path, e);
+        }
+      }
+    },
+    Err(e) => warn!("Failed to list chat logs: {}", e)
+  }
+}
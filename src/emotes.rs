@@ -15,7 +15,7 @@ use std::{collections::HashMap, path::{Path, PathBuf}};
 use std::str;
 use tracing_unwrap::OptionExt;
 
-use crate::{provider::{dgg, channel::{ChannelShared, Channel}, Provider}, TemplateApp};
+use crate::{provider::{dgg, kick, channel::{ChannelShared, Channel}, Provider, ProviderName}, TemplateApp};
 
 pub mod fetch;
 pub mod imaging;
@@ -31,7 +31,8 @@ pub enum EmoteRequest {
   TwitchBadgeEmoteListRequest { channel_id: String, channel_name: String, token: String, force_redownload: bool },
   TwitchEmoteSetRequest { token: String, emote_set_id: String, force_redownload: bool },
   DggFlairEmotesRequest { channel_name: String, cdn_base_url: String, force_redownload: bool },
-  YouTubeMsgEmoteImage { name: String, url: String, path: String },
+  ProviderMsgEmoteImage { provider: ProviderName, name: String, id: String, url: String, path: String },
+  KickEmoteListRequest { channel_name: String, slug: String, force_redownload: bool },
   //JsonDownloadRequest { url: String, filename: String, headers: Option<Vec<(String, String)>> }
 }
 
@@ -40,14 +41,13 @@ pub enum EmoteResponse {
   GlobalBadgeImageLoaded { name : String, data: Option<Vec<(ColorImage, u16)>> },
   ChannelEmoteImageLoaded { name : String, channel_name: String, data: Option<Vec<(ColorImage, u16)>> },
   ChannelBadgeImageLoaded { name : String, channel_name: String, data: Option<Vec<(ColorImage, u16)>> },
-  TwitchMsgEmoteLoaded { name: String, id: String, data: Option<Vec<(ColorImage, u16)>> },
+  ProviderMsgEmoteLoaded { provider: ProviderName, name: String, data: Option<Vec<(ColorImage, u16)>> },
   ChannelEmoteListResponse { channel_name: String, response: Result<HashMap<String, Emote>, anyhow::Error> },
   ChannelBadgeListResponse { channel_name: String, response: Result<HashMap<String, Emote>, anyhow::Error> },
   TwitchEmoteSetResponse { emote_set_id: String, response: Result<HashMap<String, Emote>, anyhow::Error> },
   //JsonDownloadResponse { url: String, filename: String, content: String }
   GlobalEmoteListResponse { response: Result<HashMap<String, Emote>, anyhow::Error> },
   TwitchGlobalBadgeListResponse { response: Result<HashMap<String, Emote>, anyhow::Error> },
-  YouTubeMsgEmoteLoaded { name: String, data: Option<Vec<(ColorImage, u16)>> },
 }
 
 #[derive(Default)]
@@ -87,7 +87,8 @@ pub enum EmoteSource
   ChannelBadge,
   GlobalBadge,
   Twitch,
-  Youtube
+  /// Emote sent with a message, loaded from its url
+  Provider(ProviderName)
 }
 
 #[derive(Default)]
@@ -154,8 +155,10 @@ impl Emote {
         name: emote.name.to_owned(), 
         id: emote.id.to_owned() 
       },
-      EmoteSource::Youtube => EmoteRequest::YouTubeMsgEmoteImage { 
+      EmoteSource::Provider(ref provider) => EmoteRequest::ProviderMsgEmoteImage { 
+        provider: provider.to_owned(),
         name: emote.name.to_owned(), 
+        id: emote.id.to_owned(), 
         url: emote.url.to_owned(), 
         path: emote.path.to_owned() 
       }
@@ -308,12 +311,11 @@ impl EmoteLoader {
                     &format!("https://static-cdn.jtvnw.net/emoticons/v2/{id}/animated/light/3.0"), 
                     &format!("https://static-cdn.jtvnw.net/emoticons/v2/{id}/static/light/3.0")
                   ], &cache_path.join("twitch/"), &id, &None, &client, &None).await;
-                EmoteResponse::TwitchMsgEmoteLoaded { name, id, data }
+                EmoteResponse::ProviderMsgEmoteLoaded { provider: ProviderName::Twitch, name, data }
               },
-              EmoteRequest::YouTubeMsgEmoteImage { name, url, path } => {
-                //info!("{n} loading youtube emote '{}'", name);
-                let data = imaging::get_image_data(&name, &[&url], &cache_path.join(path), &name, &None, &client, &None).await;
-                EmoteResponse::YouTubeMsgEmoteLoaded { name, data }
+              EmoteRequest::ProviderMsgEmoteImage { provider, name, id, url, path } => {
+                let data = imaging::get_image_data(&name, &[&url], &cache_path.join(path), &id, &None, &client, &None).await;
+                EmoteResponse::ProviderMsgEmoteLoaded { provider, name, data }
               },
               EmoteRequest::TwitchEmoteSetRequest { token, emote_set_id, force_redownload } => {
                 let data = twitch_get_emote_set(&token, &emote_set_id, &cache_path, &client, force_redownload).await;
@@ -337,6 +339,11 @@ impl EmoteLoader {
                 };
                 EmoteResponse::ChannelBadgeListResponse { channel_name: channel_name.to_owned(), response: badge_list.await }
               },
+              EmoteRequest::KickEmoteListRequest { channel_name, slug, force_redownload } => {
+                // badges come with the channel info the chat client loads
+                let data = kick::load_kick_emotes(&channel_name, &slug, &cache_path, &client, force_redownload).await;
+                EmoteResponse::ChannelEmoteListResponse { channel_name, response: data }
+              },
               EmoteRequest::GlobalEmoteListRequest { force_redownload } => {
                 let data = load_global_emotes(&cache_path, &client, force_redownload).await;
                 EmoteResponse::GlobalEmoteListResponse { response: data }
@@ -667,11 +674,7 @@ impl AddEmote for Channel {
           emote.source = EmoteSource::Channel;
           emote.channel_name.clone_from(self.channel_name());
         }
-        let shared = match self {
-          Channel::DGG { dgg: _, ref mut shared } => shared,
-          Channel::Twitch { twitch: _, ref mut shared } => shared,
-          Channel::Youtube { youtube: _, ref mut shared } => shared
-        };
+        let shared = self.shared_mut();
         if let Some(t) = shared.transient.as_mut() {
           t.channel_emotes = Some(emotes)
         }
@@ -686,11 +689,7 @@ impl AddEmote for Channel {
           badge.source = EmoteSource::ChannelBadge;
           badge.channel_name.clone_from(self.channel_name());
         }
-        let shared = match self {
-          Channel::DGG { dgg: _, ref mut shared } => shared,
-          Channel::Twitch { twitch: _, ref mut shared } => shared,
-          Channel::Youtube { youtube: _, ref mut shared } => shared
-        };
+        let shared = self.shared_mut();
         if let Some(t) = shared.transient.as_mut() {
           t.badge_emotes = Some(badges)
         }
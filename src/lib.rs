@@ -7,6 +7,9 @@
 pub mod ui;
 pub mod provider;
 pub mod emotes;
+pub mod archive;
+pub mod notifications;
+pub mod sounds;
 pub mod test;
 pub use ui::TemplateApp;
 pub mod mod_selected_label;
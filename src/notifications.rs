@@ -0,0 +1,127 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::{collections::VecDeque, sync::{Arc, Mutex}};
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+/// Max number of notifications shown within `NOTIFICATION_RATE_WINDOW_SECS`, the rest are dropped
+pub const NOTIFICATION_RATE_LIMIT : usize = 3;
+pub const NOTIFICATION_RATE_WINDOW_SECS : i64 = 30;
+
+/// Shows a notification outside of the app window
+pub trait Notifier {
+  fn notify(&mut self, title: &str, body: &str) -> anyhow::Result<()>;
+}
+
+/// Uses the notification tool that comes with the OS, so no extra libraries are needed
+#[derive(Default)]
+pub struct SystemNotifier;
+
+impl Notifier for SystemNotifier {
+  #[cfg(target_os = "linux")]
+  fn notify(&mut self, title: &str, body: &str) -> anyhow::Result<()> {
+    spawn(std::process::Command::new("notify-send").args(["--app-name=Gigachat", title, body]))
+  }
+
+  #[cfg(target_os = "macos")]
+  fn notify(&mut self, title: &str, body: &str) -> anyhow::Result<()> {
+    // passed as script arguments, so the text is never parsed as AppleScript
+    spawn(std::process::Command::new("osascript").args([
+      "-e", "on run argv",
+      "-e", "display notification (item 2 of argv) with title (item 1 of argv)",
+      "-e", "end run",
+      title, body
+    ]))
+  }
+
+  #[cfg(target_os = "windows")]
+  fn notify(&mut self, title: &str, body: &str) -> anyhow::Result<()> {
+    spawn(&mut powershell_notify_command(title, body))
+  }
+
+  #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+  fn notify(&mut self, _title: &str, _body: &str) -> anyhow::Result<()> {
+    anyhow::bail!("desktop notifications are not supported on this platform")
+  }
+}
+
+/// The title and body are passed in environment variables, so chat text never becomes part of the PowerShell script
+#[cfg(any(target_os = "windows", test))]
+pub fn powershell_notify_command(title: &str, body: &str) -> std::process::Command {
+  const SCRIPT : &str = "Add-Type -AssemblyName System.Windows.Forms; \
+    $n = New-Object System.Windows.Forms.NotifyIcon; \
+    $n.Icon = [System.Drawing.SystemIcons]::Information; \
+    $n.Visible = $true; \
+    $n.ShowBalloonTip(5000, $env:GIGACHAT_NOTIFY_TITLE, $env:GIGACHAT_NOTIFY_BODY, 'None'); \
+    Start-Sleep -Seconds 6; \
+    $n.Dispose()";
+  let mut command = std::process::Command::new("powershell");
+  command.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", SCRIPT])
+    .env("GIGACHAT_NOTIFY_TITLE", title)
+    .env("GIGACHAT_NOTIFY_BODY", body);
+  command
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn spawn(command: &mut std::process::Command) -> anyhow::Result<()> {
+  let mut child = command.spawn()?;
+  // reap the process once the notification tool exits
+  std::thread::spawn(move || child.wait());
+  Ok(())
+}
+
+/// Keeps notifications in memory instead of showing them
+#[derive(Clone, Default)]
+pub struct RecordingNotifier {
+  pub notifications: Arc<Mutex<Vec<(String, String)>>>
+}
+
+impl Notifier for RecordingNotifier {
+  fn notify(&mut self, title: &str, body: &str) -> anyhow::Result<()> {
+    self.notifications.lock().map_err(|e| anyhow::anyhow!("{e}"))?.push((title.to_owned(), body.to_owned()));
+    Ok(())
+  }
+}
+
+/// Rate limited notification sender
+pub struct Notifications {
+  notifier: Box<dyn Notifier>,
+  sent: VecDeque<DateTime<Utc>>
+}
+
+impl Default for Notifications {
+  fn default() -> Self {
+    Self::new(Box::new(SystemNotifier))
+  }
+}
+
+impl Notifications {
+  pub fn new(notifier: Box<dyn Notifier>) -> Self {
+    Self { notifier, sent: Default::default() }
+  }
+
+  /// Returns false if the notification was dropped by the rate limit or failed to show
+  pub fn notify(&mut self, title: &str, body: &str, now: DateTime<Utc>) -> bool {
+    while self.sent.front().is_some_and(|t| now.signed_duration_since(t).num_seconds() >= NOTIFICATION_RATE_WINDOW_SECS) {
+      self.sent.pop_front();
+    }
+    if self.sent.len() >= NOTIFICATION_RATE_LIMIT {
+      info!("Dropped notification due to rate limit: {title}");
+      return false;
+    }
+    match self.notifier.notify(title, body) {
+      Ok(()) => {
+        self.sent.push_back(now);
+        true
+      },
+      Err(e) => {
+        warn!("Failed to show notification: {e}");
+        false
+      }
+    }
+  }
+}
@@ -4,28 +4,35 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use async_channel::{Sender, Receiver};
+use async_channel::{Sender, Receiver, TrySendError};
 use egui::{Color32, Context};
 use reqwest::header::{HeaderValue, HeaderName, HeaderMap};
-//use rand::{distributions::{Alphanumeric, DistString}};
+use rand::distributions::{Alphanumeric, DistString};
 use tracing::info;
+use tokio::runtime::Runtime;
 use std::collections::{HashMap, HashSet};
 
 use chrono::{DateTime, Utc};
 use tracing_unwrap::ResultExt;
 
-use crate::{emotes::Emote, ui::{addtl_functions::convert_color, chat, consts::DEFAULT_USER_COLOR}};
+use crate::{emotes::{Emote, EmoteLoader}, ui::{addtl_functions::convert_color, chat, consts::DEFAULT_USER_COLOR, models::AuthTokens}};
 
-use self::channel::ChannelStatus;
+use self::channel::{Channel, ChannelStatus, ChatModes, IrcNetwork};
 
 pub mod twitch;
+pub mod twitch_eventsub;
+pub mod helix;
+pub mod twitch_moderation;
+pub mod twitch_whispers;
 pub mod youtube_server;
+pub mod youtube;
 pub mod dgg;
+pub mod kick;
+pub mod generic_irc;
 pub mod channel;
 
 
 
-#[derive(Clone)]
 pub enum IncomingMessage {
   PrivMsg { message: ChatMessage },
   EmoteSets { provider: ProviderName, emote_sets: Vec<String> },
@@ -35,8 +42,29 @@ pub enum IncomingMessage {
   UserJoin { channel: String, username: String, display_name: String },
   UserLeave { channel: String, username: String, display_name: String },
   UserMuted { channel: String, username: String },
-  VoteStart {  },
-  VoteStop {}
+  MessageDeleted { channel: String, id: String },
+  ChatCleared { channel: String },
+  ChatModes { channel: String, modes: ChatModes },
+  /// Whether the logged in user can moderate the channel
+  ModeratorStatus { channel: String, is_moderator: bool },
+  /// Channel badges that came with the chat connection instead of the emote loader
+  ChannelBadges { channel: String, badges: HashMap<String, Emote> },
+  ChannelEvent { provider: ProviderName, channel: String, event: ChannelEvent },
+  /// Private message sent or received by the logged in user. `conversation` is the other user's name.
+  Whisper { conversation: String, message: ChatMessage },
+  /// Earlier whispers with `conversation` kept by the provider, and the ids of the ones not read yet
+  WhisperHistory { provider: ProviderName, conversation: String, messages: Vec<ChatMessage>, unread_ids: Vec<String> },
+  VoteStart { channel: String, poll: Poll },
+  /// `option` is the index into the poll's options, `quantity` the weight of the vote
+  VoteCast { channel: String, option: usize, quantity: usize },
+  /// The logged in user's vote for `option` was accepted
+  VoteCounted { channel: String, option: usize },
+  /// `poll` has the final totals
+  VoteStop { channel: String, poll: Poll },
+  /// Messages restored from the chat log archive for a newly opened channel, oldest first
+  ArchiveHistory { channel: String, messages: Vec<ChatMessage> },
+  /// Chat log archive messages matching the search started with `search_id`
+  ArchiveSearchResults { search_id: usize, messages: Vec<ChatMessage> }
 }
 
 impl Default for IncomingMessage {
@@ -45,15 +73,136 @@ impl Default for IncomingMessage {
     }
 }
 
+/// Channel activity outside of chat messages, shown in chat as an announcement
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelEvent {
+  Raid { from: String, viewers: usize },
+  Follow { user: String },
+  Redemption { user: String, reward: String, cost: usize, input: Option<String> },
+  PollBegin { title: String, choices: Vec<String> },
+  PollEnd { title: String, results: Vec<(String, usize)> },
+  PredictionBegin { title: String, outcomes: Vec<String> },
+  PredictionLock { title: String },
+  /// No winner if the prediction was canceled
+  PredictionEnd { title: String, winner: Option<String> },
+  HypeTrainBegin { level: usize },
+  HypeTrainEnd { level: usize }
+}
+
+impl ChannelEvent {
+  pub fn message(&self) -> String {
+    match self {
+      ChannelEvent::Raid { from, viewers } => format!("{from} is raiding with {viewers} viewers"),
+      ChannelEvent::Follow { user } => format!("{user} followed"),
+      ChannelEvent::Redemption { user, reward, cost, input } => match input {
+        Some(input) => format!("{user} redeemed {reward} ({cost}): {input}"),
+        None => format!("{user} redeemed {reward} ({cost})")
+      },
+      ChannelEvent::PollBegin { title, choices } => format!("Poll started: {title} ({})", choices.join(" / ")),
+      ChannelEvent::PollEnd { title, results } => format!("Poll ended: {title} ({})", results.iter().map(|(choice, votes)| format!("{choice}: {votes}")).collect::<Vec<_>>().join(", ")),
+      ChannelEvent::PredictionBegin { title, outcomes } => format!("Prediction started: {title} ({})", outcomes.join(" / ")),
+      ChannelEvent::PredictionLock { title } => format!("Prediction locked: {title}"),
+      ChannelEvent::PredictionEnd { title, winner: Some(winner) } => format!("Prediction ended: {title}, {winner} won"),
+      ChannelEvent::PredictionEnd { title, winner: None } => format!("Prediction canceled: {title}"),
+      ChannelEvent::HypeTrainBegin { level } => format!("Hype Train started at level {level}"),
+      ChannelEvent::HypeTrainEnd { level } => format!("Hype Train ended at level {level}")
+    }
+  }
+}
+
+/// Poll running in a channel that can be voted on from the app
+#[derive(Clone, Debug, PartialEq)]
+pub struct Poll {
+  pub question: String,
+  pub options: Vec<String>,
+  /// Votes for each option, in the same order as `options`
+  pub totals: Vec<usize>,
+  pub started_by: String,
+  pub ends_at: DateTime<Utc>,
+  /// Votes from subscribers count more
+  pub weighted: bool,
+  pub can_vote: bool,
+  /// Index of the option the user voted for
+  pub my_vote: Option<usize>
+}
+
+impl Poll {
+  pub fn total_votes(&self) -> usize {
+    self.totals.iter().sum()
+  }
+
+  pub fn add_vote(&mut self, option: usize, quantity: usize) {
+    if let Some(total) = self.totals.get_mut(option) {
+      *total += quantity;
+    }
+  }
+
+  pub fn seconds_left(&self, now: DateTime<Utc>) -> i64 {
+    self.ends_at.signed_duration_since(now).num_seconds().max(0)
+  }
+
+  /// Announcement posted in chat once the poll is over
+  pub fn end_event(&self) -> ChannelEvent {
+    ChannelEvent::PollEnd { title: self.question.to_owned(), results: self.options.iter().cloned().zip(self.totals.iter().copied()).collect() }
+  }
+}
+
 #[derive(Debug)]
 pub enum OutgoingMessage {
   Chat { channel: String, message: String },
+  Reply { channel: String, message: String, reply_to: ReplyParent },
+  Moderate { channel: String, action: ModerationAction },
+  Whisper { username: String, message: String },
+  WhisperHistory { username: String },
+  WhisperRead { ids: Vec<String> },
+  Vote { channel: String, option: usize },
   Leave { channel_name: String },
   Join { channel_name: String },
   TwitchJoin { channel_name: String, room_id: Option<String>, show_offline_chat: bool },
   Quit { }
 }
 
+/// Moderator action on a user or a single message
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModerationAction {
+  Timeout { username: String, duration_sec: u32 },
+  Ban { username: String },
+  Unban { username: String },
+  DeleteMessage { id: String, username: String }
+}
+
+impl ModerationAction {
+  pub fn username(&self) -> &str {
+    match self {
+      ModerationAction::Timeout { username, .. } 
+      | ModerationAction::Ban { username } 
+      | ModerationAction::Unban { username } 
+      | ModerationAction::DeleteMessage { username, .. } => username
+    }
+  }
+
+  /// Shown in chat once the chat service has accepted the action
+  pub fn success_message(&self) -> String {
+    match self {
+      ModerationAction::Timeout { username, duration_sec } => format!("Timed out {username} for {duration_sec} seconds."),
+      ModerationAction::Ban { username } => format!("Banned {username}."),
+      ModerationAction::Unban { username } => format!("Unbanned {username}."),
+      ModerationAction::DeleteMessage { username, .. } => format!("Deleted message from {username}.")
+    }
+  }
+
+  /// Shown in chat when the action failed
+  pub fn failure_message(&self, error: &str) -> String {
+    let action = match self {
+      ModerationAction::Timeout { .. } => "timeout",
+      ModerationAction::Ban { .. } => "ban",
+      ModerationAction::Unban { .. } => "unban",
+      ModerationAction::DeleteMessage { .. } => "delete message from"
+    };
+    format!("Failed to {action} {}: {error}", self.username())
+  }
+}
+
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 pub struct Provider {
   pub name: String,
@@ -68,7 +217,7 @@ pub struct Provider {
   pub my_emote_sets: Vec<String>
 }
 
-#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Default)]
 #[derive(Eq, Hash, PartialEq)]
 #[derive(Clone)]
@@ -77,6 +226,75 @@ pub enum ProviderName {
   Twitch,
   DGG,
   YouTube,
+  Kick,
+  Irc,
+}
+
+impl ProviderName {
+  /// Every provider, in the order they are listed in menus
+  pub const ALL : [ProviderName; 5] = [ProviderName::Twitch, ProviderName::DGG, ProviderName::YouTube, ProviderName::Kick, ProviderName::Irc];
+
+  pub fn info(&self) -> &'static dyn ProviderInfo {
+    match self {
+      ProviderName::Twitch => &twitch::TwitchInfo,
+      ProviderName::DGG => &dgg::DggInfo,
+      ProviderName::YouTube => &youtube::YoutubeInfo,
+      ProviderName::Kick => &kick::KickInfo,
+      ProviderName::Irc => &generic_irc::IrcInfo
+    }
+  }
+
+  pub fn display_name(&self) -> &'static str {
+    self.info().display_name()
+  }
+}
+
+/// Fixed details of a platform, for messages and channels whether or not the platform is connected.
+/// Each provider module implements this on a unit struct returned by `ProviderName::info`.
+pub trait ProviderInfo : Sync {
+  fn display_name(&self) -> &'static str;
+
+  /// Folder the platform's chat logs are archived in
+  fn archive_dir(&self) -> &'static str;
+
+  /// Used for the platform's channel names and announcements
+  fn color(&self) -> Color32;
+
+  /// Create a channel to add to the channel list. `network` is only used by IRC.
+  fn init_channel(&self, channel_name: &str, network: IrcNetwork) -> Channel;
+
+  /// The platform only has one chat, so channels can't be added by name
+  fn single_channel(&self) -> bool { false }
+
+  /// Hint for the channel name in the Add Channel menu
+  fn channel_name_hint(&self) -> &'static str { "" }
+
+  /// Channel for a chat the platform pushed that is not in the channel list, e.g. from the YouTube Tampermonkey script.
+  /// None if such chats don't get a tab of their own.
+  fn init_pushed_channel(&self, _channel_name: &str) -> Option<Channel> { None }
+
+  /// Whether global 3rd party emotes are shown in the platform's chats
+  fn uses_global_emotes(&self) -> bool { true }
+
+  /// Message context menu actions that can be sent to the platform
+  fn supports_replies(&self) -> bool { false }
+  fn supports_moderation(&self) -> bool { false }
+  fn supports_message_deletion(&self) -> bool { false }
+
+  /// Clean up the text of a received message before it is shown
+  fn clean_message(&self, message: String) -> String { message }
+
+  /// Text color for messages with special formatting, e.g. greentext
+  fn message_color(&self, _message: &str) -> Option<(u8, u8, u8)> { None }
+
+  /// Hover text for a user's badge
+  fn badge_tooltip(&self, _badge: &Emote) -> Option<String> { None }
+
+  /// URL to open for a link in a message
+  fn link_url(&self, link: &str) -> String { link.to_owned() }
+
+  /// Emote for an id and name sent with `IncomingMessage::MsgEmotes`
+  fn message_emote(&self, _id: String, _name: String) -> Option<Emote> { None }
 }
 
 pub trait ChatManagerRx {
@@ -84,8 +302,101 @@ pub trait ChatManagerRx {
   fn out_rx(&mut self) -> &mut Receiver<IncomingMessage>;
 }
 
+/// A connection to a chat platform. The UI only talks to providers through this trait and `ProviderInfo`,
+/// so adding a platform means implementing both and adding a case to `connect` and `ProviderName::info`.
+pub trait ChatProvider : ChatManagerRx {
+  fn provider_name(&self) -> ProviderName;
+
+  /// Logged in user, empty if connected anonymously
+  fn username(&self) -> &str;
+
+  fn is_logged_in(&self) -> bool {
+    !self.username().is_empty()
+  }
+
+  /// Whether messages can be sent to the channel, with the reason shown in chat if not
+  fn can_send(&self, _channel_name: &str) -> Result<(), String> {
+    Ok(())
+  }
+
+  /// Start receiving chat for the channel and set up its transient state
+  fn open_channel(&mut self, channel: &mut Channel);
+
+  fn leave_channel(&mut self, channel_name: &str) {
+    if let Err(e) = self.in_tx().try_send(OutgoingMessage::Leave { channel_name: channel_name.to_owned() }) {
+      info!("Failed to leave channel {}: {}", channel_name, e);
+    }
+  }
+
+  fn send_chat(&mut self, channel_name: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.in_tx().try_send(OutgoingMessage::Chat { channel: channel_name.to_owned(), message: message.to_owned() })
+  }
+
+  /// Send a timeout, ban, unban or message deletion for the channel
+  fn send_moderation(&mut self, channel_name: &str, action: ModerationAction) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.in_tx().try_send(OutgoingMessage::Moderate { channel: channel_name.to_owned(), action })
+  }
+
+  /// Send a message as a reply to another message, or as a plain message if the provider has no reply threads
+  fn send_reply(&mut self, channel_name: &str, message: &str, _reply_to: &ChatMessage) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.send_chat(channel_name, message)
+  }
+
+  /// Send a /me message, as a CTCP ACTION unless the provider has its own format
+  fn send_action(&mut self, channel_name: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.send_chat(channel_name, &format!("\u{1}ACTION {message}\u{1}"))
+  }
+
+  /// Send a private message, only for providers that list "w" in `supported_commands`
+  fn send_whisper(&mut self, username: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.in_tx().try_send(OutgoingMessage::Whisper { username: username.to_owned(), message: message.to_owned() })
+  }
+
+  /// Vote for an option of the poll running in the channel, by its index
+  fn send_vote(&mut self, channel_name: &str, option: usize) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.in_tx().try_send(OutgoingMessage::Vote { channel: channel_name.to_owned(), option })
+  }
+
+  /// Load earlier whispers with the user, for providers that keep them server side
+  fn request_whisper_history(&mut self, _username: &str) {}
+
+  /// Mark received whispers as read on the provider's side
+  fn mark_whispers_read(&mut self, _ids: Vec<String>) {}
+
+  /// Names of the slash commands that go through this provider, e.g. "me" or "ban".
+  /// Commands that only act on the client (/clear, /search, ...) work everywhere and are not listed.
+  fn supported_commands(&self) -> &'static [&'static str] { &[] }
+
+  /// Request provider wide emotes and badges from the emote loader
+  fn request_global_emotes(&self, _emote_loader: &EmoteLoader, _force_redownload: bool) {}
+
+  /// Request channel emotes and badges from the emote loader
+  fn request_channel_emotes(&self, _channel: &Channel, _emote_loader: &EmoteLoader, _force_redownload: bool) {}
+
+  fn close(&mut self);
+}
+
+/// Connect to a provider's chat with the saved logins. Returns None if the provider requires a login that is missing.
+/// `enable_yt_integration` also starts the local server the YouTube Tampermonkey script pushes chat to.
+pub fn connect(provider: &ProviderName, auth_tokens: &AuthTokens, enable_yt_integration: bool, runtime: &Runtime, ctx: &Context) -> Option<Box<dyn ChatProvider>> {
+  match provider {
+    ProviderName::Twitch => {
+      if auth_tokens.twitch_username.is_empty() || auth_tokens.twitch_auth_token.is_empty() {
+        None
+      } else {
+        Some(Box::new(twitch::TwitchChatManager::new(&auth_tokens.twitch_username, &auth_tokens.twitch_auth_token, runtime, ctx)))
+      }
+    },
+    ProviderName::DGG => Some(Box::new(dgg::DggChatManager::new(&auth_tokens.dgg_username, &auth_tokens.dgg_auth_token, runtime, ctx))),
+    ProviderName::YouTube => Some(Box::new(youtube::YoutubeChatManager::new(Default::default(), enable_yt_integration, runtime, ctx))),
+    ProviderName::Kick => Some(Box::new(kick::KickChatManager::new(runtime, ctx))),
+    ProviderName::Irc => Some(Box::new(generic_irc::IrcChatManager::new(runtime, ctx)))
+  }
+}
+
 pub struct ChatManager {
   handles: Vec<tokio::task::JoinHandle<()>>,
+  pub provider: ProviderName,
   pub username: String,
   pub in_tx: Sender<OutgoingMessage>,
   pub out_rx: Receiver<IncomingMessage>,
@@ -99,6 +410,32 @@ impl ChatManagerRx for ChatManager {
   }
 }
 
+impl ChatProvider for ChatManager {
+  fn provider_name(&self) -> ProviderName {
+    self.provider.to_owned()
+  }
+
+  fn username(&self) -> &str {
+    &self.username
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let shared = channel.shared_mut();
+    if shared.transient.is_none() {
+      shared.transient = Some(Default::default());
+    }
+  }
+
+  fn close(&mut self) {
+    if let Err(e) = self.in_tx.try_send(OutgoingMessage::Quit {}) {
+      info!("Failed to send quit: {}", e);
+    }
+    for handle in self.handles.iter_mut() {
+      handle.abort();
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct ComboCounter {
   pub word: String,
@@ -109,6 +446,18 @@ pub struct ComboCounter {
   pub cached_height: Option<f32>
 }
 
+/// What a matching message filter does with a message
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub enum FilterAction {
+  #[default]
+  Hide,
+  /// Shown as a one line placeholder
+  Collapse,
+  /// Covered until hovered, e.g. for spoilers
+  Blur
+}
+
 #[derive(Clone)]
 pub struct ChatMessage {
   pub provider: ProviderName,
@@ -119,10 +468,34 @@ pub struct ChatMessage {
   pub profile: UserProfile,
   pub combo_data: Option<ComboCounter>,
   pub is_removed: Option<String>,
-  pub msg_type: MessageType
-  //pub unique_id: String
+  pub msg_type: MessageType,
+  pub from_archive: bool,
+  /// Id assigned by the chat service, or a random one if the service has none
+  pub id: String,
+  pub reply_to: Option<ReplyParent>,
+  /// Set from the first matching message filter when added to the chat history
+  pub filtered: Option<FilterAction>
+}
+
+/// The message that a chat message is replying to
+#[derive(Clone, Debug)]
+pub struct ReplyParent {
+  pub id: String,
+  pub username: String,
+  pub message: String
+}
+
+impl From<&ChatMessage> for ReplyParent {
+  fn from(msg: &ChatMessage) -> Self {
+    Self {
+      id: msg.id.to_owned(),
+      username: msg.profile.display_name.as_ref().unwrap_or(&msg.username).to_owned(),
+      message: msg.message.to_owned()
+    }
+  }
 }
 
+#[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Default)]
 #[derive(Clone)]
 #[derive(Eq, Hash, PartialEq)]
@@ -144,12 +517,25 @@ impl Default for ChatMessage {
       profile: Default::default(),
       combo_data: None,
       is_removed: None,
-      msg_type: MessageType::Chat
-      //unique_id: Alphanumeric.sample_string(&mut rand::thread_rng(), 16)
+      msg_type: MessageType::Chat,
+      from_archive: false,
+      id: new_message_id(),
+      reply_to: None,
+      filtered: None
     }
   }
 }
 
+/// Text of a CTCP ACTION (/me) message without its markers, or the message unchanged
+pub fn strip_ctcp_action(message: &str) -> &str {
+  message.strip_prefix("\u{1}ACTION ").map(|m| m.trim_end_matches('\u{1}')).unwrap_or(message)
+}
+
+/// Random id for messages that do not have one from the chat service
+pub fn new_message_id() -> String {
+  Alphanumeric.sample_string(&mut rand::thread_rng(), 16)
+}
+
 impl ChatMessage {
     pub fn get_username_with_color(&self) -> Option<(&String, Color32)> {
         chat::determine_name_to_display(self)
@@ -6,11 +6,9 @@
 
 use std::collections::HashMap;
 
-use async_channel::Sender;
-
 use crate::emotes::Emote;
 
-use super::{ProviderName, ChatManager, ChatManagerRx, OutgoingMessage};
+use super::ProviderName;
 
 #[derive(Default)]
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
@@ -18,6 +16,8 @@ pub struct ChannelShared {
   pub channel_name: String,
   pub show_in_mentions_tab: bool,
   pub show_tab_when_offline: bool,
+  #[cfg_attr(feature = "persistence", serde(default))]
+  pub mute_notifications: bool,
   #[cfg_attr(feature = "persistence", serde(skip))]
   pub send_history: Vec<String>,
   #[cfg_attr(feature = "persistence", serde(skip))]
@@ -38,39 +38,31 @@ impl ChannelShared {
 pub enum Channel {
   Twitch { twitch: TwitchChannel, shared: ChannelShared },
   DGG { dgg: DggChannel, shared: ChannelShared },
-  Youtube { youtube: YoutubeChannel, shared: ChannelShared }
+  Youtube { youtube: YoutubeChannel, shared: ChannelShared },
+  Kick { kick: KickChannel, shared: ChannelShared },
+  Irc { irc: IrcChannel, shared: ChannelShared }
 }
 
 impl Channel {
   pub fn channel_name(&self) -> &String {
-    match self {
-      Channel::DGG { dgg: _, shared } => &shared.channel_name,
-      Channel::Twitch { twitch: _, shared } => &shared.channel_name,
-      Channel::Youtube { youtube: _, shared } => &shared.channel_name
-    }
+    &self.shared().channel_name
   }
 
   pub fn transient(&self) -> Option<&ChannelTransient> {
-    match self {
-      Channel::DGG { dgg: _, shared } => shared.transient.as_ref(),
-      Channel::Twitch { twitch: _, shared } => shared.transient.as_ref(),
-      Channel::Youtube { youtube: _, shared } => shared.transient.as_ref()
-    }
+    self.shared().transient.as_ref()
   }
 
   pub fn transient_mut(&mut self) -> Option<&mut ChannelTransient> {
-    match self {
-      Channel::DGG { dgg: _, ref mut shared } => shared.transient_mut(),
-      Channel::Twitch { twitch: _, ref mut shared } => shared.transient_mut(),
-      Channel::Youtube { youtube: _, ref mut shared } => shared.transient_mut()
-    }
+    self.shared_mut().transient_mut()
   }
 
   pub fn shared(&self) -> &ChannelShared {
     match self {
       Channel::DGG { dgg: _, shared } => shared,
       Channel::Twitch { twitch: _, shared } => shared,
-      Channel::Youtube { youtube: _, shared } => shared
+      Channel::Youtube { youtube: _, shared } => shared,
+      Channel::Kick { kick: _, shared } => shared,
+      Channel::Irc { irc: _, shared } => shared
     }
   }
 
@@ -78,7 +70,9 @@ impl Channel {
     match self {
       Channel::DGG { dgg: _, ref mut shared } => shared,
       Channel::Twitch { twitch: _, ref mut shared } => shared,
-      Channel::Youtube { youtube: _, ref mut shared } => shared
+      Channel::Youtube { youtube: _, ref mut shared } => shared,
+      Channel::Kick { kick: _, ref mut shared } => shared,
+      Channel::Irc { irc: _, ref mut shared } => shared
     }
   }
 
@@ -86,23 +80,9 @@ impl Channel {
     match self {
       Channel::DGG { dgg: _, shared: _ } => ProviderName::DGG,
       Channel::Twitch { twitch: _, shared: _ } => ProviderName::Twitch,
-      Channel::Youtube { youtube: _, shared: _ } => ProviderName::YouTube
-    }
-  }
-  
-  pub fn close(&mut self) {
-    match self {
-      Channel::DGG { ref mut dgg, shared: _ } => if let Some(chat_mgr) = dgg.dgg_chat_manager.as_mut() { chat_mgr.close(); },
-      Channel::Twitch { twitch: _, shared: _ } => {},
-      Channel::Youtube { youtube: _, shared: _ } => {}
-    }
-  }
-
-  pub fn chat_mgr_mut(&mut self) -> Option<&mut Sender<OutgoingMessage>> {
-    match self {
-      Channel::Twitch { twitch: _, shared: _ } => None,
-      Channel::DGG { ref mut dgg, shared: _ } => dgg.dgg_chat_manager.as_mut().map(|m| m.in_tx()),
-      Channel::Youtube { youtube: _, shared: _ } => None
+      Channel::Youtube { youtube: _, shared: _ } => ProviderName::YouTube,
+      Channel::Kick { kick: _, shared: _ } => ProviderName::Kick,
+      Channel::Irc { irc: _, shared: _ } => ProviderName::Irc
     }
   }
 }
@@ -118,20 +98,51 @@ pub struct TwitchChannel {
 pub struct DggChannel {
   pub dgg_chat_url: String, 
   pub dgg_status_url: String, 
-  pub dgg_cdn_url: String,
-  #[cfg_attr(feature = "persistence", serde(skip))]
-  pub dgg_chat_manager: Option<ChatManager>
+  pub dgg_cdn_url: String
 }
 
 #[derive(Default)]
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 pub struct YoutubeChannel {
+  /// Channel or video to poll live chat for. None for chats pushed by the Tampermonkey script.
+  #[cfg_attr(feature = "persistence", serde(default))]
+  pub target: Option<String>
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub struct KickChannel {
+  pub slug: String
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub struct IrcChannel {
+  pub network: IrcNetwork,
+  pub channel: String
+}
+
+/// Connection settings for an IRC server. Channels with the same settings share one connection.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub struct IrcNetwork {
+  pub server: String,
+  pub port: u16,
+  pub use_tls: bool,
+  pub nickname: String,
+  /// Sent as the server password, or as the account password if `use_sasl` is set
+  pub password: String,
+  pub use_sasl: bool
 }
 
+#[derive(Default)]
 pub struct ChannelTransient {
   pub channel_emotes: Option<HashMap<String, Emote>>,
   pub badge_emotes: Option<HashMap<String, Emote>>,
-  pub status: Option<ChannelStatus>
+  pub status: Option<ChannelStatus>,
+  pub chat_modes: ChatModes,
+  /// The logged in user can moderate this channel
+  pub is_moderator: bool
 }
 
 pub struct ChannelUser {
@@ -146,5 +157,134 @@ pub struct ChannelStatus {
   pub is_live: bool,
   pub title: Option<String>,
   pub viewer_count: Option<usize>,
-  pub started_at: Option<String>
-}
\ No newline at end of file
+  pub started_at: Option<String>,
+  /// Each platform the channel streams to, for channels that stream to several at once
+  pub platforms: Vec<StreamPlatform>,
+  /// Streams the channel's chat is watching together
+  pub embeds: Vec<StreamEmbed>
+}
+
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct StreamPlatform {
+  /// e.g. "YouTube" or "Kick"
+  pub platform: String,
+  pub is_live: bool,
+  pub title: Option<String>,
+  pub viewer_count: Option<usize>,
+  pub started_at: Option<String>,
+  pub url: Option<String>
+}
+
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct StreamEmbed {
+  pub platform: String,
+  pub id: String,
+  pub title: Option<String>,
+  pub channel: Option<String>,
+  /// Number of chatters watching the embed
+  pub watchers: usize,
+  pub url: String
+}
+
+impl StreamEmbed {
+  pub fn label(&self) -> String {
+    let name = self.title.as_ref().or(self.channel.as_ref()).unwrap_or(&self.id);
+    format!("{name} ({}, {} watching)", self.platform, self.watchers)
+  }
+}
+
+/// Difference between two statuses of a channel that is shown in chat
+#[derive(Clone,Debug,PartialEq)]
+pub enum StatusChange {
+  WentLive { title: Option<String>, game_name: Option<String> },
+  Title(String),
+  Game(String)
+}
+
+impl StatusChange {
+  pub fn message(&self, channel: &str) -> String {
+    match self {
+      StatusChange::WentLive { title: Some(title), game_name: Some(game) } => format!("{channel} went live: {title} ({game})"),
+      StatusChange::WentLive { title: Some(title), game_name: None } => format!("{channel} went live: {title}"),
+      StatusChange::WentLive { .. } => format!("{channel} went live"),
+      StatusChange::Title(title) => format!("Title changed to: {title}"),
+      StatusChange::Game(game) => format!("Category changed to: {game}")
+    }
+  }
+}
+
+impl ChannelStatus {
+  /// Changes since the previous status. Title and game changes are only reported 
+  /// if both statuses have them, as some status updates only carry the live state.
+  pub fn changes_since(&self, previous: &ChannelStatus) -> Vec<StatusChange> {
+    if self.is_live && !previous.is_live {
+      return vec![StatusChange::WentLive { title: self.title.to_owned(), game_name: self.game_name.to_owned() }];
+    }
+    let mut changes = Vec::new();
+    if let Some(title) = self.title.as_ref() && previous.title.as_ref().is_some_and(|t| t != title) {
+      changes.push(StatusChange::Title(title.to_owned()));
+    }
+    if let Some(game) = self.game_name.as_ref() && previous.game_name.as_ref().is_some_and(|g| g != game) {
+      changes.push(StatusChange::Game(game.to_owned()));
+    }
+    changes
+  }
+}
+
+/// Chat restrictions currently set on a channel
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct ChatModes {
+  /// Seconds users have to wait between messages
+  pub slow: Option<u32>,
+  /// Minutes users have to have followed for, 0 meaning any follower
+  pub followers_only: Option<u32>,
+  pub emote_only: bool,
+  pub subs_only: bool
+}
+
+impl ChatModes {
+  /// Short label for each active mode, e.g. "slow 30s"
+  pub fn labels(&self) -> Vec<String> {
+    let mut labels = Vec::new();
+    if let Some(sec) = self.slow {
+      labels.push(format!("slow {sec}s"));
+    }
+    match self.followers_only {
+      Some(0) => labels.push("followers-only".to_owned()),
+      Some(min) => labels.push(format!("followers-only {min}m")),
+      None => ()
+    }
+    if self.emote_only {
+      labels.push("emote-only".to_owned());
+    }
+    if self.subs_only {
+      labels.push("subs-only".to_owned());
+    }
+    labels
+  }
+
+  /// Describes each mode that differs from `previous`
+  pub fn changes_from(&self, previous: &ChatModes) -> Vec<String> {
+    let mut changes = Vec::new();
+    if self.slow != previous.slow {
+      changes.push(match self.slow {
+        Some(sec) => format!("Slow mode is on, {sec} seconds between messages."),
+        None => "Slow mode is off.".to_owned()
+      });
+    }
+    if self.followers_only != previous.followers_only {
+      changes.push(match self.followers_only {
+        Some(0) => "Followers-only mode is on.".to_owned(),
+        Some(min) => format!("Followers-only mode is on, must follow for {min} minutes."),
+        None => "Followers-only mode is off.".to_owned()
+      });
+    }
+    if self.emote_only != previous.emote_only {
+      changes.push(format!("Emote-only mode is {}.", if self.emote_only { "on" } else { "off" }));
+    }
+    if self.subs_only != previous.subs_only {
+      changes.push(format!("Subscribers-only mode is {}.", if self.subs_only { "on" } else { "off" }));
+    }
+    changes
+  }
+}
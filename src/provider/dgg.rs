@@ -4,24 +4,26 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::{collections::HashMap, path::Path};
-use async_channel::{Sender, Receiver};
+use std::{collections::{HashMap, VecDeque}, path::Path};
+use async_channel::{Sender, Receiver, TrySendError};
 use backoff::backoff::Backoff;
 use chrono::DateTime;
-use egui::Context;
+use egui::{Color32, Context};
 use futures::{StreamExt, SinkExt};
 use itertools::Itertools;
 use tracing::{trace, info,warn,error, debug};
 use crate::{provider::MessageType, emotes::{EmoteRequest, EmoteSource}};
 use regex::Regex;
-use tokio::{runtime::Runtime, time::sleep, time::Duration};
+use tokio::{runtime::{Handle, Runtime}, time::sleep, time::Duration};
 use tokio_tungstenite::{tungstenite::{http::header::COOKIE, client::IntoClientRequest, Message}, connect_async_tls_with_config};
-use crate::{emotes::{fetch, Emote, EmoteLoader, CssAnimationData}, provider::ChannelStatus};
-use super::{IncomingMessage, OutgoingMessage, ProviderName, ChatMessage, UserProfile, make_request, ChatManager, convert_color_hex, channel::{Channel, ChannelTransient, DggChannel, ChannelShared}};
+use crate::{emotes::{fetch, Emote, EmoteLoader, CssAnimationData}, provider::{ChannelStatus, channel::{StreamEmbed, StreamPlatform}}};
+use super::{IncomingMessage, ModerationAction, OutgoingMessage, Poll, ProviderName, ChatMessage, UserProfile, make_request, ChatManagerRx, ChatProvider, ProviderInfo, convert_color_hex, channel::{Channel, ChannelTransient, ChatModes, DggChannel, ChannelShared, IrcNetwork}};
 use tracing_unwrap::{OptionExt, ResultExt};
 use base64::{Engine as _, engine::general_purpose};
 
 pub const DGG_CHANNEL_NAME : &str = "Destiny";
+const DGG_API_URL : &str = "https://www.destiny.gg/api";
+
 
 pub fn init_channel() -> Channel {
   Channel::DGG {  
@@ -29,6 +31,7 @@ pub fn init_channel() -> Channel {
       channel_name: DGG_CHANNEL_NAME.to_owned(),
       show_in_mentions_tab: true,
       show_tab_when_offline: true,
+      mute_notifications: false,
       send_history: Default::default(),
       send_history_ix: None,
       transient: None,
@@ -37,116 +40,257 @@ pub fn init_channel() -> Channel {
     dgg: DggChannel {
       dgg_cdn_url: "https://cdn.destiny.gg/2.42.0/".to_owned(),
       dgg_status_url: "wss://live.destiny.gg/ws".to_owned(),
-      dgg_chat_url: "wss://chat.destiny.gg/ws".to_owned(),
-      dgg_chat_manager: None
+      dgg_chat_url: "wss://chat.destiny.gg/ws".to_owned()
     }
   }
 }
 
-pub fn open_channel(user_name: &String, token: &String, dgg: &DggChannel, channel: &mut ChannelShared, runtime: &Runtime, emote_loader: &EmoteLoader, ctx: &Context) -> ChatManager {
-  let (out_tx, out_rx) = async_channel::bounded::<IncomingMessage>(10000);
-  let (in_tx, in_rx) = async_channel::bounded::<OutgoingMessage>(10000);
-
-  let status_url = dgg.dgg_status_url.to_owned();
-  let chat_url = dgg.dgg_chat_url.to_owned();
-  let status_ctx = ctx.clone();
-  let ctx = ctx.clone();
-
-  let out_tx_2 = out_tx.clone();
-  let handle2 = runtime.spawn(async move { 
-    let mut backoff = backoff::ExponentialBackoffBuilder::new()
-    .with_initial_interval(Duration::from_millis(3000))
-    .with_max_interval(Duration::from_millis(60000))
-    .with_max_elapsed_time(None)
-    .with_randomization_factor(0.)
-    .build();
-    
-    loop {
-      let retry_wait = backoff.next_backoff();
-      match spawn_websocket_live_client(&status_url, &out_tx_2, &status_ctx).await {
-        Ok(x) => if x { break; } else { backoff.reset(); backoff.next_backoff(); warn!("Lost connection to DGG status websocket, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())); },
-        Err(x) => error!("error connecting to DGG channel status websocket: {:?}", x)
-      }
-      if let Some(duration) = retry_wait {
-        sleep(duration).await;
-      }
+pub struct DggInfo;
+
+impl ProviderInfo for DggInfo {
+  fn display_name(&self) -> &'static str {
+    "destiny.gg"
+  }
+
+  fn archive_dir(&self) -> &'static str {
+    "dgg"
+  }
+
+  fn color(&self) -> Color32 {
+    Color32::from_rgba_unmultiplied(83, 140, 198, 255)
+  }
+
+  fn init_channel(&self, _channel_name: &str, _network: IrcNetwork) -> Channel {
+    init_channel()
+  }
+
+  fn single_channel(&self) -> bool {
+    true
+  }
+
+  fn uses_global_emotes(&self) -> bool {
+    false
+  }
+
+  // DGG can only mute/ban users
+  fn supports_moderation(&self) -> bool {
+    true
+  }
+
+  fn message_color(&self, message: &str) -> Option<(u8, u8, u8)> {
+    // greentext
+    message.starts_with('>').then_some((99, 151, 37))
+  }
+
+  fn badge_tooltip(&self, badge: &Emote) -> Option<String> {
+    Some(badge.display_name.as_ref().unwrap_or(&badge.name).to_owned())
+  }
+
+  fn link_url(&self, link: &str) -> String {
+    match link.split('/').collect_tuple() {
+      Some(("#twitch", channel)) => format!("https://twitch.tv/{channel}"),
+      Some(("#youtube", video)) => format!("https://www.youtube.com/watch?v={video}"),
+      _ => link.to_owned()
     }
-    warn!("exiting websocket_live thread");
-  });
-
-  let name1 = user_name.to_owned();
-  let token1 = token.to_owned();
-  let handle = runtime.spawn(async move { 
-    let mut backoff = backoff::ExponentialBackoffBuilder::new()
-    .with_initial_interval(Duration::from_millis(3000))
-    .with_max_interval(Duration::from_millis(60000))
-    .with_max_elapsed_time(None)
-    .with_randomization_factor(0.)
-    .build();
-
-    loop {
-      let retry_wait = backoff.next_backoff();
-      match spawn_websocket_chat_client(&chat_url, &name1, &token1, &out_tx, &in_rx, &ctx).await {
-        Ok(x) => if x { break; } else { 
-          backoff.reset();
-          backoff.next_backoff();
-          super::display_system_message_in_chat(
-            &out_tx, 
-            DGG_CHANNEL_NAME.to_owned(), 
-            ProviderName::DGG, 
-            format!("Lost connection, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())),
-            MessageType::Error,
-            &ctx);
-        },
-        Err(e) => { 
-          error!("error connecting to DGG channel status websocket: {:?}", e);
-          //super::display_system_message_in_chat(&out_tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, format!("Error: {}", e), MessageType::Error);
-          super::display_system_message_in_chat(
-            &out_tx, 
-            DGG_CHANNEL_NAME.to_owned(), 
-            ProviderName::DGG, 
-            format!("Failed to connect, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())),
-            MessageType::Error,
-            &ctx);
+  }
+}
+
+pub struct DggChatManager {
+  handles: HashMap<String, Vec<tokio::task::JoinHandle<()>>>,
+  pub username: String,
+  token: String,
+  runtime: Handle,
+  ctx: Context,
+  out_tx: Sender<IncomingMessage>,
+  pub out_rx: Receiver<IncomingMessage>,
+  pub in_tx: Sender<OutgoingMessage>,
+  in_rx: Receiver<OutgoingMessage>
+}
+
+impl DggChatManager {
+  pub fn new(user_name: &str, token: &str, runtime: &Runtime, ctx: &Context) -> Self {
+    let (out_tx, out_rx) = async_channel::bounded::<IncomingMessage>(10000);
+    let (in_tx, in_rx) = async_channel::bounded::<OutgoingMessage>(10000);
+
+    Self {
+      handles: Default::default(),
+      username: user_name.to_owned(),
+      token: token.to_owned(),
+      runtime: runtime.handle().clone(),
+      ctx: ctx.clone(),
+      out_tx,
+      out_rx,
+      in_tx,
+      in_rx
+    }
+  }
+
+  fn spawn_channel_clients(&self, dgg: &DggChannel) -> Vec<tokio::task::JoinHandle<()>> {
+    let status_url = dgg.dgg_status_url.to_owned();
+    let chat_url = dgg.dgg_chat_url.to_owned();
+    let status_ctx = self.ctx.clone();
+    let ctx = self.ctx.clone();
+
+    let out_tx_2 = self.out_tx.clone();
+    let handle2 = self.runtime.spawn(async move { 
+      let mut backoff = backoff::ExponentialBackoffBuilder::new()
+      .with_initial_interval(Duration::from_millis(3000))
+      .with_max_interval(Duration::from_millis(60000))
+      .with_max_elapsed_time(None)
+      .with_randomization_factor(0.)
+      .build();
+      
+      loop {
+        let retry_wait = backoff.next_backoff();
+        match spawn_websocket_live_client(&status_url, &out_tx_2, &status_ctx).await {
+          Ok(x) => if x { break; } else { backoff.reset(); backoff.next_backoff(); warn!("Lost connection to DGG status websocket, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())); },
+          Err(x) => error!("error connecting to DGG channel status websocket: {:?}", x)
+        }
+        if let Some(duration) = retry_wait {
+          sleep(duration).await;
         }
       }
-      if let Some(duration) = retry_wait {
-        sleep(duration).await;
+      warn!("exiting websocket_live thread");
+    });
+
+    let name1 = self.username.to_owned();
+    let token1 = self.token.to_owned();
+    let out_tx = self.out_tx.clone();
+    let in_rx = self.in_rx.clone();
+    let handle = self.runtime.spawn(async move { 
+      let mut backoff = backoff::ExponentialBackoffBuilder::new()
+      .with_initial_interval(Duration::from_millis(3000))
+      .with_max_interval(Duration::from_millis(60000))
+      .with_max_elapsed_time(None)
+      .with_randomization_factor(0.)
+      .build();
+
+      loop {
+        let retry_wait = backoff.next_backoff();
+        match spawn_websocket_chat_client(&chat_url, &name1, &token1, &out_tx, &in_rx, &ctx).await {
+          Ok(x) => if x { break; } else { 
+            backoff.reset();
+            backoff.next_backoff();
+            super::display_system_message_in_chat(
+              &out_tx, 
+              DGG_CHANNEL_NAME.to_owned(), 
+              ProviderName::DGG, 
+              format!("Lost connection, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())),
+              MessageType::Error,
+              &ctx);
+          },
+          Err(e) => { 
+            error!("error connecting to DGG channel status websocket: {:?}", e);
+            //super::display_system_message_in_chat(&out_tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, format!("Error: {}", e), MessageType::Error);
+            super::display_system_message_in_chat(
+              &out_tx, 
+              DGG_CHANNEL_NAME.to_owned(), 
+              ProviderName::DGG, 
+              format!("Failed to connect, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32())),
+              MessageType::Error,
+              &ctx);
+          }
+        }
+        if let Some(duration) = retry_wait {
+          sleep(duration).await;
+        }
       }
-    }
-    warn!("exiting websocket_chat thread");
-  });
-
-  channel.transient = Some(ChannelTransient {
-    channel_emotes: None,
-    badge_emotes: None,
-    status: None
-  });
-
-  match emote_loader.tx.try_send(EmoteRequest::DggFlairEmotesRequest { 
-    channel_name: channel.channel_name.to_owned(), 
-    cdn_base_url: dgg.dgg_cdn_url.to_owned(), 
-    force_redownload: false 
-  }) {  
-    Ok(_) => {},
-    Err(e) => { error!("Failed to request global emote json due to error {:?}", e); }
-  };
+      warn!("exiting websocket_chat thread");
+    });
 
-  let handles = vec![ handle, handle2 ];
+    vec![ handle, handle2 ]
+  }
+}
 
-  ChatManager {
-    username: user_name.to_owned(),
-    handles,
-    in_tx,
-    out_rx,
+impl ChatManagerRx for DggChatManager {
+  fn in_tx(&mut self) -> &mut Sender<OutgoingMessage> {
+    &mut self.in_tx
+  }
+  fn out_rx(&mut self) -> &mut Receiver<IncomingMessage> {
+    &mut self.out_rx
   }
 }
 
-impl ChatManager {
-  pub fn close(&mut self) {
+impl ChatProvider for DggChatManager {
+  fn provider_name(&self) -> ProviderName {
+    ProviderName::DGG
+  }
+
+  fn username(&self) -> &str {
+    &self.username
+  }
+
+  fn can_send(&self, _channel_name: &str) -> Result<(), String> {
+    match self.token.is_empty() {
+      true => Err("Log in to destiny.gg to chat.".to_owned()),
+      false => Ok(())
+    }
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let Channel::DGG { dgg, shared } = channel else { return; };
+
+    // already connected, e.g. when only the channel options changed
+    if let Some(handles) = self.handles.get(&shared.channel_name) && handles.iter().all(|h| !h.is_finished()) {
+      return;
+    }
+    if let Some(handles) = self.handles.remove(&shared.channel_name) {
+      for handle in handles {
+        handle.abort();
+      }
+    }
+    self.handles.insert(shared.channel_name.to_owned(), self.spawn_channel_clients(dgg));
+
+    if shared.transient.is_none() {
+      shared.transient = Some(ChannelTransient {
+        channel_emotes: None,
+        badge_emotes: None,
+        status: None,
+        chat_modes: Default::default(),
+        is_moderator: false
+      });
+    }
+  }
+
+  fn send_action(&mut self, channel_name: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.send_chat(channel_name, &format!("/me {message}"))
+  }
+
+  fn request_whisper_history(&mut self, username: &str) {
+    if let Err(e) = self.in_tx.try_send(OutgoingMessage::WhisperHistory { username: username.to_owned() }) {
+      info!("Send failure: {}", e);
+    }
+  }
+
+  fn mark_whispers_read(&mut self, ids: Vec<String>) {
+    if let Err(e) = self.in_tx.try_send(OutgoingMessage::WhisperRead { ids }) {
+      info!("Send failure: {}", e);
+    }
+  }
+
+  fn supported_commands(&self) -> &'static [&'static str] {
+    &["me", "w", "timeout", "ban", "unban"]
+  }
+
+  fn request_channel_emotes(&self, channel: &Channel, emote_loader: &EmoteLoader, force_redownload: bool) {
+    if let Channel::DGG { dgg, shared } = channel {
+      match emote_loader.tx.try_send(EmoteRequest::DggFlairEmotesRequest { 
+        channel_name: shared.channel_name.to_owned(), 
+        cdn_base_url: dgg.dgg_cdn_url.to_owned(), 
+        force_redownload
+      }) {  
+        Ok(_) => {},
+        Err(e) => { error!("Failed to request global emote json due to error {:?}", e); }
+      };
+    }
+  }
+
+  fn close(&mut self) {
     self.in_tx.try_send(OutgoingMessage::Quit {}).expect_or_log("channel failure");
-    for handle in self.handles.iter_mut() {
-      handle.abort();
+    for (_, handles) in self.handles.drain() {
+      for handle in handles {
+        handle.abort();
+      }
     }
   }
 }
@@ -156,6 +300,8 @@ async fn spawn_websocket_live_client(dgg_status_url: &String, tx : &Sender<Incom
   let request = dgg_status_url.into_client_request()?;
   let (mut socket, _) = connect_async_tls_with_config(request, None, false, None).await?;
 
+  let mut status = ChannelStatus::default();
+  let mut has_stream_info = false;
   loop {
     tokio::select! {
       Some(result) = socket.next() => {
@@ -163,25 +309,18 @@ async fn spawn_websocket_live_client(dgg_status_url: &String, tx : &Sender<Incom
           Ok(message) => {
             match message.into_text() {
               Ok(message) => {
-                if message.contains("dggApi:streamInfo")
-                  && let Ok(msg) = serde_json::from_str::<DggApiMsg>(&message).inspect_err(|f| warn!("json parse error: {}\n {}", f, message))
-                  && msg.r#type == Some("dggApi:streamInfo".to_string())
-                  && let Some(data) = msg.data
-                  && let Some(streams) = data.streams
-                  && let Some(yt_data) = streams.youtube {
-                    let status_msg = IncomingMessage::StreamingStatus { channel: DGG_CHANNEL_NAME.to_owned(), status: Some(ChannelStatus { 
-                      game_name: yt_data.game, 
-                      is_live: yt_data.live.unwrap_or(false), 
-                      title: yt_data.status_text,  
-                      viewer_count: yt_data.viewers, 
-                      started_at: yt_data.started_at 
-                    }) };
-  
-                    if let Err(e) = tx.try_send(status_msg) { warn!("error sending dgg stream status: {}", e) }
-                    ctx.request_repaint();
-                }
-                else if !message.is_empty() {
-                  debug!("received dgg status message: {}", message);
+                match update_live_status(&message, &mut status) {
+                  Some(update) => {
+                    has_stream_info |= update == LiveUpdate::StreamInfo;
+                    // embeds on their own would look like an offline status
+                    if has_stream_info {
+                      let status_msg = IncomingMessage::StreamingStatus { channel: DGG_CHANNEL_NAME.to_owned(), status: Some(status.to_owned()) };
+                      if let Err(e) = tx.try_send(status_msg) { warn!("error sending dgg stream status: {}", e) }
+                      ctx.request_repaint();
+                    }
+                  },
+                  None if !message.is_empty() => debug!("received dgg status message: {}", message),
+                  None => ()
                 }
               },
               Err(e) => {
@@ -199,7 +338,7 @@ async fn spawn_websocket_live_client(dgg_status_url: &String, tx : &Sender<Incom
   }
 }
 
-async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, token: &String, tx : &Sender<IncomingMessage>, rx: &Receiver<OutgoingMessage>, ctx: &Context) -> Result<bool, anyhow::Error> {
+async fn spawn_websocket_chat_client(dgg_chat_url: &String, user_name : &str, token: &String, tx : &Sender<IncomingMessage>, rx: &Receiver<OutgoingMessage>, ctx: &Context) -> Result<bool, anyhow::Error> {
   let mut quitted = false;
 
   let cookie = format!("authtoken={token}");
@@ -216,6 +355,26 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
 
   super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, "Connected to chat.".to_owned(), MessageType::Information, ctx);
 
+  let web_client = reqwest::Client::new();
+  // DGG confirms a sent private message with an empty PRIVMSGSENT, so keep what was sent until then
+  let mut pending_whispers : VecDeque<(String, String)> = Default::default();
+  // an ERR doesn't say which command failed, so it is only taken as a whisper failure when PRIVMSG was the last command sent
+  let mut last_sent_privmsg = false;
+  // moderation commands are answered with a BAN/MUTE/UNBAN broadcast, or an ERR if they were refused
+  let mut pending_moderation : VecDeque<ModerationAction> = Default::default();
+  let mut chat_modes = ChatModes::default();
+  if !token.is_empty() {
+    let (web_client, token, user_name, tx, ctx) = (web_client.clone(), token.to_owned(), user_name.to_owned(), tx.clone(), ctx.clone());
+    tokio::spawn(async move {
+      match load_unread_whispers(&web_client, DGG_API_URL, &token).await {
+        Ok(unread) => for (username, _) in unread {
+          send_whisper_history(&web_client, &token, &user_name, &username, &tx, &ctx).await;
+        },
+        Err(e) => warn!("Failed to load DGG private messages: {e}")
+      }
+    });
+  }
+
   while !quitted {
     tokio::select! {
       Some(result) = socket.next() => {
@@ -235,6 +394,11 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
             }
             else if message.is_text() && let Ok(message) = message.into_text().inspect_err(|f| info!("websocket error: {}", f)) 
               && let Some((command, msg)) = message.split_once(' ') {
+                if matches!(command, "MUTE" | "BAN" | "UNBAN") && let Ok(event) = serde_json::from_str::<DggEventMessage>(msg)
+                  && let Some(ix) = confirmed_moderation(&pending_moderation, command, event.data.as_deref().unwrap_or_default())
+                  && let Some(action) = pending_moderation.remove(ix) {
+                  super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, action.success_message(), MessageType::Information, ctx);
+                }
                 match command {
                   "MSG" => {
                     if let Ok(msg) = serde_json::from_str::<MsgMessage>(msg).inspect_err(|f| info!("json parse error: {}\n {}", f, message)) {
@@ -243,6 +407,8 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
                         provider: ProviderName::DGG,
                         channel: DGG_CHANNEL_NAME.to_owned(),
                         username: msg.nick.to_lowercase(), 
+                        // DGG has no message ids, but a user can't send two messages in the same millisecond
+                        id: format!("{}-{}", msg.timestamp, msg.nick.to_lowercase()),
                         timestamp: DateTime::from_timestamp(msg.timestamp as i64 / 1000, (msg.timestamp % 1000 * 1000_usize.pow(2)) as u32 )
                           //.map(|x| DateTime::from_utc(x, Utc))
                           .unwrap_or_else(chrono::Utc::now),
@@ -280,6 +446,11 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
                       };
                     }
                   },
+                  "ME" => {
+                    if let Err(x) = tx.try_send(IncomingMessage::ModeratorStatus { channel: DGG_CHANNEL_NAME.to_owned(), is_moderator: me_is_moderator(msg) }) {
+                      info!("Send failure for ME: {}", x);
+                    }
+                  },
                   "REFRESH" => {
                     // REFRESH {\"nick\":\"Bob\",\"features\":[\"subscriber\",\"flair1\"],\"timestamp\":1660506127552}
                   },
@@ -309,21 +480,49 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
                       }
                     }
                   },
+                  "PRIVMSG" => {
+                    if let Ok(whisper) = parse_private_message(msg).inspect_err(|f| info!("json parse error: {}\n {}", f, message))
+                      && let Err(x) = tx.try_send(IncomingMessage::Whisper { conversation: whisper.username.to_owned(), message: whisper }) {
+                      info!("Send failure for PRIVMSG: {}", x);
+                    }
+                  },
+                  "PRIVMSGSENT" => {
+                    let sent = pending_whispers.pop_front();
+                    last_sent_privmsg &= !pending_whispers.is_empty();
+                    if let Some((username, data)) = sent {
+                      let whisper = ChatMessage { provider: ProviderName::DGG, username: user_name.to_lowercase(), message: data, profile: UserProfile { display_name: Some(user_name.to_owned()), ..Default::default() }, ..Default::default() };
+                      if let Err(x) = tx.try_send(IncomingMessage::Whisper { conversation: username.to_lowercase(), message: whisper }) {
+                        info!("Send failure for PRIVMSGSENT: {}", x);
+                      }
+                    }
+                  },
                   "ERR" => {
                     if let Ok(msg) = serde_json::from_str::<DggErr>(msg).inspect_err(|f| info!("json parse error: {}\n {}", f, message)) {
-                      match tx.try_send(IncomingMessage::PrivMsg { message: ChatMessage {
-                        channel: DGG_CHANNEL_NAME.to_owned(), 
-                        provider: ProviderName::DGG, 
-                        message: match msg.description.as_str() {
-                          "duplicate" => "The message is identical to the last one you sent".to_owned(),
-                          _ => msg.description.to_owned()
-                        },
-                        msg_type: MessageType::Error,
-                        ..Default::default() 
-                      } }) {
-                        Ok(_) => (),
-                        Err(x) => info!("Send failure for ERR: {}", x)
-                      };
+                      if last_sent_privmsg && let Some((username, _)) = pending_whispers.pop_front() {
+                        last_sent_privmsg = !pending_whispers.is_empty();
+                        let whisper = ChatMessage { provider: ProviderName::DGG, message: format!("Failed to whisper {username}: {}", whisper_error(&msg.description)), msg_type: MessageType::Error, ..Default::default() };
+                        if let Err(x) = tx.try_send(IncomingMessage::Whisper { conversation: username.to_lowercase(), message: whisper }) {
+                          info!("Send failure for ERR: {}", x);
+                        }
+                      }
+                      else if let Some(error) = moderation_error(&msg.description) && let Some(action) = pending_moderation.pop_front() {
+                        super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, action.failure_message(error), MessageType::Error, ctx);
+                      }
+                      else {
+                        match tx.try_send(IncomingMessage::PrivMsg { message: ChatMessage {
+                          channel: DGG_CHANNEL_NAME.to_owned(), 
+                          provider: ProviderName::DGG, 
+                          message: match msg.description.as_str() {
+                            "duplicate" => "The message is identical to the last one you sent".to_owned(),
+                            _ => msg.description.to_owned()
+                          },
+                          msg_type: MessageType::Error,
+                          ..Default::default() 
+                        } }) {
+                          Ok(_) => (),
+                          Err(x) => info!("Send failure for ERR: {}", x)
+                        };
+                      }
                     }
                   },
                   "MUTE" => {
@@ -334,16 +533,33 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
                       };
                     }
                   },
-                  "POLLSTART" => {
-                    
-                  },
-                  "POLLSTOP" => {
-
+                  "POLLSTART" | "POLLSTOP" => {
+                    if let Ok(msg) = serde_json::from_str::<DggPollStart>(msg).inspect_err(|f| info!("json parse error: {}\n {}", f, message)) {
+                      let (channel, poll) = (DGG_CHANNEL_NAME.to_owned(), msg.to_poll(chrono::Utc::now()));
+                      let incoming = if command == "POLLSTART" { IncomingMessage::VoteStart { channel, poll } } else { IncomingMessage::VoteStop { channel, poll } };
+                      if let Err(x) = tx.try_send(incoming) {
+                        info!("Send failure for {command}: {}", x);
+                      }
+                    }
                   },
-                  "VOTECAST" => {
-
+                  // VOTECAST goes to everyone, VOTECOUNTED only to the voter once their own vote is accepted
+                  "VOTECAST" | "VOTECOUNTED" => {
+                    if let Some((option, quantity)) = parse_vote(msg) {
+                      let channel = DGG_CHANNEL_NAME.to_owned();
+                      let incoming = if command == "VOTECAST" { IncomingMessage::VoteCast { channel, option, quantity } } else { IncomingMessage::VoteCounted { channel, option } };
+                      if let Err(x) = tx.try_send(incoming) {
+                        info!("Send failure for {command}: {}", x);
+                      }
+                    }
                   },
-                  _ => debug!("unknown dgg command: {:?}", message)
+                  _ => match parse_event(command, msg, &mut chat_modes) {
+                    Some(events) => for event in events {
+                      if let Err(x) = tx.try_send(event) {
+                        info!("Send failure for {command}: {}", x);
+                      }
+                    },
+                    None => debug!("unknown dgg command: {:?}", message)
+                  }
                 }
                 ctx.request_repaint();
             }
@@ -357,6 +573,7 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
       Ok(out_msg) = rx.recv() => {
         match out_msg {
           OutgoingMessage::Chat { channel : _, message } => { 
+            last_sent_privmsg = false;
             socket.send(Message::Text(format!("MSG {{\"data\":\"{message}\"}}\r"))).await
               .inspect_err(|f| info!("socket send error: {f}"))
               .expect_or_log("Error sending websocket message");
@@ -364,6 +581,52 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
           OutgoingMessage::Leave { channel_name : _ } => {
             socket.close(None).await.expect_or_log("Error while quitting IRC server"); quitted = true;
           },
+          OutgoingMessage::Moderate { channel : _, action } => {
+            match moderation_command(&action) {
+              Some(command) => match socket.send(Message::Text(command)).await {
+                Ok(_) => {
+                  last_sent_privmsg = false;
+                  pending_moderation.push_back(action);
+                },
+                Err(e) => super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, action.failure_message(&e.to_string()), MessageType::Error, ctx)
+              },
+              None => super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, action.failure_message("not supported by DGG chat"), MessageType::Error, ctx)
+            }
+          },
+          OutgoingMessage::Whisper { username, message } => {
+            match socket.send(Message::Text(format!("PRIVMSG {}", serde_json::json!({ "nick": username, "data": message })))).await {
+              Ok(_) => {
+                last_sent_privmsg = true;
+                pending_whispers.push_back((username, message));
+              },
+              Err(e) => {
+                let whisper = ChatMessage { provider: ProviderName::DGG, message: format!("Failed to whisper {username}: {e}"), msg_type: MessageType::Error, ..Default::default() };
+                if let Err(x) = tx.try_send(IncomingMessage::Whisper { conversation: username.to_lowercase(), message: whisper }) {
+                  info!("Send failure: {}", x);
+                }
+              }
+            }
+          },
+          OutgoingMessage::Vote { channel: _, option } => {
+            last_sent_privmsg = false;
+            if let Err(e) = socket.send(Message::Text(format!("CASTVOTE {}", serde_json::json!({ "vote": (option + 1).to_string() })))).await {
+              super::display_system_message_in_chat(tx, DGG_CHANNEL_NAME.to_owned(), ProviderName::DGG, format!("Failed to vote: {e}"), MessageType::Error, ctx);
+            }
+          },
+          OutgoingMessage::WhisperHistory { username } => {
+            let (web_client, token, user_name, tx, ctx) = (web_client.clone(), token.to_owned(), user_name.to_owned(), tx.clone(), ctx.clone());
+            tokio::spawn(async move { send_whisper_history(&web_client, &token, &user_name, &username, &tx, &ctx).await; });
+          },
+          OutgoingMessage::WhisperRead { ids } => {
+            let (web_client, token) = (web_client.clone(), token.to_owned());
+            tokio::spawn(async move {
+              for id in ids {
+                if let Err(e) = mark_whisper_read(&web_client, DGG_API_URL, &token, &id).await {
+                  warn!("Failed to mark DGG private message {id} as read: {e}");
+                }
+              }
+            });
+          },
           _ => ()
         };
       }
@@ -372,6 +635,291 @@ async fn spawn_websocket_chat_client(dgg_chat_url: &String, _user_name : &str, t
   Ok(true)
 }
 
+#[derive(Debug, PartialEq)]
+pub enum LiveUpdate {
+  StreamInfo,
+  Embeds
+}
+
+/// Applies a message from the live websocket to the status, keeping what the message doesn't cover
+/// (stream info doesn't include the embeds and vice versa). Returns None for other messages.
+pub fn update_live_status(message: &str, status: &mut ChannelStatus) -> Option<LiveUpdate> {
+  let msg = serde_json::from_str::<DggApiMsg>(message).ok()?;
+  match msg.r#type.as_deref() {
+    Some("dggApi:streamInfo") => {
+      let streams = serde_json::from_value::<LiveSocketMsg>(msg.data).inspect_err(|f| warn!("json parse error: {}\n {}", f, message)).ok()?.streams?;
+      let platforms = [("YouTube", streams.youtube), ("Kick", streams.kick), ("Twitch", streams.twitch), ("Rumble", streams.rumble)].into_iter()
+        .filter_map(|(platform, detail)| detail.map(|d| (platform, d)))
+        .collect_vec();
+      // title and category come from the first live platform
+      let main = platforms.iter().find(|(_, d)| d.live == Some(true)).or(platforms.first()).map(|(_, d)| d);
+      status.is_live = platforms.iter().any(|(_, d)| d.live == Some(true));
+      status.game_name = main.and_then(|d| d.game.to_owned());
+      status.title = main.and_then(|d| d.status_text.to_owned());
+      status.started_at = main.and_then(|d| d.started_at.to_owned());
+      status.viewer_count = if status.is_live {
+        Some(platforms.iter().filter(|(_, d)| d.live == Some(true)).filter_map(|(_, d)| d.viewers).sum())
+      } else {
+        main.and_then(|d| d.viewers)
+      };
+      status.platforms = platforms.into_iter().map(|(platform, d)| StreamPlatform {
+        platform: platform.to_owned(),
+        is_live: d.live == Some(true),
+        url: d.id.as_ref().filter(|id| !id.is_empty()).map(|id| stream_url(platform, id)),
+        title: d.status_text,
+        viewer_count: d.viewers,
+        started_at: d.started_at
+      }).collect_vec();
+      Some(LiveUpdate::StreamInfo)
+    },
+    Some("dggApi:embeds") => {
+      let embeds = serde_json::from_value::<Vec<DggEmbed>>(msg.data).inspect_err(|f| warn!("json parse error: {}\n {}", f, message)).ok()?;
+      status.embeds = embeds.into_iter().filter_map(|e| {
+        // links look like #youtube/abc123
+        let (link_platform, link_id) = e.link.as_deref().and_then(|l| l.trim_start_matches('#').split_once('/')).unzip();
+        let platform = e.platform.or_else(|| link_platform.map(|x| x.to_owned()))?;
+        let id = e.id.or_else(|| link_id.map(|x| x.to_owned()))?;
+        Some(StreamEmbed {
+          url: stream_url(&platform, &id),
+          platform,
+          id,
+          title: e.title.filter(|t| !t.is_empty()),
+          channel: e.channel.filter(|c| !c.is_empty()),
+          watchers: e.count.unwrap_or_default()
+        })
+      })
+      .sorted_by_key(|e| std::cmp::Reverse(e.watchers))
+      .collect_vec();
+      Some(LiveUpdate::Embeds)
+    },
+    _ => None
+  }
+}
+
+/// Page for a stream or video id on its platform, or the DGG bigscreen for platforms without a known url
+pub fn stream_url(platform: &str, id: &str) -> String {
+  match platform.to_lowercase().as_str() {
+    "youtube" => format!("https://www.youtube.com/watch?v={id}"),
+    "twitch" => format!("https://www.twitch.tv/{id}"),
+    "kick" => format!("https://kick.com/{id}"),
+    "rumble" => format!("https://rumble.com/embed/{id}"),
+    platform => format!("https://www.destiny.gg/bigscreen#{platform}/{id}")
+  }
+}
+
+/// Messages for moderation and subscription events, or None if the command is not one of them.
+/// `chat_modes` is updated by mode changes such as SUBONLY.
+pub fn parse_event(command: &str, json: &str, chat_modes: &mut ChatModes) -> Option<Vec<IncomingMessage>> {
+  if !["BAN", "UNBAN", "UNMUTE", "SUBONLY", "SUBSCRIPTION", "GIFTSUB", "MASSGIFT", "DONATION"].contains(&command) {
+    return None;
+  }
+  let Ok(msg) = serde_json::from_str::<DggEventMessage>(json).inspect_err(|f| info!("json parse error: {}\n {} {}", f, command, json)) else {
+    return Some(Vec::new());
+  };
+  let channel = DGG_CHANNEL_NAME.to_owned();
+  let chat_message = |message: String, msg_type: MessageType| IncomingMessage::PrivMsg { message: ChatMessage {
+    provider: ProviderName::DGG,
+    channel: DGG_CHANNEL_NAME.to_owned(),
+    timestamp: msg.timestamp
+      .and_then(|ts| DateTime::from_timestamp(ts as i64 / 1000, (ts % 1000 * 1000_usize.pow(2)) as u32))
+      .unwrap_or_else(chrono::Utc::now),
+    message,
+    msg_type,
+    ..Default::default()
+  }};
+  let target = msg.data.to_owned().unwrap_or_default();
+  let tier = msg.tierlabel.to_owned().or_else(|| msg.tier.map(|t| format!("Tier {t}")));
+
+  let events = match command {
+    // same as MUTE, the user's messages are removed
+    "BAN" => vec![IncomingMessage::UserMuted { channel, username: target.to_lowercase() }],
+    "UNBAN" => vec![chat_message(format!("{target} was unbanned by {}", msg.nick), MessageType::Information)],
+    "UNMUTE" => vec![chat_message(format!("{target} was unmuted by {}", msg.nick), MessageType::Information)],
+    "SUBONLY" => {
+      chat_modes.subs_only = target == "on";
+      let state = if chat_modes.subs_only { "enabled" } else { "disabled" };
+      vec![
+        IncomingMessage::ChatModes { channel, modes: chat_modes.to_owned() },
+        chat_message(format!("Subscriber only mode {state} by {}", msg.nick), MessageType::Information)
+      ]
+    },
+    "SUBSCRIPTION" => {
+      let mut message = match tier {
+        Some(tier) => format!("{} subscribed at {tier}", msg.nick),
+        None => format!("{} subscribed", msg.nick)
+      };
+      if let Some(streak) = msg.streak.filter(|s| *s > 1) {
+        message = format!("{message} ({streak} months)");
+      }
+      vec![chat_message(with_attached_message(message, &msg.data), MessageType::Announcement)]
+    },
+    "GIFTSUB" => {
+      let giftee = msg.giftee.to_owned().unwrap_or_default();
+      let message = match tier {
+        Some(tier) => format!("{} gifted a {tier} sub to {giftee}", msg.nick),
+        None => format!("{} gifted a sub to {giftee}", msg.nick)
+      };
+      vec![chat_message(with_attached_message(message, &msg.data), MessageType::Announcement)]
+    },
+    "MASSGIFT" => {
+      let quantity = msg.quantity.unwrap_or(1);
+      let message = match tier {
+        Some(tier) => format!("{} gifted {quantity} {tier} subs", msg.nick),
+        None => format!("{} gifted {quantity} subs", msg.nick)
+      };
+      vec![chat_message(with_attached_message(message, &msg.data), MessageType::Announcement)]
+    },
+    // amount is in cents
+    _ => {
+      let amount = msg.amount.unwrap_or_default();
+      let message = format!("{} donated ${}.{:02}", msg.nick, amount / 100, amount % 100);
+      vec![chat_message(with_attached_message(message, &msg.data), MessageType::Announcement)]
+    }
+  };
+  Some(events)
+}
+
+fn with_attached_message(message: String, data: &Option<String>) -> String {
+  match data.as_deref().map(str::trim) {
+    Some(data) if !data.is_empty() => format!("{message}: {data}"),
+    _ => message
+  }
+}
+
+/// Private message received over the chat websocket, from the JSON after the PRIVMSG command
+pub fn parse_private_message(json: &str) -> Result<ChatMessage, serde_json::Error> {
+  let msg = serde_json::from_str::<DggPrivMsg>(json)?;
+  Ok(ChatMessage {
+    provider: ProviderName::DGG,
+    username: msg.nick.to_lowercase(),
+    id: msg.messageid.map(|id| json_to_string(&id)).unwrap_or_default(),
+    timestamp: msg.timestamp
+      .and_then(|ts| DateTime::from_timestamp(ts as i64 / 1000, (ts % 1000 * 1000_usize.pow(2)) as u32))
+      .unwrap_or_else(chrono::Utc::now),
+    message: msg.data.unwrap_or_default(),
+    profile: UserProfile { display_name: Some(msg.nick), ..Default::default() },
+    ..Default::default()
+  })
+}
+
+/// Readable reason for an ERR reply to a private message
+fn whisper_error(description: &str) -> &str {
+  match description {
+    "notfound" => "user not found",
+    "privmsgbanned" => "you are not allowed to send private messages",
+    "privmsgaccounttooyoung" => "your account is too new to send private messages",
+    description => description
+  }
+}
+
+/// DGG sends times like 2023-03-17T19:46:40+0000, which RFC 3339 parsing rejects for the offset without a colon
+fn parse_timestamp(ts: &str) -> Option<DateTime<chrono::FixedOffset>> {
+  DateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.f%z").or_else(|_| DateTime::parse_from_rfc3339(ts)).ok()
+}
+
+/// Ids may come back as numbers or strings
+fn json_to_string(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::String(s) => s.to_owned(),
+    value => value.to_string()
+  }
+}
+
+async fn send_whisper_history(client: &reqwest::Client, token: &str, user_name: &str, username: &str, tx: &Sender<IncomingMessage>, ctx: &Context) {
+  match load_whisper_history(client, DGG_API_URL, token, user_name, username).await {
+    Ok((messages, unread_ids)) => {
+      if let Err(e) = tx.try_send(IncomingMessage::WhisperHistory { provider: ProviderName::DGG, conversation: username.to_lowercase(), messages, unread_ids }) {
+        info!("Send failure: {}", e);
+      }
+      ctx.request_repaint();
+    },
+    Err(e) => warn!("Failed to load DGG private messages with {username}: {e}")
+  }
+}
+
+/// The website API authenticates with the same cookie as the chat websocket
+async fn send_api_request(request: reqwest::RequestBuilder, token: &str) -> Result<String, anyhow::Error> {
+  let resp = request.header(COOKIE.as_str(), format!("authtoken={token}")).send().await?;
+  let status = resp.status();
+  let text = resp.text().await?;
+  if status.is_success() {
+    Ok(text)
+  } else {
+    Err(anyhow::anyhow!("{status}"))
+  }
+}
+
+/// Users with unread private messages, with the number of unread messages from each
+pub async fn load_unread_whispers(client: &reqwest::Client, api_url: &str, token: &str) -> Result<Vec<(String, usize)>, anyhow::Error> {
+  let json = send_api_request(client.get(format!("{api_url}/messages/unread")), token).await?;
+  Ok(serde_json::from_str::<Vec<DggUnreadMessages>>(&json)?.into_iter().map(|x| (x.username, x.unread)).collect_vec())
+}
+
+/// Private messages between the logged in user and `username`, oldest first, and the ids of the unread ones
+pub async fn load_whisper_history(client: &reqwest::Client, api_url: &str, token: &str, user_name: &str, username: &str) -> Result<(Vec<ChatMessage>, Vec<String>), anyhow::Error> {
+  let json = send_api_request(client.get(format!("{api_url}/messages/usr/{}/inbox", urlencoding::encode(username))), token).await?;
+  parse_whisper_history(&json, user_name)
+}
+
+/// Inbox response for one conversation, see `load_whisper_history`
+pub fn parse_whisper_history(json: &str, user_name: &str) -> Result<(Vec<ChatMessage>, Vec<String>), anyhow::Error> {
+  let history = serde_json::from_str::<Vec<DggInboxMessage>>(json)?;
+  let unread_ids = history.iter()
+    .filter(|x| !x.from.eq_ignore_ascii_case(user_name) && !(x.isread.as_bool() == Some(true) || x.isread.as_u64().is_some_and(|x| x > 0)))
+    .map(|x| json_to_string(&x.id))
+    .collect_vec();
+  let messages = history.into_iter()
+    .map(|x| ChatMessage {
+      provider: ProviderName::DGG,
+      username: x.from.to_lowercase(),
+      id: json_to_string(&x.id),
+      timestamp: parse_timestamp(&x.timestamp).map(|ts| ts.with_timezone(&chrono::Utc)).unwrap_or_else(chrono::Utc::now),
+      message: x.message,
+      profile: UserProfile { display_name: Some(x.from), ..Default::default() },
+      ..Default::default()
+    })
+    .sorted_by_key(|x| x.timestamp)
+    .collect_vec();
+  Ok((messages, unread_ids))
+}
+
+pub async fn mark_whisper_read(client: &reqwest::Client, api_url: &str, token: &str, id: &str) -> Result<(), anyhow::Error> {
+  send_api_request(client.post(format!("{api_url}/messages/msg/{id}/open")), token).await.map(|_| ())
+}
+
+/// Websocket command for a moderation action, or None if DGG chat has no equivalent
+pub fn moderation_command(action: &ModerationAction) -> Option<String> {
+  match action {
+    // durations are in nanoseconds
+    ModerationAction::Timeout { username, duration_sec } => Some(format!("MUTE {}", serde_json::json!({ "data": username, "duration": *duration_sec as u64 * 1_000_000_000 }))),
+    ModerationAction::Ban { username } => Some(format!("BAN {}", serde_json::json!({ "nick": username, "ispermanent": true, "reason": "" }))),
+    ModerationAction::Unban { username } => Some(format!("UNBAN {}", serde_json::json!({ "data": username }))),
+    ModerationAction::DeleteMessage { .. } => None
+  }
+}
+
+/// Index of the sent moderation action that a BAN, MUTE or UNBAN broadcast for `target` confirms
+pub fn confirmed_moderation(pending: &VecDeque<ModerationAction>, command: &str, target: &str) -> Option<usize> {
+  pending.iter().position(|action| target.eq_ignore_ascii_case(action.username()) && matches!((action, command),
+    (ModerationAction::Timeout { .. }, "MUTE") | (ModerationAction::Ban { .. }, "BAN") | (ModerationAction::Unban { .. }, "UNBAN")))
+}
+
+/// Reason shown for an ERR that refuses a moderation command, or None if the ERR is about something else
+pub fn moderation_error(description: &str) -> Option<&'static str> {
+  match description {
+    "nopermission" => Some("you are not a moderator"),
+    "protected" => Some("the user is protected"),
+    "notfound" => Some("user not found"),
+    _ => None
+  }
+}
+
+/// Whether the ME message sent after connecting describes a moderator. It is `ME null` when not logged in.
+pub fn me_is_moderator(json: &str) -> bool {
+  serde_json::from_str::<Option<MeMessage>>(json).ok().flatten()
+    .is_some_and(|me| me.features.iter().any(|f| f == "admin" || f == "moderator"))
+}
+
 const REDIRECT_URI : &str = "https://dbckr.github.io/GigachatAuth";
 const CLIENT_ID : &str = "dbrq5gUQDWmv6jBzFt9UwpN8VQOIeO7i";
 
@@ -570,7 +1118,8 @@ impl CSSLoader {
 #[derive(serde::Deserialize)]
 struct DggApiMsg {
   r#type: Option<String>,
-  data: Option<LiveSocketMsg>
+  #[serde(default)]
+  data: serde_json::Value
 }
 
 #[derive(serde::Deserialize)]
@@ -580,8 +1129,10 @@ struct LiveSocketMsg {
 
 #[derive(serde::Deserialize)]
 struct LiveSocketMsgStreams {
-  //twitch: Option<LiveSocketMsgStreamDetail>,
-  youtube: Option<LiveSocketMsgStreamDetail>
+  youtube: Option<LiveSocketMsgStreamDetail>,
+  kick: Option<LiveSocketMsgStreamDetail>,
+  twitch: Option<LiveSocketMsgStreamDetail>,
+  rumble: Option<LiveSocketMsgStreamDetail>
 }
 
 #[derive(serde::Deserialize)]
@@ -594,11 +1145,22 @@ struct LiveSocketMsgStreamDetail {
   //ended_at: Option<String>,
   //duration: Option<usize>,
   viewers: Option<usize>,
-  //id: Option<String>,
+  id: Option<String>,
   //platform: Option<String>,
   //r#type: Option<String>
 }
 
+// {"type":"dggApi:embeds","data":[{"platform":"youtube","id":"abc123","link":"#youtube/abc123","title":"...","channel":"...","count":12}]}
+#[derive(serde::Deserialize)]
+struct DggEmbed {
+  platform: Option<String>,
+  id: Option<String>,
+  link: Option<String>,
+  title: Option<String>,
+  channel: Option<String>,
+  count: Option<usize>
+}
+
 #[derive(serde::Deserialize)]
 struct AuthResponse {
   access_token: String,
@@ -613,6 +1175,12 @@ struct NamesMessage {
   users: Vec<PartialMsgMessage>
 }
 
+#[derive(serde::Deserialize)]
+struct MeMessage {
+  #[serde(default)]
+  features: Vec<String>
+}
+
 #[derive(serde::Deserialize)]
 struct PartialMsgMessage {
   nick: String
@@ -645,6 +1213,48 @@ pub struct DggEmoteImage {
   name: String
 }
 
+// PRIVMSG {\"messageid\":123,\"timestamp\":1660506127552,\"nick\":\"Bob\",\"data\":\"hello\"}
+#[derive(serde::Deserialize)]
+struct DggPrivMsg {
+  messageid: Option<serde_json::Value>,
+  timestamp: Option<usize>,
+  nick: String,
+  data: Option<String>
+}
+
+// BAN {\"nick\":\"Destiny\",\"features\":[\"admin\"],\"timestamp\":1680000000000,\"data\":\"Bob\"}
+// GIFTSUB {\"nick\":\"Alice\",\"giftee\":\"Bob\",\"tier\":1,\"tierlabel\":\"Tier I\",\"timestamp\":1680000000000}
+#[derive(serde::Deserialize)]
+struct DggEventMessage {
+  nick: String,
+  timestamp: Option<usize>,
+  /// Target user for moderation events, "on" or "off" for mode changes, or the attached message
+  data: Option<String>,
+  tier: Option<usize>,
+  tierlabel: Option<String>,
+  streak: Option<usize>,
+  giftee: Option<String>,
+  quantity: Option<usize>,
+  amount: Option<usize>
+}
+
+#[derive(serde::Deserialize)]
+struct DggUnreadMessages {
+  username: String,
+  unread: usize
+}
+
+#[derive(serde::Deserialize)]
+struct DggInboxMessage {
+  id: serde_json::Value,
+  from: String,
+  //to: String,
+  message: String,
+  timestamp: String,
+  #[serde(default)]
+  isread: serde_json::Value
+}
+
 #[derive(serde::Deserialize)]
 struct DggErr {
   description: String
@@ -665,14 +1275,53 @@ pub struct DggFlair {
 // \"start\":\"2023-03-17T19:46:40+0000\",\"now\":\"2023-03-17T19:46:40+0000\",\"time\":30000,
 // \"question\":\"Are you wearing Green?\",\"options\":[\"PEPE\",\"YEE\"],\"totals\":[0,0],\"totalvotes\":0}"
 
+#[derive(serde::Deserialize)]
 pub struct DggPollStart {
-  // canvote: bool,
-  // nick: String,
-  // start: usize,
-  // now: usize,
-  // time: usize,
-  // question: String,
-  // options: Vec<String>,
-  // totals: Vec<usize>,
+  canvote: bool,
+  /// 1-based, 0 if the user has not voted
+  myvote: usize,
+  nick: String,
+  weighted: bool,
+  start: String,
+  now: String,
+  /// Duration in milliseconds
+  time: i64,
+  question: String,
+  options: Vec<String>,
+  totals: Vec<usize>,
   // totalvotes: usize
+}
+
+impl DggPollStart {
+  /// The end time is taken relative to the server's clock, as the local clock may be off
+  pub fn to_poll(self, now: DateTime<chrono::Utc>) -> Poll {
+    let elapsed_ms = parse_timestamp(&self.start).zip(parse_timestamp(&self.now)).map(|(start, server_now)| server_now.signed_duration_since(start).num_milliseconds()).unwrap_or_default();
+    Poll {
+      question: self.question,
+      options: self.options,
+      totals: self.totals,
+      started_by: self.nick,
+      ends_at: now + chrono::Duration::milliseconds(self.time - elapsed_ms),
+      weighted: self.weighted,
+      can_vote: self.canvote,
+      my_vote: self.myvote.checked_sub(1)
+    }
+  }
+}
+
+// VOTECAST {\"vote\":\"2\",\"quantity\":1}
+#[derive(serde::Deserialize)]
+struct DggVoteCast {
+  vote: serde_json::Value,
+  #[serde(default = "default_vote_quantity")]
+  quantity: usize
+}
+
+fn default_vote_quantity() -> usize { 1 }
+
+/// Index of the option and the weight of a VOTECAST or VOTECOUNTED vote. Options are numbered from 1 on the wire.
+pub fn parse_vote(json: &str) -> Option<(usize, usize)> {
+  let msg = serde_json::from_str::<DggVoteCast>(json).inspect_err(|f| info!("json parse error: {}\n {}", f, json)).ok()?;
+  let option = json_to_string(&msg.vote).parse::<usize>().ok()?.checked_sub(1)?;
+  Some((option, msg.quantity))
 }
\ No newline at end of file
@@ -0,0 +1,395 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::{HashMap, HashSet};
+use async_channel::{Receiver, Sender, TrySendError};
+use backoff::backoff::Backoff;
+use base64::{Engine as _, engine::general_purpose};
+use egui::{Color32, Context};
+use futures::prelude::*;
+use irc::client::prelude::*;
+use irc::proto::CapSubCommand;
+use tokio::{runtime::{Handle, Runtime}, time::sleep, time::Duration};
+use tracing::{debug, error, info, trace, warn};
+use super::{ChatMessage, ChatManagerRx, ChatProvider, IncomingMessage, MessageType, OutgoingMessage, ProviderInfo, ProviderName, UserProfile, channel::{Channel, ChannelShared, IrcChannel, IrcNetwork}};
+
+/// IRC channels are keyed by server and channel with this prefix, since the same channel name can exist on several networks
+pub const IRC_CHANNEL_PREFIX : &str = "IRC:";
+
+pub fn init_channel(network: IrcNetwork, channel: &str) -> Channel {
+  let channel = normalize_channel(channel);
+  Channel::Irc {
+    shared: ChannelShared {
+      channel_name: channel_name(&network.server, &channel),
+      show_in_mentions_tab: true,
+      // IRC channels have no live status
+      show_tab_when_offline: true,
+      mute_notifications: false,
+      send_history: Default::default(),
+      send_history_ix: None,
+      transient: None,
+      users: Default::default()
+    },
+    irc: IrcChannel {
+      network,
+      channel
+    }
+  }
+}
+
+pub struct IrcInfo;
+
+impl ProviderInfo for IrcInfo {
+  fn display_name(&self) -> &'static str {
+    "IRC"
+  }
+
+  fn archive_dir(&self) -> &'static str {
+    "irc"
+  }
+
+  fn color(&self) -> Color32 {
+    Color32::from_rgba_unmultiplied(180, 180, 180, 255)
+  }
+
+  fn init_channel(&self, channel_name: &str, network: IrcNetwork) -> Channel {
+    init_channel(network, channel_name)
+  }
+
+  fn channel_name_hint(&self) -> &'static str {
+    "#channel"
+  }
+}
+
+pub fn channel_name(server: &str, irc_channel: &str) -> String {
+  format!("{IRC_CHANNEL_PREFIX}{server}/{}", irc_channel.to_lowercase())
+}
+
+fn normalize_channel(channel: &str) -> String {
+  let channel = channel.trim().to_lowercase();
+  if channel.starts_with(['#', '&']) { channel } else { format!("#{channel}") }
+}
+
+struct IrcConnection {
+  handle: tokio::task::JoinHandle<()>,
+  in_tx: Sender<OutgoingMessage>
+}
+
+pub struct IrcChatManager {
+  /// keyed by the full network settings, so channels using different nicknames or passwords on the same server get their own connection
+  connections: HashMap<IrcNetwork, IrcConnection>,
+  /// app channel name -> (network, IRC channel)
+  channels: HashMap<String, (IrcNetwork, String)>,
+  runtime: Handle,
+  ctx: Context,
+  out_tx: Sender<IncomingMessage>,
+  pub out_rx: Receiver<IncomingMessage>,
+  pub in_tx: Sender<OutgoingMessage>
+}
+
+impl IrcChatManager {
+  pub fn new(runtime: &Runtime, ctx: &Context) -> Self {
+    let (out_tx, out_rx) = async_channel::bounded::<IncomingMessage>(10000);
+    // messages are routed to each server's connection instead
+    let (in_tx, _) = async_channel::bounded::<OutgoingMessage>(1);
+
+    Self {
+      connections: Default::default(),
+      channels: Default::default(),
+      runtime: runtime.handle().clone(),
+      ctx: ctx.clone(),
+      out_tx,
+      out_rx,
+      in_tx
+    }
+  }
+
+  fn spawn_connection(&self, network: &IrcNetwork) -> IrcConnection {
+    let (in_tx, in_rx) = async_channel::bounded::<OutgoingMessage>(10000);
+    let network = network.to_owned();
+    let out_tx = self.out_tx.clone();
+    let ctx = self.ctx.clone();
+
+    let handle = self.runtime.spawn(async move {
+      let mut backoff = backoff::ExponentialBackoffBuilder::new()
+      .with_initial_interval(Duration::from_millis(3000))
+      .with_max_interval(Duration::from_millis(60000))
+      .with_max_elapsed_time(None)
+      .with_randomization_factor(0.)
+      .build();
+
+      let mut channels : HashSet<String> = Default::default();
+      loop {
+        let retry_wait = backoff.next_backoff();
+        let message = match spawn_irc_client(&network, &mut channels, &out_tx, &in_rx, &ctx).await {
+          Ok(true) => break,
+          Ok(false) => {
+            backoff.reset();
+            backoff.next_backoff();
+            format!("Lost connection, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32()))
+          },
+          Err(e) => {
+            error!("Failed to connect to {}: {:?}", network.server, e);
+            format!("Failed to connect, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32()))
+          }
+        };
+        for channel in channels.iter() {
+          super::display_system_message_in_chat(&out_tx, channel_name(&network.server, channel), ProviderName::Irc, message.to_owned(), MessageType::Error, &ctx);
+        }
+        if let Some(duration) = retry_wait {
+          sleep(duration).await;
+        }
+      }
+    });
+
+    IrcConnection { handle, in_tx }
+  }
+
+  fn send_to_connection(&self, channel_name: &str, msg: OutgoingMessage) -> Result<(), TrySendError<OutgoingMessage>> {
+    match self.channels.get(channel_name).and_then(|(key, _)| self.connections.get(key)) {
+      Some(connection) => connection.in_tx.try_send(msg),
+      None => Err(TrySendError::Closed(msg))
+    }
+  }
+}
+
+impl ChatManagerRx for IrcChatManager {
+  fn in_tx(&mut self) -> &mut Sender<OutgoingMessage> {
+    &mut self.in_tx
+  }
+  fn out_rx(&mut self) -> &mut Receiver<IncomingMessage> {
+    &mut self.out_rx
+  }
+}
+
+impl ChatProvider for IrcChatManager {
+  fn provider_name(&self) -> ProviderName {
+    ProviderName::Irc
+  }
+
+  fn username(&self) -> &str {
+    ""
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let Channel::Irc { irc, shared } = channel else { return; };
+
+    if self.connections.get(&irc.network).is_none_or(|c| c.handle.is_finished()) {
+      let connection = self.spawn_connection(&irc.network);
+      self.connections.insert(irc.network.to_owned(), connection);
+    }
+    self.channels.insert(shared.channel_name.to_owned(), (irc.network.to_owned(), irc.channel.to_owned()));
+    if let Err(e) = self.send_to_connection(&shared.channel_name, OutgoingMessage::Join { channel_name: irc.channel.to_owned() }) {
+      warn!("Failed to join {}: {}", shared.channel_name, e);
+    }
+
+    if shared.transient.is_none() {
+      shared.transient = Some(Default::default());
+    }
+  }
+
+  fn leave_channel(&mut self, channel_name: &str) {
+    if let Some((_, irc_channel)) = self.channels.get(channel_name)
+      && let Err(e) = self.send_to_connection(channel_name, OutgoingMessage::Leave { channel_name: irc_channel.to_owned() }) {
+      info!("Failed to leave channel {}: {}", channel_name, e);
+    }
+    self.channels.remove(channel_name);
+  }
+
+  fn send_chat(&mut self, channel_name: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    let irc_channel = self.channels.get(channel_name).map(|(_, c)| c.to_owned()).unwrap_or_default();
+    self.send_to_connection(channel_name, OutgoingMessage::Chat { channel: irc_channel, message: message.to_owned() })
+  }
+
+  fn supported_commands(&self) -> &'static [&'static str] {
+    &["me"]
+  }
+
+  fn close(&mut self) {
+    for (_, connection) in self.connections.drain() {
+      if !connection.handle.is_finished() && let Err(e) = connection.in_tx.try_send(OutgoingMessage::Quit {}) {
+        info!("Failed to send quit: {}", e);
+      }
+    }
+  }
+}
+
+/// Runs one connection to the network until it drops (`Ok(false)`) or a quit is requested (`Ok(true)`).
+/// `channels` holds the IRC channels to be in, and is rejoined after registering.
+pub async fn spawn_irc_client(network: &IrcNetwork, channels: &mut HashSet<String>, tx: &Sender<IncomingMessage>, rx: &Receiver<OutgoingMessage>, ctx: &Context) -> Result<bool, anyhow::Error> {
+  let mut client = Client::from_config(Config {
+    nickname: Some(network.nickname.to_owned()),
+    username: Some(network.nickname.to_owned()),
+    realname: Some(network.nickname.to_owned()),
+    server: Some(network.server.to_owned()),
+    port: Some(network.port),
+    password: if !network.use_sasl && !network.password.is_empty() { Some(network.password.to_owned()) } else { None },
+    use_tls: Some(network.use_tls),
+    ping_time: Some(180),
+    ping_timeout: Some(90),
+    ..Default::default()
+  }).await?;
+  let mut stream = client.stream()?;
+  let sender = client.sender();
+
+  if network.use_sasl {
+    // registration is held open with CAP until authenticated
+    sender.send_cap_req(&[Capability::Sasl])?;
+    sender.send(Command::NICK(network.nickname.to_owned()))?;
+    sender.send(Command::USER(network.nickname.to_owned(), "0".to_owned(), network.nickname.to_owned()))?;
+  } else {
+    client.identify()?;
+  }
+
+  let mut registered = false;
+  // users seen in each joined channel, so a QUIT can be shown in the right channels
+  let mut members : HashMap<String, HashSet<String>> = Default::default();
+
+  loop {
+    tokio::select! {
+      result = stream.next() => {
+        let message = match result {
+          Some(Ok(message)) => message,
+          Some(Err(e)) => {
+            error!("IRC error from {}: {:?}", network.server, e);
+            return Ok(false);
+          },
+          None => return Ok(false)
+        };
+        trace!("{}", message);
+        let nick = message.source_nickname().unwrap_or_default().to_owned();
+        let is_me = nick.eq_ignore_ascii_case(client.current_nickname());
+        match message.command {
+          Command::CAP(_, CapSubCommand::ACK, _, _) => {
+            sender.send_sasl_plain()?;
+          },
+          Command::CAP(_, CapSubCommand::NAK, _, _) => {
+            warn!("{} does not support SASL", network.server);
+            sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+          },
+          Command::AUTHENTICATE(ref data) if data == "+" => {
+            let credentials = format!("{0}\0{0}\0{1}", network.nickname, network.password);
+            sender.send_sasl(general_purpose::STANDARD.encode(credentials))?;
+          },
+          Command::Response(Response::RPL_SASLSUCCESS, _) => {
+            sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+          },
+          Command::Response(Response::ERR_SASLFAIL, _) | Command::Response(Response::ERR_SASLTOOLONG, _) => {
+            for channel in channels.iter() {
+              super::display_system_message_in_chat(tx, channel_name(&network.server, channel), ProviderName::Irc, "SASL authentication failed.".to_owned(), MessageType::Error, ctx);
+            }
+            sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+          },
+          Command::Response(Response::RPL_WELCOME, _) => {
+            registered = true;
+            for channel in channels.iter() {
+              sender.send_join(channel)?;
+            }
+          },
+          Command::Response(Response::RPL_NAMREPLY, ref args) => {
+            if let [.., channel, names] = args.as_slice() {
+              let channel = channel.to_lowercase();
+              for name in names.split_whitespace().map(|n| n.trim_start_matches(['~', '&', '@', '%', '+'])) {
+                members.entry(channel.to_owned()).or_default().insert(name.to_lowercase());
+                send(tx, IncomingMessage::UserJoin { channel: channel_name(&network.server, &channel), username: name.to_lowercase(), display_name: name.to_owned() });
+              }
+            }
+          },
+          Command::JOIN(ref channel, _, _) => {
+            let channel = channel.to_lowercase();
+            if is_me {
+              super::display_system_message_in_chat(tx, channel_name(&network.server, &channel), ProviderName::Irc, format!("Joined {channel}."), MessageType::Information, ctx);
+            }
+            members.entry(channel.to_owned()).or_default().insert(nick.to_lowercase());
+            send(tx, IncomingMessage::UserJoin { channel: channel_name(&network.server, &channel), username: nick.to_lowercase(), display_name: nick });
+          },
+          Command::PART(ref channel, _) => {
+            let channel = channel.to_lowercase();
+            if let Some(users) = members.get_mut(&channel) {
+              users.remove(&nick.to_lowercase());
+            }
+            send(tx, IncomingMessage::UserLeave { channel: channel_name(&network.server, &channel), username: nick.to_lowercase(), display_name: nick });
+          },
+          Command::QUIT(_) => {
+            for (channel, users) in members.iter_mut() {
+              if users.remove(&nick.to_lowercase()) {
+                send(tx, IncomingMessage::UserLeave { channel: channel_name(&network.server, channel), username: nick.to_lowercase(), display_name: nick.to_owned() });
+              }
+            }
+          },
+          Command::PRIVMSG(ref target, _) if !target.starts_with(['#', '&']) => {
+            debug!("ignoring private message from {}", nick);
+          },
+          Command::PRIVMSG(ref target, ref msg) => {
+            let channel = target.to_lowercase();
+            // CTCP ACTION (/me)
+            let msg = super::strip_ctcp_action(msg);
+            send(tx, IncomingMessage::PrivMsg { message: ChatMessage {
+              provider: ProviderName::Irc,
+              channel: channel_name(&network.server, &channel),
+              username: nick.to_lowercase(),
+              timestamp: chrono::Utc::now(),
+              message: msg.to_owned(),
+              profile: UserProfile { display_name: Some(nick), ..Default::default() },
+              ..Default::default()
+            }});
+          },
+          Command::ERROR(ref msg) => {
+            error!("IRC error from {}: {}", network.server, msg);
+          },
+          _ => debug!("Unhandled IRC message: {:?}", message)
+        }
+        ctx.request_repaint();
+      },
+      Ok(out_msg) = rx.recv() => {
+        match out_msg {
+          OutgoingMessage::Chat { channel, message } => {
+            if let Err(e) = sender.send_privmsg(&channel, &message) {
+              info!("Error sending IRC message: {}", e);
+            }
+            // the server does not echo our own messages back
+            send(tx, IncomingMessage::PrivMsg { message: ChatMessage {
+              provider: ProviderName::Irc,
+              channel: channel_name(&network.server, &channel),
+              username: client.current_nickname().to_lowercase(),
+              timestamp: chrono::Utc::now(),
+              message: super::strip_ctcp_action(&message).to_owned(),
+              profile: UserProfile { display_name: Some(client.current_nickname().to_owned()), ..Default::default() },
+              ..Default::default()
+            }});
+            ctx.request_repaint();
+          },
+          OutgoingMessage::Join { channel_name } => {
+            if channels.insert(channel_name.to_owned()) && registered {
+              sender.send_join(&channel_name)?;
+            }
+          },
+          OutgoingMessage::Leave { channel_name } => {
+            if channels.remove(&channel_name) && registered {
+              sender.send_part(&channel_name)?;
+            }
+            members.remove(&channel_name);
+          },
+          OutgoingMessage::Quit {} => {
+            sender.send_quit("Leaving")?;
+            info!("quit command received");
+            // outgoing messages are only flushed while the stream is polled
+            _ = tokio::time::timeout(Duration::from_secs(2), async { while stream.next().await.is_some() {} }).await;
+            return Ok(true);
+          },
+          OutgoingMessage::TwitchJoin { .. } | OutgoingMessage::Reply { .. } | OutgoingMessage::Moderate { .. } | OutgoingMessage::Whisper { .. }
+            | OutgoingMessage::WhisperHistory { .. } | OutgoingMessage::WhisperRead { .. } | OutgoingMessage::Vote { .. } => {}
+        }
+      }
+    }
+  }
+}
+
+fn send(tx: &Sender<IncomingMessage>, msg: IncomingMessage) {
+  if let Err(e) = tx.try_send(msg) {
+    info!("Send failure: {}", e);
+  }
+}
@@ -0,0 +1,35 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use anyhow::anyhow;
+use serde_json::Value;
+
+pub const HELIX_BASE_URL : &str = "https://api.twitch.tv/helix";
+
+/// Twitch user id for a login name
+pub(super) async fn get_helix_user_id(client: &reqwest::Client, base_url: &str, token: &str, client_id: &str, username: &str) -> Result<String, anyhow::Error> {
+  let json = send_helix_request(client.get(format!("{base_url}/users?login={}", username.to_lowercase())), token, client_id).await?;
+  json["data"][0]["id"].as_str()
+    .map(|id| id.to_owned())
+    .ok_or_else(|| anyhow!("user not found"))
+}
+
+/// Sends the request with the user's token, returning the response json or Twitch's error message
+pub(super) async fn send_helix_request(request: reqwest::RequestBuilder, token: &str, client_id: &str) -> Result<Value, anyhow::Error> {
+  let resp = request
+    .bearer_auth(token)
+    .header("Client-Id", client_id)
+    .send().await?;
+  let status = resp.status();
+  let text = resp.text().await?;
+  let json = serde_json::from_str::<Value>(&text).unwrap_or_default();
+  if status.is_success() {
+    Ok(json)
+  } else {
+    // e.g. {"error":"Bad Request","status":400,"message":"The user specified in the user_id field is already banned."}
+    Err(anyhow!(json["message"].as_str().map(|x| x.to_owned()).unwrap_or_else(|| status.to_string())))
+  }
+}
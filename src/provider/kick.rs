@@ -0,0 +1,547 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::{collections::HashMap, path::Path};
+use async_channel::{Sender, Receiver};
+use backoff::backoff::Backoff;
+use chrono::{DateTime, Utc};
+use egui::{Color32, Context};
+use futures::{StreamExt, SinkExt};
+use itertools::Itertools;
+use regex::Regex;
+use tokio::{runtime::{Handle, Runtime}, time::sleep, time::Duration};
+use tokio_tungstenite::{tungstenite::{client::IntoClientRequest, Message}, connect_async_tls_with_config};
+use tracing::{trace, info, warn, error, debug};
+use crate::emotes::{fetch, Emote, EmoteLoader, EmoteRequest, EmoteSource};
+use super::{IncomingMessage, OutgoingMessage, ProviderName, ChatMessage, UserProfile, MessageType, ChatManagerRx, ChatProvider, ProviderInfo, convert_color_hex, channel::{Channel, ChannelShared, ChannelStatus, ChannelTransient, IrcNetwork, KickChannel}};
+
+/// Kick channels are keyed by slug with this prefix, so they don't collide with a Twitch channel of the same name
+pub const KICK_CHANNEL_PREFIX : &str = "KICK:";
+pub const KICK_API_URL : &str = "https://kick.com";
+pub const KICK_WS_URL : &str = "wss://ws-us2.pusher.com/app/32cbd69e4b950bf97679?protocol=7&client=js&version=8.4.0-rc2&flash=false";
+
+const KICK_EMOTE_URL : &str = "https://files.kick.com/emotes";
+const NOT_SUPPORTED_MESSAGE : &str = "Sending messages to Kick chat is not supported.";
+
+pub fn init_channel(slug : &str) -> Channel {
+  let slug = slug.trim_start_matches(KICK_CHANNEL_PREFIX).to_lowercase();
+  Channel::Kick {
+    shared: ChannelShared {
+      channel_name: format!("{KICK_CHANNEL_PREFIX}{slug}"),
+      show_in_mentions_tab: true,
+      show_tab_when_offline: false,
+      mute_notifications: false,
+      send_history: Default::default(),
+      send_history_ix: None,
+      transient: None,
+      users: Default::default()
+    },
+    kick: KickChannel {
+      slug
+    }
+  }
+}
+
+fn emote_url(id: &str) -> String {
+  format!("{KICK_EMOTE_URL}/{id}/fullsize")
+}
+
+pub struct KickInfo;
+
+impl ProviderInfo for KickInfo {
+  fn display_name(&self) -> &'static str {
+    "Kick"
+  }
+
+  fn archive_dir(&self) -> &'static str {
+    "kick"
+  }
+
+  fn color(&self) -> Color32 {
+    Color32::from_rgba_unmultiplied(83, 252, 24, 255)
+  }
+
+  fn init_channel(&self, channel_name: &str, _network: IrcNetwork) -> Channel {
+    init_channel(channel_name)
+  }
+
+  fn badge_tooltip(&self, badge: &Emote) -> Option<String> {
+    Some(match badge.name.split_once('/') {
+      Some(("subscriber", months)) => format!("{months} Month Sub"),
+      _ => badge.name.to_owned()
+    })
+  }
+
+  fn message_emote(&self, id: String, name: String) -> Option<Emote> {
+    Some(Emote { url: emote_url(&id), name, id, path: "kick/".to_owned(), source: EmoteSource::Provider(ProviderName::Kick), ..Default::default() })
+  }
+}
+
+pub struct KickChatManager {
+  handles: HashMap<String, tokio::task::JoinHandle<()>>,
+  outgoing_handle: tokio::task::JoinHandle<()>,
+  runtime: Handle,
+  ctx: Context,
+  out_tx: Sender<IncomingMessage>,
+  pub out_rx: Receiver<IncomingMessage>,
+  pub in_tx: Sender<OutgoingMessage>
+}
+
+impl KickChatManager {
+  pub fn new(runtime: &Runtime, ctx: &Context) -> Self {
+    let (out_tx, out_rx) = async_channel::bounded::<IncomingMessage>(10000);
+    let (in_tx, in_rx) = async_channel::bounded::<OutgoingMessage>(10000);
+
+    // chat is read-only, anything sent to a channel is answered with an error
+    let tx = out_tx.clone();
+    let context = ctx.clone();
+    let outgoing_handle = runtime.spawn(async move {
+      while let Ok(msg) = in_rx.recv().await {
+        let channel = match msg {
+          OutgoingMessage::Quit {} => break,
+          OutgoingMessage::Chat { channel, .. } | OutgoingMessage::Reply { channel, .. }
+            | OutgoingMessage::Moderate { channel, .. } | OutgoingMessage::Vote { channel, .. } => channel,
+          _ => continue
+        };
+        super::display_system_message_in_chat(&tx, channel, ProviderName::Kick, NOT_SUPPORTED_MESSAGE.to_owned(), MessageType::Error, &context);
+      }
+    });
+
+    Self {
+      handles: Default::default(),
+      outgoing_handle,
+      runtime: runtime.handle().clone(),
+      ctx: ctx.clone(),
+      out_tx,
+      out_rx,
+      in_tx
+    }
+  }
+
+  fn spawn_channel_client(&self, channel_name: &str, slug: &str) -> tokio::task::JoinHandle<()> {
+    let channel_name = channel_name.to_owned();
+    let slug = slug.to_owned();
+    let out_tx = self.out_tx.clone();
+    let ctx = self.ctx.clone();
+
+    self.runtime.spawn(async move {
+      let client = reqwest::Client::new();
+      let mut backoff = backoff::ExponentialBackoffBuilder::new()
+      .with_initial_interval(Duration::from_millis(3000))
+      .with_max_interval(Duration::from_millis(60000))
+      .with_max_elapsed_time(None)
+      .with_randomization_factor(0.)
+      .build();
+
+      loop {
+        let retry_wait = backoff.next_backoff();
+        let result = match get_channel_info(KICK_API_URL, &slug, &client).await {
+          Ok(info) => {
+            if let Err(e) = out_tx.try_send(IncomingMessage::StreamingStatus { channel: channel_name.to_owned(), status: Some(info.status()) }) {
+              warn!("error sending kick stream status: {}", e);
+            }
+            if let Err(e) = out_tx.try_send(IncomingMessage::ChannelBadges { channel: channel_name.to_owned(), badges: info.badge_emotes(&channel_name) }) {
+              warn!("error sending kick badges: {}", e);
+            }
+            spawn_websocket_chat_client(KICK_WS_URL, &channel_name, &info, &out_tx, &ctx).await
+          },
+          Err(e) => Err(e)
+        };
+        let message = match result {
+          Ok(()) => {
+            backoff.reset();
+            backoff.next_backoff();
+            format!("Lost connection, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32()))
+          },
+          Err(e) => {
+            error!("error connecting to Kick chat for {}: {:?}", slug, e);
+            format!("Failed to connect, retrying in {:.3?} seconds...", retry_wait.map(|x| x.as_secs_f32()))
+          }
+        };
+        super::display_system_message_in_chat(&out_tx, channel_name.to_owned(), ProviderName::Kick, message, MessageType::Error, &ctx);
+        if let Some(duration) = retry_wait {
+          sleep(duration).await;
+        }
+      }
+    })
+  }
+}
+
+impl ChatManagerRx for KickChatManager {
+  fn in_tx(&mut self) -> &mut Sender<OutgoingMessage> {
+    &mut self.in_tx
+  }
+  fn out_rx(&mut self) -> &mut Receiver<IncomingMessage> {
+    &mut self.out_rx
+  }
+}
+
+impl ChatProvider for KickChatManager {
+  fn provider_name(&self) -> ProviderName {
+    ProviderName::Kick
+  }
+
+  fn username(&self) -> &str {
+    ""
+  }
+
+  fn can_send(&self, _channel_name: &str) -> Result<(), String> {
+    Err(NOT_SUPPORTED_MESSAGE.to_owned())
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let Channel::Kick { kick, shared } = channel else { return; };
+
+    if self.handles.get(&shared.channel_name).is_none_or(|h| h.is_finished()) {
+      if let Some(handle) = self.handles.remove(&shared.channel_name) {
+        handle.abort();
+      }
+      self.handles.insert(shared.channel_name.to_owned(), self.spawn_channel_client(&shared.channel_name, &kick.slug));
+    }
+
+    if shared.transient.is_none() {
+      shared.transient = Some(ChannelTransient {
+        channel_emotes: None,
+        badge_emotes: None,
+        status: None,
+        chat_modes: Default::default(),
+        is_moderator: false
+      });
+    }
+  }
+
+  fn leave_channel(&mut self, channel_name: &str) {
+    if let Some(handle) = self.handles.remove(channel_name) {
+      handle.abort();
+    }
+  }
+
+  fn request_channel_emotes(&self, channel: &Channel, emote_loader: &EmoteLoader, force_redownload: bool) {
+    if let Channel::Kick { kick, shared } = channel
+      && let Err(e) = emote_loader.tx.try_send(EmoteRequest::KickEmoteListRequest {
+        channel_name: shared.channel_name.to_owned(),
+        slug: kick.slug.to_owned(),
+        force_redownload
+      }) {
+      error!("Failed to request emote json for {} due to error {:?}", shared.channel_name, e);
+    }
+  }
+
+  fn close(&mut self) {
+    for (_, handle) in self.handles.drain() {
+      handle.abort();
+    }
+    self.outgoing_handle.abort();
+  }
+}
+
+pub async fn get_channel_info(api_url: &str, slug: &str, client: &reqwest::Client) -> Result<KickChannelInfo, anyhow::Error> {
+  let json = fetch::get_json_from_url(&format!("{}/api/v2/channels/{slug}", api_url.trim_end_matches('/')), None, None, client, true).await?;
+  Ok(serde_json::from_str::<KickChannelInfo>(&json)?)
+}
+
+/// Connects to Kick's Pusher websocket and forwards chat and live status events for one channel.
+/// Returns Ok when the connection is closed.
+pub async fn spawn_websocket_chat_client(ws_url: &str, channel_name: &str, info: &KickChannelInfo, tx : &Sender<IncomingMessage>, ctx: &Context) -> Result<(), anyhow::Error> {
+  let request = ws_url.into_client_request()?;
+  let (mut socket, _) = connect_async_tls_with_config(request, None, false, None).await?;
+  let emote_regex = Regex::new(r"\[emote:(\d+):([^\]]+)\]")?;
+
+  while let Some(result) = socket.next().await {
+    let message = match result {
+      Ok(message) => message,
+      Err(e) => {
+        error!("Websocket error: {:?}", e);
+        return Ok(());
+      }
+    };
+    if message.is_ping() {
+      trace!("Received Ping: {:?}", message);
+      socket.send(Message::Pong(message.into_data())).await
+        .inspect_err(|f| info!("socket send Pong error: {}", f))?;
+      continue;
+    }
+    else if message.is_close() {
+      return Ok(());
+    }
+    let Ok(message) = message.into_text().inspect_err(|f| info!("websocket error: {}", f)) else { continue; };
+    let Ok(event) = serde_json::from_str::<PusherEvent>(&message).inspect_err(|f| info!("json parse error: {}\n {}", f, message)) else { continue; };
+
+    match event.event.as_str() {
+      "pusher:connection_established" => {
+        for channel in [format!("chatrooms.{}.v2", info.chatroom.id), format!("channel.{}", info.id)] {
+          socket.send(Message::Text(serde_json::json!({ "event": "pusher:subscribe", "data": { "auth": "", "channel": channel } }).to_string())).await
+            .inspect_err(|f| info!("socket send error: {f}"))?;
+        }
+        super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::Kick, "Connected to chat.".to_owned(), MessageType::Information, ctx);
+      },
+      "pusher:ping" => {
+        socket.send(Message::Text(r#"{"event":"pusher:pong","data":{}}"#.to_owned())).await
+          .inspect_err(|f| info!("socket send error: {f}"))?;
+      },
+      "App\\Events\\ChatMessageEvent" => {
+        if let Some(msg) = event.parse_data::<KickChatMessage>() {
+          let (text, emotes) = parse_message_emotes(&emote_regex, &msg.content);
+          if !emotes.is_empty() && let Err(e) = tx.try_send(IncomingMessage::MsgEmotes { provider: ProviderName::Kick, emote_ids: emotes }) {
+            info!("Send failure for emotes: {}", e);
+          }
+          let badges = msg.sender.identity.badges.iter().map(|b| info.badge_name(b)).collect_vec();
+          let cmsg = ChatMessage {
+            provider: ProviderName::Kick,
+            channel: channel_name.to_owned(),
+            username: msg.sender.slug.to_lowercase(),
+            timestamp: DateTime::parse_from_rfc3339(&msg.created_at).map(|x| x.with_timezone(&Utc)).unwrap_or_else(|_| Utc::now()),
+            message: text,
+            profile: UserProfile {
+              badges: if !badges.is_empty() { Some(badges) } else { None },
+              display_name: Some(msg.sender.username),
+              color: convert_color_hex(msg.sender.identity.color.as_ref())
+            },
+            id: msg.id,
+            ..Default::default()
+          };
+          if let Err(e) = tx.try_send(IncomingMessage::PrivMsg { message: cmsg }) {
+            info!("Send failure for MSG: {}", e);
+          }
+        }
+      },
+      "App\\Events\\MessageDeletedEvent" => {
+        if let Some(msg) = event.parse_data::<KickMessageDeleted>() {
+          if let Err(e) = tx.try_send(IncomingMessage::MessageDeleted { channel: channel_name.to_owned(), id: msg.message.id }) {
+            info!("Send failure for message deletion: {}", e);
+          }
+        }
+      },
+      "App\\Events\\UserBannedEvent" => {
+        if let Some(msg) = event.parse_data::<KickUserBanned>() {
+          if let Err(e) = tx.try_send(IncomingMessage::UserMuted { channel: channel_name.to_owned(), username: msg.user.slug.to_lowercase() }) {
+            info!("Send failure for ban: {}", e);
+          }
+        }
+      },
+      "App\\Events\\StreamerIsLive" => {
+        let livestream = event.parse_data::<KickStreamerIsLive>().map(|x| x.livestream);
+        let status = ChannelStatus {
+          is_live: true,
+          title: livestream.as_ref().and_then(|x| x.session_title.to_owned()),
+          started_at: livestream.and_then(|x| x.created_at),
+          ..Default::default()
+        };
+        if let Err(e) = tx.try_send(IncomingMessage::StreamingStatus { channel: channel_name.to_owned(), status: Some(status) }) {
+          warn!("error sending kick stream status: {}", e);
+        }
+      },
+      "App\\Events\\StopStreamBroadcast" => {
+        if let Err(e) = tx.try_send(IncomingMessage::StreamingStatus { channel: channel_name.to_owned(), status: Some(ChannelStatus { is_live: false, ..Default::default() }) }) {
+          warn!("error sending kick stream status: {}", e);
+        }
+      },
+      "pusher_internal:subscription_succeeded" => debug!("subscribed to kick channel {:?}", event.channel),
+      "pusher:error" => warn!("kick websocket error: {}", message),
+      _ => debug!("unknown kick event: {:?}", message)
+    }
+    ctx.request_repaint();
+  }
+  Ok(())
+}
+
+/// Replaces inline `[emote:id:name]` tags with the emote name, returning the text and the (id, name) of each emote
+pub fn parse_message_emotes(emote_regex: &Regex, content: &str) -> (String, Vec<(String, String)>) {
+  let emotes = emote_regex.captures_iter(content)
+    .filter_map(|caps| Some((caps.get(1)?.as_str().to_owned(), caps.get(2)?.as_str().to_owned())))
+    .unique()
+    .collect_vec();
+  let text = emote_regex.replace_all(content, " $2 ");
+  (text.split_whitespace().join(" "), emotes)
+}
+
+pub async fn load_kick_emotes(channel_name: &str, slug: &str, cache_path: &Path, client: &reqwest::Client, force_redownload: bool) -> Result<HashMap<String, Emote>, anyhow::Error> {
+  let json_path = &cache_path.join(format!("kick-emotes-{slug}.json"));
+  let json = fetch::get_json_from_url(&format!("{KICK_API_URL}/emotes/{slug}"), json_path.to_str(), None, client, force_redownload).await?;
+  let sets = serde_json::from_str::<Vec<KickEmoteSet>>(&json)?;
+  let mut result : HashMap<String, Emote> = Default::default();
+  for emote in sets.into_iter().flat_map(|set| set.emotes) {
+    let id = emote.id.to_string();
+    result.insert(emote.name.to_owned(), Emote {
+      name: emote.name,
+      url: emote_url(&id),
+      id,
+      path: "kick/".to_owned(),
+      source: EmoteSource::Channel,
+      channel_name: channel_name.to_owned(),
+      ..Default::default()
+    });
+  }
+  Ok(result)
+}
+
+#[derive(serde::Deserialize)]
+pub struct KickChannelInfo {
+  pub id: u64,
+  pub slug: String,
+  pub chatroom: KickChatroom,
+  pub livestream: Option<KickLivestream>,
+  #[serde(default)]
+  pub subscriber_badges: Vec<KickSubscriberBadge>
+}
+
+impl KickChannelInfo {
+  pub fn status(&self) -> ChannelStatus {
+    match self.livestream.as_ref() {
+      Some(livestream) => ChannelStatus {
+        game_name: livestream.categories.first().map(|x| x.name.to_owned()),
+        is_live: livestream.is_live,
+        title: livestream.session_title.to_owned(),
+        viewer_count: livestream.viewer_count,
+        started_at: livestream.created_at.to_owned(),
+        ..Default::default()
+      },
+      None => ChannelStatus::default()
+    }
+  }
+
+  /// The channel's subscriber badges, named to match `badge_name`
+  pub fn badge_emotes(&self, channel_name: &str) -> HashMap<String, Emote> {
+    self.subscriber_badges.iter().map(|badge| {
+      let name = format!("subscriber/{}", badge.months);
+      (name.to_owned(), Emote {
+        name,
+        id: format!("{}__{}", self.slug, badge.id),
+        url: badge.badge_image.src.to_owned(),
+        path: "kick-badge/".to_owned(),
+        source: EmoteSource::ChannelBadge,
+        channel_name: channel_name.to_owned(),
+        ..Default::default()
+      })
+    }).collect()
+  }
+
+  /// Subscriber badges map to the channel's badge for the longest tier the user has reached, e.g. `subscriber/6`
+  fn badge_name(&self, badge: &KickBadge) -> String {
+    match (badge.r#type.as_str(), badge.count) {
+      ("subscriber", Some(count)) => match self.subscriber_badges.iter().filter(|b| b.months <= count).max_by_key(|b| b.months) {
+        Some(tier) => format!("subscriber/{}", tier.months),
+        None => badge.r#type.to_owned()
+      },
+      _ => badge.r#type.to_owned()
+    }
+  }
+}
+
+#[derive(serde::Deserialize)]
+pub struct KickChatroom {
+  pub id: u64
+}
+
+#[derive(serde::Deserialize)]
+pub struct KickLivestream {
+  #[serde(default)]
+  is_live: bool,
+  session_title: Option<String>,
+  viewer_count: Option<usize>,
+  created_at: Option<String>,
+  #[serde(default)]
+  categories: Vec<KickCategory>
+}
+
+#[derive(serde::Deserialize)]
+struct KickCategory {
+  name: String
+}
+
+#[derive(serde::Deserialize)]
+pub struct KickSubscriberBadge {
+  id: u64,
+  months: usize,
+  badge_image: KickImage
+}
+
+#[derive(serde::Deserialize)]
+struct KickImage {
+  src: String
+}
+
+#[derive(serde::Deserialize)]
+struct PusherEvent {
+  event: String,
+  data: Option<serde_json::Value>,
+  channel: Option<String>
+}
+
+impl PusherEvent {
+  /// Pusher event data is usually a json encoded string
+  fn parse_data<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+    let result = match self.data.as_ref()? {
+      serde_json::Value::String(data) => serde_json::from_str::<T>(data),
+      data => serde_json::from_value::<T>(data.to_owned())
+    };
+    result.inspect_err(|f| info!("json parse error for {}: {}", self.event, f)).ok()
+  }
+}
+
+#[derive(serde::Deserialize)]
+struct KickChatMessage {
+  id: String,
+  content: String,
+  created_at: String,
+  sender: KickSender
+}
+
+#[derive(serde::Deserialize)]
+struct KickSender {
+  username: String,
+  slug: String,
+  identity: KickIdentity
+}
+
+#[derive(serde::Deserialize)]
+struct KickIdentity {
+  color: Option<String>,
+  #[serde(default)]
+  badges: Vec<KickBadge>
+}
+
+#[derive(serde::Deserialize)]
+struct KickBadge {
+  r#type: String,
+  count: Option<usize>
+}
+
+#[derive(serde::Deserialize)]
+struct KickMessageDeleted {
+  message: KickDeletedMessage
+}
+
+#[derive(serde::Deserialize)]
+struct KickDeletedMessage {
+  id: String
+}
+
+#[derive(serde::Deserialize)]
+struct KickUserBanned {
+  user: KickBannedUser
+}
+
+#[derive(serde::Deserialize)]
+struct KickBannedUser {
+  slug: String
+}
+
+#[derive(serde::Deserialize)]
+struct KickStreamerIsLive {
+  livestream: KickLivestream
+}
+
+#[derive(serde::Deserialize)]
+struct KickEmoteSet {
+  emotes: Vec<KickEmote>
+}
+
+#[derive(serde::Deserialize)]
+struct KickEmote {
+  id: u64,
+  name: String
+}
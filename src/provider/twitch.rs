@@ -5,29 +5,54 @@
  */
 
 use std::collections::{HashSet, HashMap};
-use async_channel::{Receiver, Sender};
+use async_channel::{Receiver, Sender, TrySendError};
 use backoff::backoff::Backoff;
-use egui::Context;
+use egui::{Color32, Context};
 use tracing::{info, trace, error, debug};
 use chrono::{DateTime, Utc};
 use futures::prelude::*;
 use irc::client::prelude::*;
 use itertools::Itertools;
 use tokio::{runtime::Runtime, time::sleep, time::Duration};
-use crate::{provider::{convert_color_hex, ProviderName, ChannelStatus, MessageType}, emotes::fetch::get_json_from_url};
+use crate::{provider::{convert_color_hex, ProviderName, ChannelStatus, MessageType}, emotes::{fetch::get_json_from_url, Emote, EmoteLoader, EmoteRequest, EmoteSource}};
 use tracing_unwrap::{OptionExt, ResultExt};
-use super::{ChatMessage, UserProfile, IncomingMessage, OutgoingMessage, ChatManagerRx, channel::{Channel, ChannelTransient, ChannelShared, TwitchChannel}};
+use super::{ChatMessage, ReplyParent, UserProfile, IncomingMessage, OutgoingMessage, ChatManagerRx, ChatProvider, ProviderInfo, channel::{Channel, ChannelTransient, ChannelShared, ChatModes, IrcNetwork, TwitchChannel}};
+use super::twitch_eventsub::{self, EventSubCommand, EventSubConfig, StatusUpdate};
+use super::helix::HELIX_BASE_URL;
+use super::twitch_moderation::HelixModeration;
+use super::twitch_whispers::HelixWhisper;
 
+pub const TWITCH_CLIENT_ID : &str = "fpj6py15j5qccjs8cm7iz5ljjzp1uf";
 const TWITCH_STATUS_FETCH_INTERVAL_SEC : i64 = 60;
+/// Live/offline changes are pushed by EventSub for subscribed channels, so only title/game/viewers are polled
+const TWITCH_METADATA_FETCH_INTERVAL_SEC : i64 = 300;
 
 struct TwitchChannelData {
     room_id: Option<String>,
     show_offline_chat: bool
 }
 
+/// The IRC client's side of the EventSub client
+struct EventSubLink {
+  commands: Sender<EventSubCommand>,
+  updates: Receiver<StatusUpdate>,
+  /// Channels with stream.online/offline subscriptions
+  live_subscribed: HashSet<String>
+}
+
+impl EventSubLink {
+  fn send(&self, command: EventSubCommand) {
+    if let Err(e) = self.commands.try_send(command) {
+      info!("Failed to send EventSub command: {}", e);
+    }
+  }
+}
+
 pub struct TwitchChatManager {
   handle: tokio::task::JoinHandle<()>,
+  eventsub_handle: tokio::task::JoinHandle<()>,
   pub username: String,
+  token: String,
   pub in_tx: Sender<OutgoingMessage>,
   pub out_rx: Receiver<IncomingMessage>,
 }
@@ -38,6 +63,16 @@ impl TwitchChatManager {
     let (in_tx, in_rx) = async_channel::bounded::<OutgoingMessage>(10000);
     let token2 = token.to_owned();
     let name2 = username.to_owned();
+
+    let (eventsub_tx, eventsub_rx) = async_channel::unbounded::<EventSubCommand>();
+    let (update_tx, update_rx) = async_channel::unbounded::<StatusUpdate>();
+    let eventsub_handle = runtime.spawn(twitch_eventsub::run_eventsub(
+      EventSubConfig::from_env(token, TWITCH_CLIENT_ID),
+      eventsub_rx,
+      out_tx.clone(),
+      update_tx,
+      ctx.clone()));
+    let mut eventsub = EventSubLink { commands: eventsub_tx, updates: update_rx, live_subscribed: Default::default() };
     let ctx = ctx.clone();
 
     let task = runtime.spawn(async move { 
@@ -51,7 +86,7 @@ impl TwitchChatManager {
       let mut channels_joined : HashMap<String,TwitchChannelData> = Default::default();
       loop {
         let retry_wait = backoff.next_backoff();
-        match spawn_irc(&name2, &token2, &out_tx, &in_rx, &mut channels_joined, &ctx).await {
+        match spawn_irc(&name2, &token2, &out_tx, &in_rx, &mut channels_joined, &mut eventsub, &ctx).await {
           Ok(x) => if x { break; } else { 
             backoff.reset(); 
             backoff.next_backoff();
@@ -81,55 +116,144 @@ impl TwitchChatManager {
 
     Self {
         username: username.to_owned(),
+        token: token.to_owned(),
         handle: task,
+        eventsub_handle,
         in_tx,
         out_rx,
     }
   }
+}
 
-  pub fn leave_channel(&mut self, channel_name : &String) {
-    self.in_tx.try_send(OutgoingMessage::Leave { channel_name: channel_name.to_owned() }).expect_or_log("channel failure");
+pub fn init_channel(channel_name : &str) -> Channel {
+  Channel::Twitch { 
+    shared: ChannelShared {
+      channel_name: channel_name.to_lowercase(),
+      show_in_mentions_tab: true,
+      show_tab_when_offline: false,
+      mute_notifications: false,
+      send_history: Default::default(),
+      send_history_ix: None,
+      transient: None,
+      users: Default::default()
+    },
+    twitch: TwitchChannel {
+      room_id: Default::default()
+    }
   }
+}
 
-  pub fn close(&mut self) {
-    if !self.handle.is_finished() {
-      self.in_tx.try_send(OutgoingMessage::Quit {}).expect_or_log("channel failure");
-    }
+pub struct TwitchInfo;
+
+impl ProviderInfo for TwitchInfo {
+  fn display_name(&self) -> &'static str {
+    "Twitch"
   }
 
-  pub fn init_channel(&mut self, channel_name : &str) -> Channel {
-    let mut channel = Channel::Twitch { 
-      shared: ChannelShared {
-        channel_name: channel_name.to_lowercase(),
-        show_in_mentions_tab: true,
-        show_tab_when_offline: false,
-        send_history: Default::default(),
-        send_history_ix: None,
-        transient: None,
-        users: Default::default()
+  fn archive_dir(&self) -> &'static str {
+    "twitch"
+  }
+
+  fn color(&self) -> Color32 {
+    //Color32::from_rgba_unmultiplied(145, 71, 255, 255)
+    Color32::from_rgba_unmultiplied(169, 112, 255, 255)
+  }
+
+  fn init_channel(&self, channel_name: &str, _network: IrcNetwork) -> Channel {
+    init_channel(channel_name)
+  }
+
+  fn supports_replies(&self) -> bool {
+    true
+  }
+
+  fn supports_moderation(&self) -> bool {
+    true
+  }
+
+  fn supports_message_deletion(&self) -> bool {
+    true
+  }
+
+  fn badge_tooltip(&self, badge: &Emote) -> Option<String> {
+    let (name, value) = badge.name.split('/').collect_tuple::<(&str, &str)>().unwrap_or(("", ""));
+    Some(match name {
+      "subscriber" => {
+        let num = value.parse::<usize>().unwrap_or(0);
+        let tier = match num / 1000 {
+          3 => "T3",
+          2 => "T2",
+          _ => "T1",
+        };
+        format!("{} Month Sub ({})", num % 1000, tier)
       },
-      twitch: TwitchChannel {
-        room_id: Default::default()
-      }
-    };
-    if let Channel::Twitch { twitch, shared } = &mut channel {
-        self.open_channel(twitch, shared);
-    }
-    
-    channel
+      "sub-gifter" => format!("{value}\nGift Subs"),
+      "bits" => format!("{value} Bits"),
+      _ => name.to_owned()
+    })
   }
 
-  pub fn open_channel(&mut self, twitch: &TwitchChannel, shared: &mut ChannelShared) {
+  fn message_emote(&self, id: String, name: String) -> Option<Emote> {
+    Some(Emote { name, id, url: "".to_owned(), path: "twitch/".to_owned(), source: EmoteSource::Twitch, ..Default::default() })
+  }
+}
+
+impl ChatProvider for TwitchChatManager {
+  fn provider_name(&self) -> ProviderName {
+    ProviderName::Twitch
+  }
+
+  fn username(&self) -> &str {
+    &self.username
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let Channel::Twitch { twitch, shared } = channel else { return; };
     if shared.transient.is_none() {
             shared.transient = Some(ChannelTransient {
             channel_emotes: None,
             badge_emotes: None,
-            status: None
+            status: None,
+            chat_modes: Default::default(),
+            is_moderator: false
         });
     }
 
     self.in_tx.try_send(OutgoingMessage::TwitchJoin{ channel_name: shared.channel_name.to_owned(), room_id: twitch.room_id.clone(), show_offline_chat: shared.show_tab_when_offline }).expect_or_log("channel failure");
   }
+
+  fn send_reply(&mut self, channel_name: &str, message: &str, reply_to: &ChatMessage) -> Result<(), TrySendError<OutgoingMessage>> {
+    self.in_tx.try_send(OutgoingMessage::Reply { channel: channel_name.to_owned(), message: message.to_owned(), reply_to: reply_to.into() })
+  }
+
+  fn supported_commands(&self) -> &'static [&'static str] {
+    &["me", "w", "timeout", "ban", "unban"]
+  }
+
+  fn request_global_emotes(&self, emote_loader: &EmoteLoader, force_redownload: bool) {
+    if let Err(e) = emote_loader.tx.try_send(EmoteRequest::TwitchGlobalBadgeListRequest { token: self.token.to_owned(), force_redownload }) {
+      error!("Failed to request global badge json due to error {:?}", e);
+    }
+  }
+
+  fn request_channel_emotes(&self, channel: &Channel, emote_loader: &EmoteLoader, force_redownload: bool) {
+    if let Channel::Twitch { twitch, shared } = channel && let Some(room_id) = twitch.room_id.as_ref() 
+    && let Err(e) = emote_loader.tx.try_send(EmoteRequest::TwitchBadgeEmoteListRequest { 
+      channel_id: room_id.to_owned(), 
+      channel_name: shared.channel_name.to_owned(),
+      token: self.token.to_owned(), 
+      force_redownload
+    }) {
+      error!("Failed to request channel badge and emote list for {} due to error: {:?}", shared.channel_name, e);
+    }
+  }
+
+  fn close(&mut self) {
+    if !self.handle.is_finished() {
+      self.in_tx.try_send(OutgoingMessage::Quit {}).expect_or_log("channel failure");
+    }
+    self.eventsub_handle.abort();
+  }
 }
 
 impl ChatManagerRx for TwitchChatManager {
@@ -141,7 +265,7 @@ impl ChatManagerRx for TwitchChatManager {
   }
 }
 
-async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMessage>, rx: &Receiver<OutgoingMessage>, channels: &mut HashMap<String,TwitchChannelData>, ctx: &Context) -> Result<bool, anyhow::Error> {
+async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMessage>, rx: &Receiver<OutgoingMessage>, channels: &mut HashMap<String,TwitchChannelData>, eventsub: &mut EventSubLink, ctx: &Context) -> Result<bool, anyhow::Error> {
   let web_client_builder = reqwest::Client::builder()
       .timeout(Duration::from_secs(30));
   let web_client = web_client_builder.build().unwrap_or_log();
@@ -166,6 +290,8 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
   //super::display_system_message_in_chat(tx, String::new(), ProviderName::Twitch, "Connected to chat.".to_owned(), MessageType::Information);
 
   let mut joined_channels : HashMap<String, bool> = Default::default();
+  let mut room_modes : HashMap<String, ChatModes> = Default::default();
+  let mut user_id : Option<String> = None;
   let mut seen_emote_ids : HashSet<String> = Default::default();
   let mut last_status_check : Option<DateTime<Utc>> = None;
   let mut last_ping_received : DateTime<Utc> = Utc::now();
@@ -181,7 +307,9 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
 
     //TODO: split this out to a separate thread
     // check channel statuses
-    if last_status_check.is_none() || last_status_check.is_some_and(|f| Utc::now().signed_duration_since(f.to_owned()).num_milliseconds() > TWITCH_STATUS_FETCH_INTERVAL_SEC * 1000) {
+    let all_subscribed = channels.iter().all(|(name, data)| data.room_id.is_none() || eventsub.live_subscribed.contains(name));
+    let status_interval_sec = if all_subscribed { TWITCH_METADATA_FETCH_INTERVAL_SEC } else { TWITCH_STATUS_FETCH_INTERVAL_SEC };
+    if last_status_check.is_none() || last_status_check.is_some_and(|f| Utc::now().signed_duration_since(f.to_owned()).num_milliseconds() > status_interval_sec * 1000) {
       let room_ids = channels.values().filter_map(|x| x.room_id.as_ref()).collect_vec();
       if !room_ids.is_empty() {
         last_status_check = Some(Utc::now());
@@ -196,6 +324,7 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
               title: Some(status.title.to_owned()),
               viewer_count: Some(status.viewer_count),
               started_at: Some(status.started_at.to_owned()),
+              ..Default::default()
             }
           }
           else {
@@ -205,6 +334,7 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
               title: None,
               viewer_count: None,
               started_at: None,
+              ..Default::default()
             }
           };
 
@@ -236,46 +366,8 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
                 //let channel = message.
                 // Parse out tags
                 if let Some(tags) = message.tags.as_ref() {
-                  let cmsg = ChatMessage { 
-                    provider: ProviderName::Twitch,
-                    channel: _target.trim_start_matches('#').to_owned(),
-                    username: sender_name.to_owned(),
-                    //tmi-sent-ts
-                    timestamp: get_tag_value(tags, "tmi-sent-ts")
-                      .and_then(|x| x.parse::<usize>().ok())
-                      .and_then(|x| DateTime::from_timestamp(x as i64 / 1000, (x % 1000 * 1000_usize.pow(2)) as u32 ))
-                      //.map(|x| x.)
-                      .unwrap_or_else(chrono::Utc::now),
-                    message: msg.trim_end_matches(['\u{e0000}', '\u{1}']).to_owned(),
-                    profile: get_user_profile(tags),
-                    ..Default::default()
-                  };
-                  if let Some(emote_ids) = get_tag_value(tags, "emotes") && !emote_ids.is_empty() {
-                    //info!("{}", message);
-                    let ids = emote_ids.split('/').filter_map(|x| {
-                      let pair = x.split(':').collect_vec();
-                      if pair.len() < 2 { return None; }
-                      if seen_emote_ids.contains(pair[0]) {
-                        return None;
-                      } else {
-                        seen_emote_ids.insert(pair[0].to_owned());
-                      }
-                      let range = pair[1].split(',').next()
-                        .map(|r| r.split('-').filter_map(|x| match x.parse::<usize>() { Ok(x) => Some(x), Err(_x) => None } ).collect_vec())
-                        .unwrap_or_default();
-                      match range.len() {
-                        //2 => Some((pair[0].to_owned(), msg[range[0]..=range[1]].to_owned())),
-                        2 => { 
-                          let x : String = msg.to_owned().chars().collect_vec().iter().skip(range[0]).take(range[1] - range[0] + 1).collect();
-                          Some((pair[0].to_owned(), x))
-                        },
-                        _ => None
-                      }
-                    }).sorted_by_key(|(_a, b)| b.to_owned()).dedup().collect_vec();
-                    if let Err(e) = tx.try_send(IncomingMessage::MsgEmotes { provider: ProviderName::Twitch, emote_ids: ids }) {
-                      info!("Error sending MsgEmotes: {}", e);
-                    }
-                  }
+                  let cmsg = get_chat_message(tags, _target.trim_start_matches('#'), sender_name, msg);
+                  send_msg_emotes(tx, tags, msg, &mut seen_emote_ids);
                   match tx.try_send(IncomingMessage::PrivMsg { message: cmsg }) {
                     Ok(_) => (),
                     Err(x) => info!("Send failure: {}", x)
@@ -292,12 +384,29 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
               },
               Command::Raw(ref command, ref str_vec) => {
                 //trace!("Recieved Twitch IRC Command: {}", command);
-                if let Some(tags) = message.tags {
-                  let channel_name = str_vec.last().unwrap_or_log().trim_start_matches('#');
+                if let Some((conversation, whisper)) = get_whisper(&message) {
+                  if let Err(e) = tx.try_send(IncomingMessage::Whisper { conversation, message: whisper }) {
+                    info!("Send failure: {}", e);
+                  }
+                }
+                else if let Some(tags) = message.tags {
+                  let channel_name = str_vec.first().map(|x| x.trim_start_matches('#')).unwrap_or_default();
                   let result = match command.as_str() {
+                    "GLOBALUSERSTATE" => {
+                      if let Some(id) = get_tag_value(&tags, "user-id") {
+                        eventsub.send(EventSubCommand::SetUserId { user_id: id.to_owned() });
+                        user_id = Some(id);
+                      }
+                      Ok(())
+                    },
                     "USERSTATE" => {
                       let channel = channel_name.to_owned();
-                      profiles.insert(channel, get_user_profile(&tags));
+                      let profile = get_user_profile(&tags);
+                      let is_moderator = profile.badges.iter().flatten().any(|b| b.starts_with("moderator/") || b.starts_with("broadcaster/"));
+                      if let Err(e) = tx.try_send(IncomingMessage::ModeratorStatus { channel: channel.to_owned(), is_moderator }) {
+                        info!("Send failure: {}", e);
+                      }
+                      profiles.insert(channel, profile);
                       tx.try_send(IncomingMessage::EmoteSets { 
                         provider: ProviderName::Twitch,
                         emote_sets: get_tag_value(&tags, "emote-sets").unwrap_or_log().split(',').map(|x| x.to_owned()).collect::<Vec<String>>() 
@@ -305,11 +414,25 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
                     },
                     "ROOMSTATE" => {
                       if let Some(channel_data) = channels.get_mut(channel_name) && let Some(roomid) = get_tag_value(&tags, "room-id") {
+                        eventsub.send(EventSubCommand::Subscribe { channel: channel_name.to_owned(), room_id: roomid.to_owned() });
                         channel_data.room_id = Some(roomid);
                       }
 
+                      // Only the changed modes are included after the first ROOMSTATE for a channel
+                      let previous_modes = room_modes.get(channel_name).cloned();
+                      let modes = parse_room_modes(&tags, previous_modes.as_ref().unwrap_or(&Default::default()));
+                      if let Some(previous_modes) = previous_modes {
+                        for change in modes.changes_from(&previous_modes) {
+                          super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::Twitch, change, MessageType::Information, ctx);
+                        }
+                      }
+                      room_modes.insert(channel_name.to_owned(), modes.to_owned());
+                      if let Err(e) = tx.try_send(IncomingMessage::ChatModes { channel: channel_name.to_owned(), modes }) {
+                        info!("error sending chat modes: {}", e);
+                      }
+
                       // small delay to not spam twitch API when joining channels at app start
-                      last_status_check = Some(Utc::now() - chrono::Duration::milliseconds(TWITCH_STATUS_FETCH_INTERVAL_SEC * 1000 - 250));
+                      last_status_check = Some(Utc::now() - chrono::Duration::milliseconds(status_interval_sec * 1000 - 250));
                       tx.try_send(IncomingMessage::RoomId { 
                         channel: channel_name.to_owned(),
                         room_id: get_tag_value(&tags, "room-id").unwrap_or_log().to_owned() })
@@ -325,23 +448,37 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
                         }})
                     },
                     "USERNOTICE" => {
-                      if let Some(sys_msg) = get_tag_value(&tags, "system-msg") {
-                        tx.try_send(IncomingMessage::PrivMsg { message: ChatMessage { 
-                          provider: ProviderName::Twitch, 
-                          channel: channel_name.to_owned(), 
-                          timestamp: chrono::Utc::now(), 
-                          message: sys_msg,
-                          msg_type: MessageType::Announcement,
-                          ..Default::default()
-                        }})
-                      } else {
-                        Ok(())
+                      if let Some(msg) = str_vec.get(1) {
+                        send_msg_emotes(tx, &tags, msg, &mut seen_emote_ids);
                       }
+                      get_user_notice_messages(&tags, channel_name, str_vec.get(1)).into_iter()
+                        .try_for_each(|message| tx.try_send(IncomingMessage::PrivMsg { message }))
                     },
                     "CLEARCHAT" => {
-                      tx.try_send(IncomingMessage::UserMuted { 
-                        channel: channel_name.to_owned(), 
-                        username: str_vec[1].to_owned() })
+                      match str_vec.get(1) {
+                        Some(username) => {
+                          let result = tx.try_send(IncomingMessage::UserMuted { 
+                            channel: channel_name.to_owned(), 
+                            username: username.to_owned() });
+                          let notice = match get_tag_value(&tags, "ban-duration") {
+                            Some(duration) => format!("{username} was timed out for {duration} seconds."),
+                            None => format!("{username} was permanently banned.")
+                          };
+                          super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::Twitch, notice, MessageType::Information, ctx);
+                          result
+                        },
+                        None => {
+                          let result = tx.try_send(IncomingMessage::ChatCleared { channel: channel_name.to_owned() });
+                          super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::Twitch, "Chat was cleared by a moderator.".to_owned(), MessageType::Information, ctx);
+                          result
+                        }
+                      }
+                    },
+                    "CLEARMSG" => {
+                      match get_tag_value(&tags, "target-msg-id") {
+                        Some(id) => tx.try_send(IncomingMessage::MessageDeleted { channel: channel_name.to_owned(), id }),
+                        None => Ok(())
+                      }
                     },
                     _ => { debug!("unknown IRC command: {} {}", command, str_vec.join(", ")); Ok(())}
                   };
@@ -362,34 +499,74 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
       },
       Ok(out_msg) = rx.recv() => {
         match out_msg {
-          OutgoingMessage::Chat { channel, message } => { 
-            _ = match &message.chars().next() {
-              Some(x) if *x == ':' => sender.send_privmsg(&channel, format!(" {}", &message)),
-              _ => sender.send_privmsg(&format!("#{channel}"), &message),
-            }.inspect_err(|e| { info!("Error sending twitch IRC message: {}", e)});
-            let profile = profiles.get(&channel).map(|f| f.to_owned()).unwrap_or_default();
-            let cmsg = ChatMessage { 
-              provider: ProviderName::Twitch,
-              channel,
-              username: client.current_nickname().to_owned(), 
-              timestamp: chrono::Utc::now(), 
-              message, 
-              profile,
-              ..Default::default()
-            };
-            match tx.try_send(IncomingMessage::PrivMsg { message: cmsg }) {
-              Ok(_) => (),
-              Err(x) => info!("Send failure: {}", x)
-            };
-            ctx.request_repaint();
+          OutgoingMessage::Chat { channel, message } => send_message(&client, tx, &profiles, channel, message, None, ctx),
+          OutgoingMessage::Reply { channel, message, reply_to } => send_message(&client, tx, &profiles, channel, message, Some(reply_to), ctx),
+          OutgoingMessage::Moderate { channel, action } => {
+            match (channels.get(&channel).and_then(|c| c.room_id.to_owned()), user_id.to_owned()) {
+              (Some(broadcaster_id), Some(moderator_id)) => {
+                let helix = HelixModeration { 
+                  base_url: HELIX_BASE_URL.to_owned(), 
+                  token: token.to_owned(), 
+                  client_id: TWITCH_CLIENT_ID.to_owned(), 
+                  broadcaster_id, 
+                  moderator_id 
+                };
+                let (tx, ctx, web_client) = (tx.clone(), ctx.clone(), web_client.clone());
+                // run the Helix calls off of the IRC loop
+                tokio::spawn(async move {
+                  let (message, msg_type) = match helix.send(&web_client, &action).await {
+                    Ok(()) => (action.success_message(), MessageType::Information),
+                    Err(e) => (action.failure_message(&e.to_string()), MessageType::Error)
+                  };
+                  super::display_system_message_in_chat(&tx, channel, ProviderName::Twitch, message, msg_type, &ctx);
+                });
+              },
+              _ => super::display_system_message_in_chat(tx, channel, ProviderName::Twitch, action.failure_message("channel not joined yet"), MessageType::Error, ctx)
+            }
+          },
+          OutgoingMessage::Whisper { username, message } => {
+            let conversation = username.to_lowercase();
+            match user_id.to_owned() {
+              Some(from_user_id) => {
+                let helix = HelixWhisper {
+                  base_url: HELIX_BASE_URL.to_owned(),
+                  token: token.to_owned(),
+                  client_id: TWITCH_CLIENT_ID.to_owned(),
+                  from_user_id
+                };
+                let (tx, ctx, web_client, nickname) = (tx.clone(), ctx.clone(), web_client.clone(), client.current_nickname().to_owned());
+                tokio::spawn(async move {
+                  // Helix doesn't echo whispers back, so show the sent message once it is accepted
+                  let whisper = match helix.send(&web_client, &username, &message).await {
+                    Ok(()) => ChatMessage { provider: ProviderName::Twitch, username: nickname, message, ..Default::default() },
+                    Err(e) => ChatMessage { provider: ProviderName::Twitch, message: format!("Failed to whisper {username}: {e}"), msg_type: MessageType::Error, ..Default::default() }
+                  };
+                  if let Err(e) = tx.try_send(IncomingMessage::Whisper { conversation, message: whisper }) {
+                    info!("Send failure: {}", e);
+                  }
+                  ctx.request_repaint();
+                });
+              },
+              None => {
+                let whisper = ChatMessage { provider: ProviderName::Twitch, message: format!("Failed to whisper {username}: not logged in yet"), msg_type: MessageType::Error, ..Default::default() };
+                if let Err(e) = tx.try_send(IncomingMessage::Whisper { conversation, message: whisper }) {
+                  info!("Send failure: {}", e);
+                }
+              }
+            }
           },
           OutgoingMessage::Quit {  } => { client.send_quit("Leaving").expect_or_log("Error while quitting IRC server"); info!("quit command received"); return Ok(true); },
           OutgoingMessage::Leave { channel_name } => {
+            eventsub.send(EventSubCommand::Unsubscribe { channel: channel_name.to_owned() });
+            eventsub.live_subscribed.remove(&channel_name);
             leave(&client, tx, &mut joined_channels, &channel_name, ctx);
           },
-          OutgoingMessage::Join { channel_name: _ } => {},
+          OutgoingMessage::Join { channel_name: _ } | OutgoingMessage::WhisperHistory { .. } | OutgoingMessage::WhisperRead { .. } | OutgoingMessage::Vote { .. } => {},
           OutgoingMessage::TwitchJoin { channel_name, room_id, show_offline_chat } => {
             let has_room_id = room_id.is_some();
+            if let Some(room_id) = room_id.as_ref() {
+              eventsub.send(EventSubCommand::Subscribe { channel: channel_name.to_owned(), room_id: room_id.to_owned() });
+            }
             channels.insert(channel_name.to_owned(), TwitchChannelData { room_id, show_offline_chat });
         
             // Join chat to get the roomid (needed for status checks)
@@ -399,6 +576,30 @@ async fn spawn_irc(user_name : &String, token: &String, tx : &Sender<IncomingMes
           }
         };
       },
+      Ok(update) = eventsub.updates.recv() => {
+        match update {
+          StatusUpdate::Disconnected => eventsub.live_subscribed.clear(),
+          StatusUpdate::Subscribed { channel, live_events } => {
+            if live_events {
+              eventsub.live_subscribed.insert(channel);
+            } else {
+              eventsub.live_subscribed.remove(&channel);
+            }
+          },
+          StatusUpdate::Live { channel, is_live } => {
+            if let Some(channel_data) = channels.get(&channel) {
+              let joined = joined_channels.get(&channel).unwrap_or(&false) == &true;
+              if is_live && !joined {
+                join(&client, tx, &mut joined_channels, &channel, ctx);
+              } else if !is_live && joined && !channel_data.show_offline_chat {
+                leave(&client, tx, &mut joined_channels, &channel, ctx);
+              }
+            }
+            // pick up the new title/game shortly
+            last_status_check = Some(Utc::now() - chrono::Duration::milliseconds(status_interval_sec * 1000 - 10000));
+          }
+        }
+      },
       _ = tokio::time::sleep(Duration::from_secs(3)) => {
         if last_ping_received.checked_add_signed(chrono::Duration::minutes(10)).unwrap_or_log() < Utc::now() {
             error!("IRC is unresponsive, reconnecting...");
@@ -418,12 +619,148 @@ fn join(client: &Client, tx: &Sender<IncomingMessage>, joined_channels: &mut Has
     joined_channels.insert(channel.to_owned(), true);
 }
 
+fn send_message(client: &Client, tx: &Sender<IncomingMessage>, profiles: &HashMap<String, UserProfile>, channel: String, message: String, reply_to: Option<ReplyParent>, ctx: &Context) {
+  let (target, text) = match &message.chars().next() {
+    Some(x) if *x == ':' => (channel.to_owned(), format!(" {}", &message)),
+    _ => (format!("#{channel}"), message.to_owned())
+  };
+  let irc_message = Message {
+    tags: reply_to.as_ref().map(|parent| vec![irc::proto::message::Tag("reply-parent-msg-id".to_owned(), Some(parent.id.to_owned()))]),
+    prefix: None,
+    command: Command::PRIVMSG(target, text)
+  };
+  _ = client.send(irc_message).inspect_err(|e| { info!("Error sending twitch IRC message: {}", e)});
+  let profile = profiles.get(&channel).map(|f| f.to_owned()).unwrap_or_default();
+  let cmsg = ChatMessage { 
+    provider: ProviderName::Twitch,
+    channel,
+    username: client.current_nickname().to_owned(), 
+    timestamp: chrono::Utc::now(), 
+    message: super::strip_ctcp_action(&message).to_owned(), 
+    profile,
+    reply_to,
+    ..Default::default()
+  };
+  match tx.try_send(IncomingMessage::PrivMsg { message: cmsg }) {
+    Ok(_) => (),
+    Err(x) => info!("Send failure: {}", x)
+  };
+  ctx.request_repaint();
+}
+
 fn leave(client: &Client, tx: &Sender<IncomingMessage>, joined_channels: &mut HashMap<String, bool>, channel: &String, ctx: &Context) {
     client.send_part(format!("#{channel}")).expect_or_log("failed to leave channel");
     super::display_system_message_in_chat(tx, channel.to_owned(), ProviderName::Twitch, format!("Leaving {channel} chat."), MessageType::Information, ctx);
     joined_channels.insert(channel.to_owned(), false);
 }
 
+fn get_chat_message(tags: &Vec<irc::proto::message::Tag>, channel: &str, username: String, msg: &str) -> ChatMessage {
+  ChatMessage { 
+    provider: ProviderName::Twitch,
+    channel: channel.to_owned(),
+    username,
+    //tmi-sent-ts
+    timestamp: get_tag_value(tags, "tmi-sent-ts")
+      .and_then(|x| x.parse::<usize>().ok())
+      .and_then(|x| DateTime::from_timestamp(x as i64 / 1000, (x % 1000 * 1000_usize.pow(2)) as u32 ))
+      //.map(|x| x.)
+      .unwrap_or_else(chrono::Utc::now),
+    message: super::strip_ctcp_action(msg.trim_end_matches(['\u{e0000}', '\u{1}'])).to_owned(),
+    profile: get_user_profile(tags),
+    id: get_tag_value(tags, "id").unwrap_or_else(super::new_message_id),
+    reply_to: get_tag_value(tags, "reply-parent-msg-id").map(|id| ReplyParent {
+      id,
+      username: get_tag_value(tags, "reply-parent-display-name")
+        .or_else(|| get_tag_value(tags, "reply-parent-user-login"))
+        .unwrap_or_default(),
+      message: get_tag_value(tags, "reply-parent-msg-body").unwrap_or_default()
+    }),
+    ..Default::default()
+  }
+}
+
+/// Conversation name and message for a WHISPER received by the logged in user
+pub fn get_whisper(message: &Message) -> Option<(String, ChatMessage)> {
+  let Command::Raw(command, args) = &message.command else { return None; };
+  if command != "WHISPER" {
+    return None;
+  }
+  let (sender, tags, text) = (message.source_nickname()?, message.tags.as_ref()?, args.get(1)?);
+  Some((sender.to_lowercase(), get_chat_message(tags, "", sender.to_owned(), text)))
+}
+
+/// Builds the chat lines for a USERNOTICE (sub, resub, subgift, raid, announcement, etc): the notice
+/// itself as an announcement, followed by the message the user attached to it if any
+pub fn get_user_notice_messages(tags: &Vec<irc::proto::message::Tag>, channel: &str, user_msg: Option<&String>) -> Vec<ChatMessage> {
+  let user_msg = user_msg.map(|msg| get_chat_message(tags, channel, get_tag_value(tags, "login").unwrap_or_default(), msg));
+  match get_tag_value(tags, "msg-id").as_deref() {
+    // announcements have no system message, the attached message is the announcement
+    Some("announcement") => user_msg.map(|msg| ChatMessage {
+      message: format!("{}: {}", msg.profile.display_name.as_ref().unwrap_or(&msg.username), msg.message),
+      msg_type: MessageType::Announcement,
+      ..msg
+    }).into_iter().collect_vec(),
+    _ => {
+      let notice = get_tag_value(tags, "system-msg").filter(|x| !x.is_empty()).map(|sys_msg| ChatMessage {
+        provider: ProviderName::Twitch,
+        channel: channel.to_owned(),
+        message: sys_msg,
+        msg_type: MessageType::Announcement,
+        ..Default::default()
+      });
+      notice.into_iter().chain(user_msg).collect_vec()
+    }
+  }
+}
+
+/// Applies the chat mode tags present on a ROOMSTATE message to the channel's current modes
+pub fn parse_room_modes(tags: &Vec<irc::proto::message::Tag>, current: &ChatModes) -> ChatModes {
+  let mut modes = current.to_owned();
+  if let Some(sec) = get_tag_value(tags, "slow").and_then(|x| x.parse::<u32>().ok()) {
+    modes.slow = if sec > 0 { Some(sec) } else { None };
+  }
+  // -1 when off, otherwise minutes followed
+  if let Some(min) = get_tag_value(tags, "followers-only").and_then(|x| x.parse::<i64>().ok()) {
+    modes.followers_only = u32::try_from(min).ok();
+  }
+  if let Some(x) = get_tag_value(tags, "emote-only") {
+    modes.emote_only = x == "1";
+  }
+  if let Some(x) = get_tag_value(tags, "subs-only") {
+    modes.subs_only = x == "1";
+  }
+  modes
+}
+
+fn send_msg_emotes(tx: &Sender<IncomingMessage>, tags: &Vec<irc::proto::message::Tag>, msg: &str, seen_emote_ids: &mut HashSet<String>) {
+  if let Some(emote_ids) = get_tag_value(tags, "emotes") && !emote_ids.is_empty() {
+    //info!("{}", message);
+    let ids = emote_ids.split('/').filter_map(|x| {
+      let pair = x.split(':').collect_vec();
+      if pair.len() < 2 { return None; }
+      if seen_emote_ids.contains(pair[0]) {
+        return None;
+      } else {
+        seen_emote_ids.insert(pair[0].to_owned());
+      }
+      let range = pair[1].split(',').next()
+        .map(|r| r.split('-').filter_map(|x| match x.parse::<usize>() { Ok(x) => Some(x), Err(_x) => None } ).collect_vec())
+        .unwrap_or_default();
+      match range.len() {
+        //2 => Some((pair[0].to_owned(), msg[range[0]..=range[1]].to_owned())),
+        2 => { 
+          let x : String = msg.to_owned().chars().collect_vec().iter().skip(range[0]).take(range[1] - range[0] + 1).collect();
+          Some((pair[0].to_owned(), x))
+        },
+        _ => None
+      }
+    }).sorted_by_key(|(_a, b)| b.to_owned()).dedup().collect_vec();
+    if let Err(e) = tx.try_send(IncomingMessage::MsgEmotes { provider: ProviderName::Twitch, emote_ids: ids }) {
+      info!("Error sending MsgEmotes: {}", e);
+    }
+  }
+}
+
 fn get_user_profile(tags: &Vec<irc::proto::message::Tag>) -> UserProfile {
   UserProfile {
     display_name: get_tag_value(tags, "display-name"),
@@ -442,8 +779,8 @@ fn get_tag_value(tags: &Vec<irc::proto::message::Tag>, key: &str) -> Option<Stri
 }
 
 pub fn authenticate() -> String {
-  let client_id = "fpj6py15j5qccjs8cm7iz5ljjzp1uf";
-  let scope = "chat:read chat:edit";
+  let client_id = TWITCH_CLIENT_ID;
+  let scope = "chat:read chat:edit moderator:read:followers channel:read:redemptions channel:read:polls channel:read:predictions channel:read:hype_train moderator:manage:banned_users moderator:manage:chat_messages user:manage:whispers";
   let state = format!("{}", rand::random::<u128>());
   format!("https://id.twitch.tv/oauth2/authorize?client_id={client_id}&redirect_uri=https://dbckr.github.io/GigachatAuth&response_type=token&scope={scope}&state={state}")
 }
@@ -455,7 +792,7 @@ async fn get_channel_statuses(channel_ids : Vec<&String>, token: &String, client
   let url = format!("https://api.twitch.tv/helix/streams?{}", channel_ids.iter().map(|f| format!("user_id={f}")).collect_vec().join("&"));
   let json = match get_json_from_url(&url, None, Some([
     ("Authorization", &format!("Bearer {token}")),
-    ("Client-Id", &"fpj6py15j5qccjs8cm7iz5ljjzp1uf".to_owned())].to_vec()), client, true).await {
+    ("Client-Id", &TWITCH_CLIENT_ID.to_owned())].to_vec()), client, true).await {
       Ok(json) => json,
       Err(e) => { error!("failed getting twitch statuses: {}", e); return Default::default(); }
     };
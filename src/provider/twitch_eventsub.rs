@@ -0,0 +1,328 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::HashMap;
+use async_channel::{Receiver, Sender};
+use backoff::backoff::Backoff;
+use egui::Context;
+use futures::{SinkExt, StreamExt};
+use itertools::Itertools;
+use serde_json::Value;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::{tungstenite::{client::IntoClientRequest, Message}, connect_async_tls_with_config};
+use tracing::{debug, error, info, trace, warn};
+use super::{ChannelEvent, IncomingMessage, ProviderName, channel::ChannelStatus};
+
+pub const EVENTSUB_WS_URL : &str = "wss://eventsub.wss.twitch.tv/ws";
+pub const EVENTSUB_SUBSCRIPTION_URL : &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+/// Overrides for testing against the Twitch CLI mock server (`twitch event websocket start-server`), e.g.
+/// `ws://127.0.0.1:8080/ws` and `http://127.0.0.1:8080/eventsub/subscriptions`
+pub const EVENTSUB_WS_URL_VAR : &str = "GIGACHAT_EVENTSUB_WS_URL";
+pub const EVENTSUB_SUBSCRIPTION_URL_VAR : &str = "GIGACHAT_EVENTSUB_SUBSCRIPTION_URL";
+
+/// Extra time allowed past the keepalive interval before the connection is considered dead
+const KEEPALIVE_GRACE_SEC : u64 = 5;
+
+/// Subscription type and version for every channel. These need no authorization, so each one counts
+/// towards the session's max total cost of 10 and they are limited to the live status.
+const SUBSCRIPTION_TYPES : [(&str, &str); 2] = [
+  ("stream.online", "1"),
+  ("stream.offline", "1"),
+];
+
+/// Subscription type and version that require the user to be the broadcaster, only tried for the user's own channel
+const BROADCASTER_SUBSCRIPTION_TYPES : [(&str, &str); 10] = [
+  ("channel.raid", "1"),
+  ("channel.follow", "2"),
+  ("channel.channel_points_custom_reward_redemption.add", "1"),
+  ("channel.poll.begin", "1"),
+  ("channel.poll.end", "1"),
+  ("channel.prediction.begin", "1"),
+  ("channel.prediction.lock", "1"),
+  ("channel.prediction.end", "1"),
+  ("channel.hype_train.begin", "1"),
+  ("channel.hype_train.end", "1"),
+];
+
+#[derive(Clone)]
+pub struct EventSubConfig {
+  pub ws_url: String,
+  pub subscription_url: String,
+  pub token: String,
+  pub client_id: String
+}
+
+impl EventSubConfig {
+  pub fn from_env(token: &str, client_id: &str) -> Self {
+    Self {
+      ws_url: std::env::var(EVENTSUB_WS_URL_VAR).unwrap_or_else(|_| EVENTSUB_WS_URL.to_owned()),
+      subscription_url: std::env::var(EVENTSUB_SUBSCRIPTION_URL_VAR).unwrap_or_else(|_| EVENTSUB_SUBSCRIPTION_URL.to_owned()),
+      token: token.to_owned(),
+      client_id: client_id.to_owned()
+    }
+  }
+}
+
+/// Sent from the IRC client as channels are joined
+pub enum EventSubCommand {
+  SetUserId { user_id: String },
+  Subscribe { channel: String, room_id: String },
+  Unsubscribe { channel: String }
+}
+
+/// Sent back to the IRC client, which joins/leaves chats and checks stream metadata based on these
+pub enum StatusUpdate {
+  /// Subscriptions were lost with the connection
+  Disconnected,
+  /// `live_events` is false if the stream.online/offline subscriptions could not be created, e.g. past the cost limit,
+  /// so the channel's live status still has to be polled
+  Subscribed { channel: String, live_events: bool },
+  Live { channel: String, is_live: bool }
+}
+
+#[derive(Default)]
+pub struct EventSubState {
+  pub user_id: Option<String>,
+  /// channel name -> room id
+  pub channels: HashMap<String, String>,
+  /// channel name -> subscription ids in the current session
+  pub subscription_ids: HashMap<String, Vec<String>>
+}
+
+pub enum EventSubNotification {
+  Online { started_at: Option<String> },
+  Offline,
+  Event(ChannelEvent)
+}
+
+pub async fn run_eventsub(config: EventSubConfig, commands: Receiver<EventSubCommand>, tx: Sender<IncomingMessage>, updates: Sender<StatusUpdate>, ctx: Context) {
+  let client = reqwest::Client::builder()
+    .timeout(Duration::from_secs(30))
+    .build()
+    .unwrap_or_default();
+  let mut backoff = backoff::ExponentialBackoffBuilder::new()
+    .with_initial_interval(Duration::from_millis(3000))
+    .with_max_interval(Duration::from_millis(60000))
+    .with_max_elapsed_time(None)
+    .with_randomization_factor(0.)
+    .build();
+
+  let mut state = EventSubState::default();
+  let mut url = config.ws_url.to_owned();
+  loop {
+    match spawn_eventsub_client(&config, &url, &mut state, &commands, &tx, &updates, &ctx, &client).await {
+      // subscriptions carry over to the new connection
+      Ok(Some(reconnect_url)) => {
+        backoff.reset();
+        url = reconnect_url;
+      },
+      Ok(None) => return,
+      Err(e) => {
+        warn!("EventSub connection lost: {:?}", e);
+        if let Err(e) = updates.try_send(StatusUpdate::Disconnected) {
+          info!("Send failure: {}", e);
+        }
+        state.subscription_ids.clear();
+        url = config.ws_url.to_owned();
+        if let Some(duration) = backoff.next_backoff() {
+          sleep(duration).await;
+        }
+      }
+    }
+  }
+}
+
+/// Runs one EventSub session. Returns the url to reconnect to if Twitch asks to move the session,
+/// or None if the chat manager was closed.
+#[allow(clippy::too_many_arguments)]
+pub async fn spawn_eventsub_client(config: &EventSubConfig, url: &str, state: &mut EventSubState, commands: &Receiver<EventSubCommand>, tx: &Sender<IncomingMessage>, updates: &Sender<StatusUpdate>, ctx: &Context, client: &reqwest::Client) -> Result<Option<String>, anyhow::Error> {
+  let request = url.into_client_request()?;
+  let (mut socket, _) = connect_async_tls_with_config(request, None, false, None).await?;
+  let mut session_id : Option<String> = None;
+  let mut keepalive = Duration::from_secs(10 + KEEPALIVE_GRACE_SEC);
+
+  loop {
+    tokio::select! {
+      result = tokio::time::timeout(keepalive, socket.next()) => {
+        let message = match result {
+          Ok(Some(Ok(message))) => message,
+          Ok(Some(Err(e))) => return Err(e.into()),
+          Ok(None) => return Err(anyhow::anyhow!("connection closed")),
+          Err(_) => return Err(anyhow::anyhow!("no keepalive received"))
+        };
+        if message.is_ping() {
+          socket.send(Message::Pong(message.into_data())).await?;
+          continue;
+        }
+        else if message.is_close() {
+          return Err(anyhow::anyhow!("connection closed by server"));
+        }
+        let Ok(text) = message.into_text() else { continue; };
+        trace!("{}", text);
+        let Ok(json) = serde_json::from_str::<Value>(&text).inspect_err(|e| info!("json parse error: {}\n {}", e, text)) else { continue; };
+        let payload = &json["payload"];
+
+        match json["metadata"]["message_type"].as_str() {
+          Some("session_welcome") => {
+            session_id = payload["session"]["id"].as_str().map(|s| s.to_owned());
+            if let Some(seconds) = payload["session"]["keepalive_timeout_seconds"].as_u64() {
+              keepalive = Duration::from_secs(seconds + KEEPALIVE_GRACE_SEC);
+            }
+            // a reconnected session keeps its subscriptions
+            if state.subscription_ids.is_empty() && let Some(session_id) = session_id.as_ref() {
+              for (channel, room_id) in state.channels.iter() {
+                let ids = subscribe(config, client, session_id, state.user_id.as_ref(), channel, room_id, updates).await;
+                state.subscription_ids.insert(channel.to_owned(), ids);
+              }
+            }
+          },
+          Some("session_keepalive") => {},
+          Some("session_reconnect") => {
+            return Ok(payload["session"]["reconnect_url"].as_str().map(|s| s.to_owned()).or_else(|| Some(config.ws_url.to_owned())));
+          },
+          Some("notification") => {
+            let event = &payload["event"];
+            let room_id = event["broadcaster_user_id"].as_str().or(event["to_broadcaster_user_id"].as_str()).unwrap_or_default();
+            let Some((channel, _)) = state.channels.iter().find(|(_, id)| *id == room_id) else { continue; };
+            let Some(notification) = parse_notification(payload["subscription"]["type"].as_str().unwrap_or_default(), event) else { continue; };
+            let result = match notification {
+              EventSubNotification::Online { started_at } => {
+                _ = updates.try_send(StatusUpdate::Live { channel: channel.to_owned(), is_live: true });
+                tx.try_send(IncomingMessage::StreamingStatus { channel: channel.to_owned(), status: Some(ChannelStatus { is_live: true, started_at, ..Default::default() }) })
+              },
+              EventSubNotification::Offline => {
+                _ = updates.try_send(StatusUpdate::Live { channel: channel.to_owned(), is_live: false });
+                tx.try_send(IncomingMessage::StreamingStatus { channel: channel.to_owned(), status: Some(ChannelStatus::default()) })
+              },
+              EventSubNotification::Event(event) => tx.try_send(IncomingMessage::ChannelEvent { provider: ProviderName::Twitch, channel: channel.to_owned(), event })
+            };
+            if let Err(e) = result {
+              info!("Send failure: {}", e);
+            }
+            ctx.request_repaint();
+          },
+          Some("revocation") => {
+            warn!("EventSub subscription revoked: {}", payload["subscription"]);
+          },
+          _ => debug!("Unknown EventSub message: {}", text)
+        }
+      },
+      command = commands.recv() => {
+        let Ok(command) = command else { return Ok(None); };
+        match command {
+          EventSubCommand::SetUserId { user_id } => state.user_id = Some(user_id),
+          EventSubCommand::Subscribe { channel, room_id } => {
+            if state.channels.get(&channel) != Some(&room_id) {
+              state.channels.insert(channel.to_owned(), room_id.to_owned());
+              if let Some(session_id) = session_id.as_ref() {
+                let ids = subscribe(config, client, session_id, state.user_id.as_ref(), &channel, &room_id, updates).await;
+                state.subscription_ids.insert(channel, ids);
+              }
+            }
+          },
+          EventSubCommand::Unsubscribe { channel } => {
+            state.channels.remove(&channel);
+            for id in state.subscription_ids.remove(&channel).unwrap_or_default() {
+              if let Err(e) = client.delete(&config.subscription_url)
+                .query(&[("id", id)])
+                .bearer_auth(&config.token)
+                .header("Client-Id", &config.client_id)
+                .send().await {
+                warn!("Failed to delete EventSub subscription: {}", e);
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Returns the ids of the subscriptions that were created, and reports whether the channel's live status is covered
+async fn subscribe(config: &EventSubConfig, client: &reqwest::Client, session_id: &str, user_id: Option<&String>, channel: &str, room_id: &str, updates: &Sender<StatusUpdate>) -> Vec<String> {
+  let mut ids = Vec::new();
+  let mut live_events = 0;
+  let is_broadcaster = user_id.is_some_and(|id| id == room_id);
+  let types = SUBSCRIPTION_TYPES.iter().chain(BROADCASTER_SUBSCRIPTION_TYPES.iter().filter(|_| is_broadcaster));
+  for (sub_type, version) in types {
+    let condition = match *sub_type {
+      "channel.raid" => serde_json::json!({ "to_broadcaster_user_id": room_id }),
+      "channel.follow" => serde_json::json!({ "broadcaster_user_id": room_id, "moderator_user_id": room_id }),
+      _ => serde_json::json!({ "broadcaster_user_id": room_id })
+    };
+    let body = serde_json::json!({
+      "type": sub_type,
+      "version": version,
+      "condition": condition,
+      "transport": { "method": "websocket", "session_id": session_id }
+    });
+    let resp = client.post(&config.subscription_url)
+      .bearer_auth(&config.token)
+      .header("Client-Id", &config.client_id)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body.to_string())
+      .send().await;
+    match resp {
+      Ok(resp) if resp.status().is_success() => {
+        match resp.text().await.map(|text| serde_json::from_str::<Value>(&text)) {
+          Ok(Ok(json)) => {
+            ids.extend(json["data"][0]["id"].as_str().map(|id| id.to_owned()));
+            if SUBSCRIPTION_TYPES.iter().any(|(t, _)| t == sub_type) {
+              live_events += 1;
+            }
+          },
+          Ok(Err(e)) => warn!("Failed to parse EventSub subscription response: {}", e),
+          Err(e) => warn!("Failed to read EventSub subscription response: {}", e)
+        }
+      },
+      Ok(resp) => warn!("EventSub subscription {} for {} not created: {}", sub_type, room_id, resp.status()),
+      Err(e) => error!("Failed to create EventSub subscription {}: {}", sub_type, e)
+    }
+  }
+  if let Err(e) = updates.try_send(StatusUpdate::Subscribed { channel: channel.to_owned(), live_events: live_events == SUBSCRIPTION_TYPES.len() }) {
+    info!("Send failure: {}", e);
+  }
+  ids
+}
+
+pub fn parse_notification(subscription_type: &str, event: &Value) -> Option<EventSubNotification> {
+  let str = |value: &Value| value.as_str().unwrap_or_default().to_owned();
+  let count = |value: &Value| value.as_u64().unwrap_or_default() as usize;
+  let titles = |value: &Value| value.as_array().into_iter().flatten().map(|x| str(&x["title"])).collect_vec();
+
+  let event = match subscription_type {
+    "stream.online" => return Some(EventSubNotification::Online { started_at: event["started_at"].as_str().map(|s| s.to_owned()) }),
+    "stream.offline" => return Some(EventSubNotification::Offline),
+    "channel.raid" => ChannelEvent::Raid { from: str(&event["from_broadcaster_user_name"]), viewers: count(&event["viewers"]) },
+    "channel.follow" => ChannelEvent::Follow { user: str(&event["user_name"]) },
+    "channel.channel_points_custom_reward_redemption.add" => ChannelEvent::Redemption {
+      user: str(&event["user_name"]),
+      reward: str(&event["reward"]["title"]),
+      cost: count(&event["reward"]["cost"]),
+      input: event["user_input"].as_str().filter(|x| !x.is_empty()).map(|x| x.to_owned())
+    },
+    "channel.poll.begin" => ChannelEvent::PollBegin { title: str(&event["title"]), choices: titles(&event["choices"]) },
+    "channel.poll.end" => ChannelEvent::PollEnd {
+      title: str(&event["title"]),
+      results: event["choices"].as_array().into_iter().flatten().map(|x| (str(&x["title"]), count(&x["votes"]))).collect_vec()
+    },
+    "channel.prediction.begin" => ChannelEvent::PredictionBegin { title: str(&event["title"]), outcomes: titles(&event["outcomes"]) },
+    "channel.prediction.lock" => ChannelEvent::PredictionLock { title: str(&event["title"]) },
+    "channel.prediction.end" => ChannelEvent::PredictionEnd {
+      title: str(&event["title"]),
+      winner: event["outcomes"].as_array().into_iter().flatten()
+        .find(|x| x["id"].as_str().is_some() && x["id"] == event["winning_outcome_id"])
+        .map(|x| str(&x["title"]))
+    },
+    "channel.hype_train.begin" => ChannelEvent::HypeTrainBegin { level: count(&event["level"]).max(1) },
+    "channel.hype_train.end" => ChannelEvent::HypeTrainEnd { level: count(&event["level"]) },
+    _ => {
+      debug!("Unhandled EventSub notification: {}", subscription_type);
+      return None;
+    }
+  };
+  Some(EventSubNotification::Event(event))
+}
@@ -0,0 +1,50 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::ModerationAction;
+use super::helix::{get_helix_user_id, send_helix_request};
+
+/// Credentials and ids for moderating one channel. The logged in user has to be a moderator or the broadcaster.
+pub struct HelixModeration {
+  pub base_url: String,
+  pub token: String,
+  pub client_id: String,
+  pub broadcaster_id: String,
+  pub moderator_id: String
+}
+
+impl HelixModeration {
+  /// Runs the action against the Helix moderation endpoints, returning Twitch's error message on failure
+  pub async fn send(&self, client: &reqwest::Client, action: &ModerationAction) -> Result<(), anyhow::Error> {
+    let (base_url, broadcaster_id, moderator_id) = (&self.base_url, &self.broadcaster_id, &self.moderator_id);
+    let request = match action {
+      ModerationAction::Timeout { username, duration_sec } => {
+        let user_id = self.get_user_id(client, username).await?;
+        client.post(format!("{base_url}/moderation/bans?broadcaster_id={broadcaster_id}&moderator_id={moderator_id}"))
+          .header(reqwest::header::CONTENT_TYPE, "application/json")
+          .body(serde_json::json!({ "data": { "user_id": user_id, "duration": duration_sec } }).to_string())
+      },
+      ModerationAction::Ban { username } => {
+        let user_id = self.get_user_id(client, username).await?;
+        client.post(format!("{base_url}/moderation/bans?broadcaster_id={broadcaster_id}&moderator_id={moderator_id}"))
+          .header(reqwest::header::CONTENT_TYPE, "application/json")
+          .body(serde_json::json!({ "data": { "user_id": user_id } }).to_string())
+      },
+      ModerationAction::Unban { username } => {
+        let user_id = self.get_user_id(client, username).await?;
+        client.delete(format!("{base_url}/moderation/bans?broadcaster_id={broadcaster_id}&moderator_id={moderator_id}&user_id={user_id}"))
+      },
+      ModerationAction::DeleteMessage { id, username: _ } => {
+        client.delete(format!("{base_url}/moderation/chat?broadcaster_id={broadcaster_id}&moderator_id={moderator_id}&message_id={id}"))
+      }
+    };
+    send_helix_request(request, &self.token, &self.client_id).await.map(|_| ())
+  }
+
+  async fn get_user_id(&self, client: &reqwest::Client, username: &str) -> Result<String, anyhow::Error> {
+    get_helix_user_id(client, &self.base_url, &self.token, &self.client_id, username).await
+  }
+}
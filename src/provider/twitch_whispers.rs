@@ -0,0 +1,27 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use super::helix::{get_helix_user_id, send_helix_request};
+
+/// Sends whispers through Helix, as Twitch no longer delivers whispers sent over IRC.
+/// The logged in user needs a verified phone number and the user:manage:whispers scope.
+pub struct HelixWhisper {
+  pub base_url: String,
+  pub token: String,
+  pub client_id: String,
+  pub from_user_id: String
+}
+
+impl HelixWhisper {
+  /// Returns Twitch's error message on failure, e.g. when the recipient blocks whispers from strangers
+  pub async fn send(&self, client: &reqwest::Client, to_username: &str, message: &str) -> Result<(), anyhow::Error> {
+    let to_user_id = get_helix_user_id(client, &self.base_url, &self.token, &self.client_id, to_username).await?;
+    let request = client.post(format!("{}/whispers?from_user_id={}&to_user_id={to_user_id}", self.base_url, self.from_user_id))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(serde_json::json!({ "message": message }).to_string());
+    send_helix_request(request, &self.token, &self.client_id).await.map(|_| ())
+  }
+}
@@ -4,145 +4,575 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::{time::Duration};
-use egui::Context;
-use chrono::{Utc, TimeZone};
-use curl::easy::{Easy};
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+use async_channel::{Receiver, Sender, TrySendError};
+use chrono::{DateTime, Utc};
+use egui::{Color32, Context};
 use itertools::Itertools;
+use regex::Regex;
 use serde_json::Value;
-use tokio::{runtime::Runtime, sync::mpsc};
-
-use super::make_request;
-use super::{Channel, OutgoingMessage, InternalMessage, ProviderName, ChatMessage, UserProfile, ChannelTransient};
-
-pub fn init_channel<'a>(name: String, channel_id: String, token: String, runtime: &Runtime, ctx: &Context) -> Channel {
-  let (out_tx, out_rx) = mpsc::channel::<InternalMessage>(32);
-  let (in_tx, mut in_rx) = mpsc::channel::<OutgoingMessage>(32);
-  let name_copy = name.to_owned();
-  let channel_id_copy = channel_id.to_owned();
-  let ctx = ctx.clone();
-
-  let task = runtime.spawn(async move { 
-    // periodically poll youtube API for new chat messages
-    let mut easy = reqwest::Client::new();
-    let mut activelivechat_id : Option<String> = None;
-    let mut next_page_token : Option<String> = None;
-
-    loop {
-      while let Ok(_) = in_rx.try_recv() { }
-
-      // If channel is streaming get chat messages until no messages found and wait 15 seconds before trying again
-      if let Some(activelivechat_id) = &activelivechat_id {
-        let (page_token, msgs) = get_chat_messages(
-          &token, 
-          activelivechat_id, 
-          &name_copy, 
-          match next_page_token { Some(x) => { info!("using page token {}", x); x}, None => "".to_owned() }, 
-          &mut easy);
-        info!("updating token to {:?}", page_token);
-        next_page_token = page_token;
-        if let Some(messages) = msgs && messages.len() > 0 {
-          info!("got {} messages", &messages.len());
-          for message in messages {
-            if let Err(e) = out_tx.try_send(InternalMessage::PrivMsg { message: message }) {
-              info!("Error sending PrivMsg: {}", e);
-            }
-            ctx.request_repaint();
+use tokio::runtime::{Handle, Runtime};
+use tracing::{debug, info, warn};
+use tracing_unwrap::ResultExt;
+
+use crate::emotes::{Emote, EmoteSource};
+use super::{ChatManager, ChatManagerRx, ChatMessage, ChatProvider, IncomingMessage, MessageType, OutgoingMessage, ProviderInfo, ProviderName, UserProfile, youtube_server};
+use super::channel::{Channel, ChannelShared, ChannelStatus, ChannelTransient, IrcNetwork, YoutubeChannel};
+
+pub const YOUTUBE_BASE_URL : &str = "https://www.youtube.com";
+/// Tab name prefix shared with chats pushed by the Tampermonkey script
+pub const YOUTUBE_CHANNEL_PREFIX : &str = "YT:";
+
+const USER_AGENT : &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+const OFFLINE_RECHECK_INTERVAL : Duration = Duration::from_secs(60);
+const MAX_POLL_ERRORS : usize = 5;
+
+static CANONICAL_VIDEO_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<link rel="canonical" href="[^"]*/watch\?v=([\w-]{11})""#).unwrap_or_log());
+static TITLE_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<meta name="title" content="([^"]*)""#).unwrap_or_log());
+static API_KEY_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""INNERTUBE_API_KEY":"([^"]+)""#).unwrap_or_log());
+static CLIENT_VERSION_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""INNERTUBE_CLIENT_VERSION":"([^"]+)""#).unwrap_or_log());
+static REDIRECT_LINK_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new("http[^\\s]*q=([^\\s]*)").unwrap_or_log());
+static CONTINUATION_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""continuation":"([^"]+)""#).unwrap_or_log());
+static VIDEO_URL_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:[?&]v=|youtu\.be/|/live/)([\w-]{11})").unwrap_or_log());
+static VIDEO_ID_REGEX : LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[\w-]{11}$").unwrap_or_log());
+
+/// `target` is a channel handle (@name), channel id (UC...), video id or video url
+pub fn init_channel(target: &str) -> Channel {
+  let target = target.trim().trim_start_matches(YOUTUBE_CHANNEL_PREFIX).to_owned();
+  Channel::Youtube {
+    shared: ChannelShared {
+      channel_name: format!("{YOUTUBE_CHANNEL_PREFIX}{target}"),
+      show_in_mentions_tab: true,
+      show_tab_when_offline: false,
+      mute_notifications: false,
+      send_history: Default::default(),
+      send_history_ix: None,
+      transient: None,
+      users: Default::default()
+    },
+    youtube: YoutubeChannel {
+      target: Some(target)
+    }
+  }
+}
+
+pub struct YoutubeInfo;
+
+impl ProviderInfo for YoutubeInfo {
+  fn display_name(&self) -> &'static str {
+    "YouTube"
+  }
+
+  fn archive_dir(&self) -> &'static str {
+    "youtube"
+  }
+
+  fn color(&self) -> Color32 {
+    Color32::from_rgba_unmultiplied(255, 78, 69, 255)
+  }
+
+  fn init_channel(&self, channel_name: &str, _network: IrcNetwork) -> Channel {
+    init_channel(channel_name)
+  }
+
+  fn channel_name_hint(&self) -> &'static str {
+    "@handle, channel ID or video URL"
+  }
+
+  fn init_pushed_channel(&self, channel_name: &str) -> Option<Channel> {
+    Some(Channel::Youtube {
+      youtube: YoutubeChannel { target: None },
+      shared: ChannelShared {
+        channel_name: channel_name.to_owned(),
+        transient: Some(ChannelTransient {
+          channel_emotes: None,
+          badge_emotes: None,
+          status: None,
+          chat_modes: Default::default(),
+          is_moderator: false }),
+        ..Default::default()
+      }
+    })
+  }
+
+  /// Strips down redirect links to the link target
+  fn clean_message(&self, message: String) -> String {
+    if !message.contains("https://www.youtube.com/redirect") {
+      return message;
+    }
+    let str = REDIRECT_LINK_REGEX.replace_all(&message, "$1");
+    urlencoding::decode(&str).map(|x| x.into_owned()).unwrap_or_else(|_| str.to_string())
+  }
+
+  /// YouTube emotes are sent as their id and image url
+  fn message_emote(&self, id: String, url: String) -> Option<Emote> {
+    Some(Emote { id: id.to_owned(), name: id, url, path: "youtube/".to_owned(), source: EmoteSource::Provider(ProviderName::YouTube), ..Default::default() })
+  }
+}
+
+/// Both natively polled chats and, if YT Integration is enabled, chats pushed by the Tampermonkey script
+pub struct YoutubeChatManager {
+  handles: HashMap<String, tokio::task::JoinHandle<()>>,
+  bridge: Option<ChatManager>,
+  http: YoutubeHttp,
+  runtime: Handle,
+  ctx: Context,
+  out_tx: Sender<IncomingMessage>,
+  pub out_rx: Receiver<IncomingMessage>,
+  pub in_tx: Sender<OutgoingMessage>
+}
+
+impl YoutubeChatManager {
+  pub fn new(http: YoutubeHttp, enable_bridge: bool, runtime: &Runtime, ctx: &Context) -> Self {
+    let (out_tx, out_rx) = async_channel::bounded::<IncomingMessage>(10000);
+    let (in_tx, _) = async_channel::bounded::<OutgoingMessage>(1);
+
+    let bridge = if enable_bridge {
+      let bridge = youtube_server::start_listening(runtime);
+      let bridge_rx = bridge.out_rx.clone();
+      let out_tx = out_tx.clone();
+      let ctx = ctx.clone();
+      runtime.spawn(async move {
+        while let Ok(msg) = bridge_rx.recv().await {
+          if let Err(e) = out_tx.send(msg).await {
+            warn!("Failure forwarding YT integration message: {}", e);
           }
+          ctx.request_repaint();
+        }
+      });
+      Some(bridge)
+    } else {
+      None
+    };
+
+    Self {
+      handles: Default::default(),
+      bridge,
+      http,
+      runtime: runtime.handle().clone(),
+      ctx: ctx.clone(),
+      out_tx,
+      out_rx,
+      in_tx
+    }
+  }
+}
+
+impl ChatManagerRx for YoutubeChatManager {
+  fn in_tx(&mut self) -> &mut Sender<OutgoingMessage> {
+    &mut self.in_tx
+  }
+  fn out_rx(&mut self) -> &mut Receiver<IncomingMessage> {
+    &mut self.out_rx
+  }
+}
+
+impl ChatProvider for YoutubeChatManager {
+  fn provider_name(&self) -> ProviderName {
+    ProviderName::YouTube
+  }
+
+  fn username(&self) -> &str {
+    ""
+  }
+
+  fn can_send(&self, _channel_name: &str) -> Result<(), String> {
+    match self.bridge.is_some() {
+      true => Ok(()),
+      false => Err("Sending messages requires YT Integration to be enabled.".to_owned())
+    }
+  }
+
+  fn open_channel(&mut self, channel: &mut Channel) {
+    let Channel::Youtube { youtube, shared } = channel else { return; };
+
+    if let Some(target) = youtube.target.as_ref() && self.handles.get(&shared.channel_name).is_none_or(|h| h.is_finished()) {
+      let http = self.http.to_owned();
+      let channel_name = shared.channel_name.to_owned();
+      let target = target.to_owned();
+      let out_tx = self.out_tx.clone();
+      let ctx = self.ctx.clone();
+      let handle = self.runtime.spawn(async move {
+        run_live_chat(&http, &channel_name, &target, &out_tx, &ctx).await;
+      });
+      self.handles.insert(shared.channel_name.to_owned(), handle);
+    }
+
+    if shared.transient.is_none() {
+      shared.transient = Some(Default::default());
+    }
+  }
+
+  fn leave_channel(&mut self, channel_name: &str) {
+    if let Some(handle) = self.handles.remove(channel_name) {
+      handle.abort();
+    }
+  }
+
+  fn send_chat(&mut self, channel_name: &str, message: &str) -> Result<(), TrySendError<OutgoingMessage>> {
+    match self.bridge.as_mut() {
+      Some(bridge) => bridge.send_chat(channel_name, message),
+      None => {
+        super::display_system_message_in_chat(&self.out_tx, channel_name.to_owned(), ProviderName::YouTube, "Sending messages requires YT Integration to be enabled.".to_owned(), MessageType::Error, &self.ctx);
+        Err(TrySendError::Closed(OutgoingMessage::Chat { channel: channel_name.to_owned(), message: message.to_owned() }))
+      }
+    }
+  }
+
+  fn close(&mut self) {
+    for (_, handle) in self.handles.drain() {
+      handle.abort();
+    }
+    if let Some(bridge) = self.bridge.as_mut() {
+      bridge.close();
+    }
+  }
+}
+
+/// Finds the target's live stream and polls its chat, rechecking periodically while offline
+async fn run_live_chat(http: &YoutubeHttp, channel_name: &str, target: &str, tx: &Sender<IncomingMessage>, ctx: &Context) {
+  let mut was_live : Option<bool> = None;
+  loop {
+    match http.resolve_live_video(target).await {
+      Ok(Some(video)) => {
+        send_status(tx, channel_name, ChannelStatus { is_live: true, title: video.title.to_owned(), ..Default::default() }, ctx);
+        was_live = Some(true);
+        match http.get_live_chat_session(&video.video_id).await {
+          Ok(session) => {
+            super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::YouTube, "Connected to chat.".to_owned(), MessageType::Information, ctx);
+            poll_live_chat(http, session, channel_name, tx, ctx).await;
+          },
+          Err(e) => warn!("Failed to open YouTube live chat for {}: {}", target, e)
         }
-        else { 
-          // no new messages
-          tokio::time::sleep(Duration::from_millis(10000)).await;
+      },
+      Ok(None) => {
+        if was_live != Some(false) {
+          send_status(tx, channel_name, ChannelStatus::default(), ctx);
+          was_live = Some(false);
         }
-        tokio::time::sleep(Duration::from_millis(20000)).await;
+      },
+      Err(e) => {
+        warn!("Failed to check YouTube live status for {}: {}", target, e);
       }
-      else {
-        // Check if channel is live streaming
-        // If not wait a minute before checking again
-        if let Some(video_id) = check_channel(&token, &channel_id_copy, &mut easy) {
-          _ = out_tx.try_send(InternalMessage::StreamingStatus { is_live: true });
-          activelivechat_id = get_active_livestreamchat_id(&token, &video_id, &mut easy);
-          if activelivechat_id.is_none() {
-            tokio::time::sleep(Duration::from_millis(60000)).await;  
+    }
+    tokio::time::sleep(OFFLINE_RECHECK_INTERVAL).await;
+  }
+}
+
+/// Polls until the chat ends or keeps failing
+async fn poll_live_chat(http: &YoutubeHttp, mut session: LiveChatSession, channel_name: &str, tx: &Sender<IncomingMessage>, ctx: &Context) {
+  let mut errors = 0;
+  loop {
+    match http.get_live_chat_page(&session, channel_name).await {
+      Ok(page) => {
+        errors = 0;
+        if !page.emotes.is_empty() && let Err(e) = tx.try_send(IncomingMessage::MsgEmotes { provider: ProviderName::YouTube, emote_ids: page.emotes }) {
+          info!("Send failure for YT emotes: {}", e);
+        }
+        for message in page.messages {
+          if let Err(e) = tx.try_send(IncomingMessage::PrivMsg { message }) {
+            info!("Send failure for YT MSG: {}", e);
+          }
+        }
+        for id in page.deleted {
+          if let Err(e) = tx.try_send(IncomingMessage::MessageDeleted { channel: channel_name.to_owned(), id }) {
+            info!("Send failure for YT message deletion: {}", e);
           }
         }
-        else {
-          activelivechat_id = None;
-          _ = out_tx.try_send(InternalMessage::StreamingStatus { is_live: false });
-          tokio::time::sleep(Duration::from_millis(60000)).await;
-          continue;
+        ctx.request_repaint();
+        match page.continuation {
+          Some(continuation) => session.continuation = continuation,
+          None => {
+            super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::YouTube, "Live chat ended.".to_owned(), MessageType::Information, ctx);
+            return;
+          }
         }
+        tokio::time::sleep(Duration::from_millis(page.timeout_ms.clamp(1000, 10000))).await;
+      },
+      Err(e) => {
+        errors += 1;
+        warn!("Error polling YouTube live chat for {}: {}", channel_name, e);
+        if errors >= MAX_POLL_ERRORS {
+          super::display_system_message_in_chat(tx, channel_name.to_owned(), ProviderName::YouTube, "Lost connection to live chat.".to_owned(), MessageType::Error, ctx);
+          return;
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
       }
     }
-  });
-
-  let channel = Channel {  
-    provider: ProviderName::YouTube,  
-    channel_name: name.to_owned(),
-    roomid: channel_id.to_owned(),
-    send_history: Default::default(),
-    send_history_ix: None,
-    transient: Some(ChannelTransient {
-      channel_emotes: None,
-      badge_emotes: None,
-      is_live: false
-    })
-  };
-  channel
+  }
 }
 
-fn check_channel(token: &String, channel_id: &String, easy : &mut Easy) -> Option<String> {
-  let url = format!("https://youtube.googleapis.com/youtube/v3/search?part=id&channelId={}&eventType=live&maxResults=5&type=video", channel_id);
-  let headers = Some([("Authorization", format!("Bearer {}", token)), ("Accept", "application/json".to_owned())].to_vec());
-  match make_request(&url, headers, easy) {
-    Ok(data) => {
-      match serde_json::from_str::<Value>(&data){
-        Ok(v) => v["items"][0]["id"]["videoId"].as_str().and_then(|x| Some(x.to_owned())),
-        Err(e) => { info!("JSON Error: {}", e); None }
-      }
+fn send_status(tx: &Sender<IncomingMessage>, channel_name: &str, status: ChannelStatus, ctx: &Context) {
+  if let Err(e) = tx.try_send(IncomingMessage::StreamingStatus { channel: channel_name.to_owned(), status: Some(status) }) {
+    warn!("error sending YT stream status: {}", e);
+  }
+  ctx.request_repaint();
+}
+
+pub struct LiveVideo {
+  pub video_id: String,
+  pub title: Option<String>
+}
+
+/// State needed to request the next batch of chat messages
+pub struct LiveChatSession {
+  pub api_key: String,
+  pub client_version: String,
+  pub continuation: String
+}
+
+pub struct LiveChatPage {
+  pub messages: Vec<ChatMessage>,
+  /// (name, image url) of custom emojis used in the messages
+  pub emotes: Vec<(String, String)>,
+  /// Ids of messages removed by moderators
+  pub deleted: Vec<String>,
+  /// None once the chat has ended
+  pub continuation: Option<String>,
+  pub timeout_ms: u64
+}
+
+/// The youtube.com endpoints used to find and poll live chats. `base_url` can point at a fixture server for tests.
+#[derive(Clone)]
+pub struct YoutubeHttp {
+  pub base_url: String,
+  client: reqwest::Client
+}
+
+impl Default for YoutubeHttp {
+  fn default() -> Self {
+    Self::new(YOUTUBE_BASE_URL)
+  }
+}
+
+impl YoutubeHttp {
+  pub fn new(base_url: &str) -> Self {
+    let client = reqwest::Client::builder()
+      .user_agent(USER_AGENT)
+      .timeout(Duration::from_secs(30))
+      .build()
+      .unwrap_or_default();
+    Self {
+      base_url: base_url.trim_end_matches('/').to_owned(),
+      client
     }
-    Err(e) => { info!("Error: {}", e); None }
   }
+
+  async fn get(&self, path: &str) -> Result<String, anyhow::Error> {
+    let resp = self.client.get(format!("{}{path}", self.base_url))
+      // skip the EU cookie consent page
+      .header(reqwest::header::COOKIE, "SOCS=CAI")
+      .send().await?
+      .error_for_status()?;
+    Ok(resp.text().await?)
+  }
+
+  async fn post_json(&self, path: &str, body: &Value) -> Result<String, anyhow::Error> {
+    let resp = self.client.post(format!("{}{path}", self.base_url))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body.to_string())
+      .send().await?
+      .error_for_status()?;
+    Ok(resp.text().await?)
+  }
+
+  /// None if the target is not currently live
+  pub async fn resolve_live_video(&self, target: &str) -> Result<Option<LiveVideo>, anyhow::Error> {
+    let (path, video_id) = match parse_target(target) {
+      // a video can be a stream that hasn't started or has already ended, so its watch page is checked too
+      YoutubeTarget::Video(video_id) => (format!("/watch?v={video_id}"), Some(video_id)),
+      YoutubeTarget::Channel(channel_id) => (format!("/channel/{channel_id}/live"), None),
+      YoutubeTarget::Handle(handle) => (format!("/@{handle}/live"), None)
+    };
+    let html = self.get(&path).await?;
+    let video_id = video_id.or_else(|| CANONICAL_VIDEO_REGEX.captures(&html).and_then(|c| c.get(1)).map(|id| id.as_str().to_owned()));
+    match video_id {
+      Some(video_id) if html.contains("\"isLiveNow\":true") => {
+        let title = TITLE_REGEX.captures(&html)
+          .and_then(|c| c.get(1))
+          .map(|t| unescape_html(t.as_str()));
+        Ok(Some(LiveVideo { video_id, title }))
+      },
+      _ => Ok(None)
+    }
+  }
+
+  pub async fn get_live_chat_session(&self, video_id: &str) -> Result<LiveChatSession, anyhow::Error> {
+    let html = self.get(&format!("/live_chat?is_popout=1&v={video_id}")).await?;
+    let capture = |regex: &Regex| regex.captures(&html).and_then(|c| c.get(1)).map(|m| m.as_str().to_owned());
+    Ok(LiveChatSession {
+      api_key: capture(&API_KEY_REGEX).ok_or_else(|| anyhow::anyhow!("missing api key"))?,
+      client_version: capture(&CLIENT_VERSION_REGEX).ok_or_else(|| anyhow::anyhow!("missing client version"))?,
+      continuation: capture(&CONTINUATION_REGEX).ok_or_else(|| anyhow::anyhow!("live chat is not available"))?
+    })
+  }
+
+  pub async fn get_live_chat_page(&self, session: &LiveChatSession, channel_name: &str) -> Result<LiveChatPage, anyhow::Error> {
+    let body = serde_json::json!({
+      "context": { "client": { "clientName": "WEB", "clientVersion": session.client_version } },
+      "continuation": session.continuation
+    });
+    let json = self.post_json(&format!("/youtubei/v1/live_chat/get_live_chat?key={}&prettyPrint=false", session.api_key), &body).await?;
+    Ok(parse_live_chat_page(&serde_json::from_str::<Value>(&json)?, channel_name))
+  }
+}
+
+enum YoutubeTarget {
+  Video(String),
+  Channel(String),
+  Handle(String)
 }
 
-fn get_active_livestreamchat_id(token: &String, video_id: &String, easy : &mut Easy) -> Option<String> {
-  let url = format!("https://youtube.googleapis.com/youtube/v3/videos?part=liveStreamingDetails&id={}", video_id);
-  let headers = Some([("Authorization", format!("Bearer {}", token)), ("Accept", "application/json".to_owned())].to_vec());
-  match make_request(&url, headers, easy) {
-    Ok(data) => {
-      match serde_json::from_str::<Value>(&data){
-        Ok(v) => v["items"][0]["liveStreamingDetails"]["activeLiveChatId"].as_str().and_then(|x| Some(x.to_owned())),
-        Err(e) => { info!("JSON Error: {}", e); None }
+fn parse_target(target: &str) -> YoutubeTarget {
+  let target = target.trim();
+  if let Some(id) = VIDEO_URL_REGEX.captures(target).and_then(|c| c.get(1)) {
+    YoutubeTarget::Video(id.as_str().to_owned())
+  }
+  else if let Some(handle) = target.split('@').nth(1).and_then(|h| h.split('/').next()) {
+    YoutubeTarget::Handle(handle.to_owned())
+  }
+  else if let Some(channel_id) = target.split("/channel/").last().and_then(|id| id.split('/').next()).filter(|id| id.starts_with("UC") && id.len() == 24) {
+    YoutubeTarget::Channel(channel_id.to_owned())
+  }
+  else if VIDEO_ID_REGEX.is_match(target) {
+    YoutubeTarget::Video(target.to_owned())
+  }
+  else {
+    YoutubeTarget::Handle(target.to_owned())
+  }
+}
+
+/// Maps a get_live_chat response into chat messages
+pub fn parse_live_chat_page(json: &Value, channel_name: &str) -> LiveChatPage {
+  let chat = &json["continuationContents"]["liveChatContinuation"];
+  let continuation_data = chat["continuations"].as_array()
+    .and_then(|c| c.first())
+    .and_then(|c| c.as_object())
+    .and_then(|c| c.values().next());
+  let mut page = LiveChatPage {
+    messages: Default::default(),
+    emotes: Default::default(),
+    deleted: Default::default(),
+    continuation: continuation_data.and_then(|c| c["continuation"].as_str()).map(|c| c.to_owned()),
+    timeout_ms: continuation_data.and_then(|c| c["timeoutMs"].as_u64()).unwrap_or(5000)
+  };
+
+  for action in chat["actions"].as_array().into_iter().flatten() {
+    if let Some(id) = action["markChatItemAsDeletedAction"]["targetItemId"].as_str() {
+      page.deleted.push(id.to_owned());
+      continue;
+    }
+    let item = &action["addChatItemAction"]["item"];
+    let Some((kind, renderer)) = item.as_object().and_then(|i| i.iter().next()) else { continue; };
+    let (text, mut emotes) = parse_runs(&renderer["message"]);
+    page.emotes.append(&mut emotes);
+
+    let author = renderer["authorName"]["simpleText"].as_str().unwrap_or_default().to_owned();
+    // display names are not unique, the author's channel id is
+    let username = match renderer["authorExternalChannelId"].as_str() {
+      Some(channel_id) => channel_id.to_owned(),
+      None => author.replace(' ', "_").trim_start_matches('@').to_lowercase()
+    };
+    let badges = author_badges(renderer);
+    let (msg_type, message) = match kind.as_str() {
+      "liveChatTextMessageRenderer" => (MessageType::Chat, text),
+      "liveChatPaidMessageRenderer" => {
+        let amount = renderer["purchaseAmountText"]["simpleText"].as_str().unwrap_or_default();
+        (MessageType::Announcement, format!("{amount} Super Chat from {author}: {text}").trim_end_matches([':', ' ']).to_owned())
+      },
+      "liveChatPaidStickerRenderer" => {
+        let amount = renderer["purchaseAmountText"]["simpleText"].as_str().unwrap_or_default();
+        (MessageType::Announcement, format!("{amount} Super Sticker from {author}"))
+      },
+      "liveChatMembershipItemRenderer" => {
+        let (header, _) = parse_runs(&renderer["headerSubtext"]);
+        let header = renderer["headerSubtext"]["simpleText"].as_str().map(|x| x.to_owned()).unwrap_or(header);
+        (MessageType::Announcement, format!("{author}: {header} {text}").trim().to_owned())
+      },
+      _ => {
+        debug!("unhandled YT chat item: {}", kind);
+        continue;
       }
+    };
+
+    page.messages.push(ChatMessage {
+      provider: ProviderName::YouTube,
+      channel: channel_name.to_owned(),
+      username,
+      timestamp: renderer["timestampUsec"].as_str()
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(DateTime::from_timestamp_micros)
+        .unwrap_or_else(Utc::now),
+      message,
+      profile: UserProfile {
+        color: Some(role_color(&badges)),
+        badges: if !badges.is_empty() { Some(badges) } else { None },
+        display_name: Some(author)
+      },
+      msg_type,
+      id: renderer["id"].as_str().map(|id| id.to_owned()).unwrap_or_else(super::new_message_id),
+      ..Default::default()
+    });
+  }
+  page
+}
+
+/// Message text with custom emojis replaced by their shortcut name, and the (name, image url) of those emojis
+fn parse_runs(message: &Value) -> (String, Vec<(String, String)>) {
+  let mut text = String::new();
+  let mut emotes : Vec<(String, String)> = Default::default();
+  for run in message["runs"].as_array().into_iter().flatten() {
+    if let Some(str) = run["text"].as_str() {
+      text.push_str(str);
     }
-    Err(e) => { info!("Error: {}", e); None }
-  }
-}
-
-fn get_chat_messages(token: &String, livestreamchat_id: &String, channel_name: &String, next_page_token: String, easy : &mut Easy) -> (Option<String>, Option<Vec<ChatMessage>>) {
-  let url = format!("https://youtube.googleapis.com/youtube/v3/liveChat/messages?liveChatId={}&part=snippet,authorDetails&maxResults=100&pageToken={}", livestreamchat_id, next_page_token);
-  let headers = Some([("Authorization", format!("Bearer {}", token)), ("Accept", "application/json".to_owned())].to_vec());
-  match make_request(&url, headers, easy) {
-    Ok(data) => {
-      info!("{}", data);
-      match serde_json::from_str::<Value>(&data){
-        Ok(v) => {
-          let token = v["nextPageToken"].as_str().and_then(|x| Some(x.to_owned()));
-          (token, Some(v["items"].as_array().unwrap_or_log().into_iter().filter_map(|item| { Some(ChatMessage { 
-            provider: ProviderName::YouTube, 
-            channel: channel_name.to_owned(), 
-            username: item["authorDetails"]["displayName"].as_str()
-              .and_then(|x| Some(x.to_owned()))
-              .or_else(|| Some("unknown1".to_owned())).unwrap_or_log(), 
-            timestamp: item["snippet"]["publishedAt"].as_str().and_then(|x| Some(Utc.datetime_from_str(x, "%+").unwrap_or_log())).or_else(|| Some(Utc::now())).unwrap_or_log().into(),
-            message: item["snippet"]["displayMessage"].as_str()
-              .and_then(|x| Some(x.to_owned()))
-              .or_else(|| Some("".to_owned())).unwrap_or_log(), 
-            ..Default::default() }) }).collect_vec()))
-        },
-        Err(e) => { info!("JSON Error: {}", e); (None, None) }
+    else if run["emoji"].is_object() {
+      let emoji = &run["emoji"];
+      if emoji["isCustomEmoji"].as_bool().unwrap_or(false)
+        && let Some(name) = emoji["shortcuts"][0].as_str()
+        && let Some(url) = emoji["image"]["thumbnails"].as_array().and_then(|t| t.last()).and_then(|t| t["url"].as_str()) {
+        text.push_str(&format!(" {name} "));
+        emotes.push((name.to_owned(), url.to_owned()));
       }
+      else if let Some(id) = emoji["emojiId"].as_str() {
+        text.push_str(id);
+      }
+    }
+  }
+  (text.split_whitespace().join(" "), emotes.into_iter().unique().collect_vec())
+}
+
+fn author_badges(renderer: &Value) -> Vec<String> {
+  renderer["authorBadges"].as_array().into_iter().flatten().filter_map(|badge| {
+    let badge = &badge["liveChatAuthorBadgeRenderer"];
+    match badge["icon"]["iconType"].as_str() {
+      Some("OWNER") => Some("owner".to_owned()),
+      Some("MODERATOR") => Some("moderator".to_owned()),
+      Some("VERIFIED") => Some("verified".to_owned()),
+      Some(_) => None,
+      None if badge["customThumbnail"].is_object() => Some("member".to_owned()),
+      None => None
     }
-    Err(e) => { info!("Error: {}", e); (None, None) }
+  }).collect_vec()
+}
+
+/// Same colors the Tampermonkey integration uses
+fn role_color(badges: &[String]) -> (u8, u8, u8) {
+  if badges.iter().any(|b| b == "owner") {
+    (255, 214, 0)
+  } else if badges.iter().any(|b| b == "moderator") {
+    (94, 132, 241)
+  } else if badges.iter().any(|b| b == "member") {
+    (43, 166, 64)
+  } else {
+    (186, 186, 186)
   }
-}
\ No newline at end of file
+}
+
+fn unescape_html(str: &str) -> String {
+  str
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&gt;", ">")
+    .replace("&lt;", "<")
+    .replace("&amp;", "&")
+}
@@ -88,7 +88,11 @@ pub fn start_listening(runtime: &Runtime) -> ChatManager {
           msg_type: match request.role.as_deref() {
             Some("error") => super::MessageType::Error,
             _ => super::MessageType::Chat 
-          }
+          },
+          from_archive: false,
+          id: super::new_message_id(),
+          reply_to: None,
+          filtered: None
         }
       }).await {
         Ok(_) => (),
@@ -121,6 +125,7 @@ pub fn start_listening(runtime: &Runtime) -> ChatManager {
 
   ChatManager { 
     handles: vec![handle], 
+    provider: ProviderName::YouTube,
     username: "".to_owned(), 
     in_tx, 
     out_rx 
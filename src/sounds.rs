@@ -0,0 +1,171 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// File types that can be chosen as alert sounds
+pub const SOUND_FILE_EXTENSIONS : [&str; 2] = ["wav", "ogg"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertEvent {
+  Mention,
+  Highlight,
+  Live
+}
+
+/// Plays a sound file at a volume from 0 to 1
+pub trait SoundSink {
+  fn play(&mut self, path: &Path, volume: f32) -> anyhow::Result<()>;
+}
+
+/// Discards all sounds, used when there is no audio output
+#[derive(Default)]
+pub struct NullSink;
+
+impl SoundSink for NullSink {
+  fn play(&mut self, _path: &Path, _volume: f32) -> anyhow::Result<()> {
+    Ok(())
+  }
+}
+
+/// Plays sounds on the default audio device. Decoding and playback run on a separate thread,
+/// which is started and opens the device on first use.
+#[cfg(feature = "sound")]
+#[derive(Default)]
+pub struct RodioSink {
+  tx: Option<std::sync::mpsc::Sender<(PathBuf, f32)>>
+}
+
+#[cfg(feature = "sound")]
+impl SoundSink for RodioSink {
+  fn play(&mut self, path: &Path, volume: f32) -> anyhow::Result<()> {
+    let tx = match self.tx.as_ref() {
+      Some(tx) => tx,
+      None => &*self.tx.insert(spawn_audio_thread()?)
+    };
+    if tx.send((path.to_owned(), volume)).is_err() {
+      // started again on the next sound
+      self.tx = None;
+      anyhow::bail!("audio thread stopped");
+    }
+    Ok(())
+  }
+}
+
+#[cfg(feature = "sound")]
+fn spawn_audio_thread() -> std::io::Result<std::sync::mpsc::Sender<(PathBuf, f32)>> {
+  let (tx, rx) = std::sync::mpsc::channel::<(PathBuf, f32)>();
+  std::thread::Builder::new().name("audio".to_owned()).spawn(move || {
+    // the output stream is not Send, so it lives on this thread
+    let mut stream : Option<(rodio::OutputStream, rodio::OutputStreamHandle)> = None;
+    for (path, volume) in rx {
+      let result = (|| -> anyhow::Result<()> {
+        let (_, handle) = match stream.as_ref() {
+          Some(stream) => stream,
+          None => &*stream.insert(rodio::OutputStream::try_default()?)
+        };
+        let sink = rodio::Sink::try_new(handle)?;
+        sink.set_volume(volume);
+        sink.append(rodio::Decoder::new(std::io::BufReader::new(std::fs::File::open(&path)?))?);
+        // keeps playing after the sink is dropped
+        sink.detach();
+        Ok(())
+      })();
+      if let Err(e) = result {
+        warn!("Failed to play sound {}: {e}", path.display());
+      }
+    }
+  })?;
+  Ok(tx)
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))]
+pub struct SoundSettings {
+  pub enabled: bool,
+  pub volume: f32,
+  pub mention_sound: Option<PathBuf>,
+  pub highlight_sound: Option<PathBuf>,
+  pub live_sound: Option<PathBuf>
+}
+
+impl Default for SoundSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      volume: 0.5,
+      mention_sound: None,
+      highlight_sound: None,
+      live_sound: None
+    }
+  }
+}
+
+impl SoundSettings {
+  pub fn sound_for(&self, event: AlertEvent) -> Option<&PathBuf> {
+    match event {
+      AlertEvent::Mention => self.mention_sound.as_ref(),
+      AlertEvent::Highlight => self.highlight_sound.as_ref(),
+      AlertEvent::Live => self.live_sound.as_ref()
+    }
+  }
+}
+
+/// Reason the file can't be used as an alert sound
+pub fn sound_file_error(path: &Path) -> Option<&'static str> {
+  if !path.extension().and_then(|e| e.to_str()).is_some_and(|e| SOUND_FILE_EXTENSIONS.contains(&e.to_lowercase().as_str())) {
+    Some("Only .wav and .ogg files are supported")
+  }
+  else if !path.is_file() {
+    Some("File not found")
+  }
+  else {
+    None
+  }
+}
+
+pub struct SoundPlayer {
+  sink: Box<dyn SoundSink>
+}
+
+impl Default for SoundPlayer {
+  fn default() -> Self {
+    #[cfg(feature = "sound")]
+    let sink : Box<dyn SoundSink> = Box::new(RodioSink::default());
+    #[cfg(not(feature = "sound"))]
+    let sink : Box<dyn SoundSink> = Box::new(NullSink);
+    Self::new(sink)
+  }
+}
+
+impl SoundPlayer {
+  pub fn new(sink: Box<dyn SoundSink>) -> Self {
+    Self { sink }
+  }
+
+  /// Returns false if sounds are disabled, no sound is set for the event, or playback failed
+  pub fn play_alert(&mut self, settings: &SoundSettings, event: AlertEvent) -> bool {
+    if !settings.enabled {
+      return false;
+    }
+    match settings.sound_for(event) {
+      Some(path) => self.play(path, settings.volume),
+      None => false
+    }
+  }
+
+  pub fn play(&mut self, path: &Path, volume: f32) -> bool {
+    match self.sink.play(path, volume.clamp(0., 1.)) {
+      Ok(()) => true,
+      Err(e) => {
+        warn!("Failed to play sound {}: {e}", path.display());
+        false
+      }
+    }
+  }
+}
@@ -116,6 +116,998 @@ mod test {
     assert_eq!(frames.unwrap().len(), 35);
   }
 
+  const KICK_CHANNEL_JSON : &str = r#"{"id":668,"slug":"xqc","chatroom":{"id":668},"livestream":null,
+    "subscriber_badges":[{"id":1,"months":1,"badge_image":{"src":"https://files.kick.com/1"}},{"id":2,"months":3,"badge_image":{"src":"https://files.kick.com/2"}},{"id":3,"months":6,"badge_image":{"src":"https://files.kick.com/3"}}]}"#;
+
+  // recorded from ws-us2.pusher.com
+  const KICK_FRAMES : [&str; 6] = [
+    r#"{"event":"pusher_internal:subscription_succeeded","data":"{}","channel":"chatrooms.668.v2"}"#,
+    r##"{"event":"App\\Events\\ChatMessageEvent","data":"{\"id\":\"9b3ba3a4-3c8c-4d8b-9a0e-0c2b2d1f1a11\",\"chatroom_id\":668,\"content\":\"hello [emote:37226:KEKW][emote:37226:KEKW]\",\"type\":\"message\",\"created_at\":\"2024-05-14T15:03:22+00:00\",\"sender\":{\"id\":1234,\"username\":\"SomeUser\",\"slug\":\"someuser\",\"identity\":{\"color\":\"#E9113C\",\"badges\":[{\"type\":\"moderator\",\"text\":\"Moderator\"},{\"type\":\"subscriber\",\"text\":\"Subscriber\",\"count\":4}]}}}","channel":"chatrooms.668.v2"}"##,
+    r#"{"event":"App\\Events\\StreamerIsLive","data":"{\"livestream\":{\"id\":1,\"channel_id\":668,\"session_title\":\"JUST CHATTING\",\"source\":null,\"created_at\":\"2024-05-14T15:00:00.000000Z\"}}","channel":"channel.668"}"#,
+    r#"{"event":"App\\Events\\MessageDeletedEvent","data":"{\"id\":\"5\",\"message\":{\"id\":\"9b3ba3a4-3c8c-4d8b-9a0e-0c2b2d1f1a11\"},\"aiModerated\":false}","channel":"chatrooms.668.v2"}"#,
+    r#"{"event":"App\\Events\\UserBannedEvent","data":"{\"id\":\"1\",\"user\":{\"id\":1234,\"username\":\"SomeUser\",\"slug\":\"someuser\"},\"banned_by\":{\"id\":0,\"username\":\"mod\",\"slug\":\"mod\"}}","channel":"chatrooms.668.v2"}"#,
+    r#"{"event":"App\\Events\\StopStreamBroadcast","data":"{\"livestream\":{\"id\":1,\"channel\":{\"id\":668,\"is_banned\":false}}}","channel":"channel.668"}"#,
+  ];
+
+  #[tokio::test]
+  async fn kick_websocket_stub() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+    use crate::provider::{kick, IncomingMessage};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+      ws.send(Message::Text(r#"{"event":"pusher:connection_established","data":"{\"socket_id\":\"1.2\",\"activity_timeout\":120}"}"#.to_owned())).await.unwrap();
+      let mut subscribed : Vec<String> = Vec::new();
+      while subscribed.len() < 2 && let Some(Ok(msg)) = ws.next().await {
+        subscribed.push(msg.into_text().unwrap());
+      }
+      for frame in KICK_FRAMES {
+        ws.send(Message::Text(frame.to_owned())).await.unwrap();
+      }
+      ws.close(None).await.unwrap();
+      subscribed
+    });
+
+    let info = serde_json::from_str::<kick::KickChannelInfo>(KICK_CHANNEL_JSON).unwrap();
+    let (tx, rx) = async_channel::unbounded::<IncomingMessage>();
+    let ctx = egui::Context::default();
+    kick::spawn_websocket_chat_client(&format!("ws://{addr}"), "KICK:xqc", &info, &tx, &ctx).await.unwrap();
+
+    let subscribed = server.await.unwrap();
+    assert!(subscribed[0].contains("chatrooms.668.v2"));
+    assert!(subscribed[1].contains("channel.668"));
+
+    let mut messages = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+      messages.push(msg);
+    }
+    let chat = messages.iter().find_map(|m| match m { IncomingMessage::PrivMsg { message } if message.username == "someuser" => Some(message), _ => None }).expect("chat message");
+    assert_eq!(chat.channel, "KICK:xqc");
+    assert_eq!(chat.message, "hello KEKW KEKW");
+    assert_eq!(chat.profile.display_name.as_deref(), Some("SomeUser"));
+    assert_eq!(chat.profile.color, Some((0xE9, 0x11, 0x3C)));
+    assert_eq!(chat.profile.badges, Some(vec!["moderator".to_owned(), "subscriber/3".to_owned()]));
+    assert_eq!(chat.id, "9b3ba3a4-3c8c-4d8b-9a0e-0c2b2d1f1a11");
+    assert!(messages.iter().any(|m| matches!(m, IncomingMessage::MessageDeleted { id, .. } if id == &chat.id)));
+    assert!(messages.iter().any(|m| matches!(m, IncomingMessage::MsgEmotes { emote_ids, .. } if emote_ids == &vec![("37226".to_owned(), "KEKW".to_owned())])));
+    assert!(messages.iter().any(|m| matches!(m, IncomingMessage::UserMuted { username, .. } if username == "someuser")));
+    let statuses = messages.iter().filter_map(|m| match m { IncomingMessage::StreamingStatus { status: Some(status), .. } => Some(status), _ => None }).collect::<Vec<_>>();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses[0].is_live);
+    assert_eq!(statuses[0].title.as_deref(), Some("JUST CHATTING"));
+    assert!(!statuses[1].is_live);
+  }
+
+  const YT_CHANNEL_LIVE_HTML : &str = r#"<html><head><meta name="title" content="Stream &amp; Chill"><link rel="canonical" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"></head>
+    <body><script>var ytInitialPlayerResponse = {"videoDetails":{"isLiveContent":true},"microformat":{"playerMicroformatRenderer":{"liveBroadcastDetails":{"isLiveNow":true}}}};</script></body></html>"#;
+
+  const YT_LIVE_CHAT_HTML : &str = r#"<html><script>ytcfg.set({"INNERTUBE_API_KEY":"test-key","INNERTUBE_CLIENT_VERSION":"2.20240514.01.00"});
+    window["ytInitialData"] = {"contents":{"liveChatRenderer":{"continuations":[{"invalidationContinuationData":{"continuation":"first-page","timeoutMs":10000}}]}}};</script></html>"#;
+
+  const YT_LIVE_CHAT_JSON : &str = r#"{"continuationContents":{"liveChatContinuation":{
+    "continuations":[{"invalidationContinuationData":{"continuation":"second-page","timeoutMs":2500}}],
+    "actions":[
+      {"addChatItemAction":{"item":{"liveChatTextMessageRenderer":{"id":"1","timestampUsec":"1715699000000000",
+        "authorName":{"simpleText":"Some Mod"},"authorExternalChannelId":"UCmodChannelId0123456789",
+        "authorBadges":[{"liveChatAuthorBadgeRenderer":{"icon":{"iconType":"MODERATOR"},"tooltip":"Moderator"}}],
+        "message":{"runs":[{"text":"hello "},{"emoji":{"emojiId":"UCkszU2WH9gy1mb0dV-11UJg/abc","shortcuts":[":yt:",":oops:"],"isCustomEmoji":true,
+          "image":{"thumbnails":[{"url":"https://yt3.ggpht.com/small"},{"url":"https://yt3.ggpht.com/large"}]}}},{"emoji":{"emojiId":"😀","shortcuts":[":grinning:"]}}]}}}}},
+      {"addChatItemAction":{"item":{"liveChatTextMessageRenderer":{"id":"2","timestampUsec":"1715699001000000",
+        "authorName":{"simpleText":"Member Person"},
+        "authorBadges":[{"liveChatAuthorBadgeRenderer":{"customThumbnail":{"thumbnails":[{"url":"https://yt3.ggpht.com/badge"}]},"tooltip":"Member (2 months)"}}],
+        "message":{"runs":[{"text":"hi"}]}}}}},
+      {"addChatItemAction":{"item":{"liveChatPaidMessageRenderer":{"id":"3","timestampUsec":"1715699002000000",
+        "authorName":{"simpleText":"Big Spender"},"purchaseAmountText":{"simpleText":"$5.00"},
+        "message":{"runs":[{"text":"great stream"}]}}}}},
+      {"addChatItemAction":{"item":{"liveChatMembershipItemRenderer":{"id":"4","timestampUsec":"1715699003000000",
+        "authorName":{"simpleText":"New Member"},"headerSubtext":{"runs":[{"text":"Welcome to "},{"text":"Test Channel"}]}}}}},
+      {"markChatItemAsDeletedAction":{"deletedStateMessage":{"runs":[{"text":"[message retracted]"}]},"targetItemId":"2"}},
+      {"addLiveChatTickerItemAction":{"item":{}}}
+    ]}}}"#;
+
+  #[tokio::test]
+  async fn youtube_live_chat_fixture() {
+    use warp::Filter;
+    use crate::provider::{youtube, MessageType};
+
+    let channel_live = warp::get().and(warp::path!("@testchannel" / "live")).map(|| warp::reply::html(YT_CHANNEL_LIVE_HTML));
+    let live_chat = warp::get().and(warp::path!("live_chat")).map(|| warp::reply::html(YT_LIVE_CHAT_HTML));
+    let get_live_chat = warp::post()
+      .and(warp::path!("youtubei" / "v1" / "live_chat" / "get_live_chat"))
+      .and(warp::query::<std::collections::HashMap<String, String>>())
+      .and(warp::body::json::<serde_json::Value>())
+      .map(|query: std::collections::HashMap<String, String>, body: serde_json::Value| {
+        assert_eq!(query.get("key").map(|k| k.as_str()), Some("test-key"));
+        assert_eq!(body["continuation"], "first-page");
+        assert_eq!(body["context"]["client"]["clientVersion"], "2.20240514.01.00");
+        warp::reply::with_header(YT_LIVE_CHAT_JSON, "content-type", "application/json")
+      });
+    let watch = warp::get()
+      .and(warp::path!("watch"))
+      .and(warp::query::<std::collections::HashMap<String, String>>())
+      .map(|query: std::collections::HashMap<String, String>| match query.get("v").map(|v| v.as_str()) {
+        Some("dQw4w9WgXcQ") => warp::reply::html(YT_CHANNEL_LIVE_HTML.to_owned()),
+        _ => warp::reply::html(YT_CHANNEL_LIVE_HTML.replace("\"isLiveNow\":true", "\"isLiveNow\":false"))
+      });
+    let (addr, server) = warp::serve(channel_live.or(watch).or(live_chat).or(get_live_chat)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let http = youtube::YoutubeHttp::new(&format!("http://{addr}"));
+    let video = http.resolve_live_video("@testchannel").await.unwrap().expect("channel should be live");
+    assert_eq!(video.video_id, "dQw4w9WgXcQ");
+    assert_eq!(video.title.as_deref(), Some("Stream & Chill"));
+    let video_url = http.resolve_live_video("https://www.youtube.com/watch?v=dQw4w9WgXcQ").await.unwrap().expect("video should be live");
+    assert_eq!((video_url.video_id.as_str(), video_url.title.as_deref()), ("dQw4w9WgXcQ", Some("Stream & Chill")));
+    assert!(http.resolve_live_video("AAAAAAAAAAA").await.unwrap().is_none(), "ended stream should be offline");
+
+    let session = http.get_live_chat_session(&video.video_id).await.unwrap();
+    assert_eq!(session.api_key, "test-key");
+    assert_eq!(session.continuation, "first-page");
+
+    let page = http.get_live_chat_page(&session, "YT:@testchannel").await.unwrap();
+    assert_eq!(page.continuation.as_deref(), Some("second-page"));
+    assert_eq!(page.timeout_ms, 2500);
+    assert_eq!(page.emotes, vec![(":yt:".to_owned(), "https://yt3.ggpht.com/large".to_owned())]);
+    assert_eq!(page.messages.len(), 4);
+    assert_eq!(page.deleted, vec!["2".to_owned()]);
+
+    let moderator = &page.messages[0];
+    assert_eq!(moderator.channel, "YT:@testchannel");
+    assert_eq!(moderator.username, "UCmodChannelId0123456789");
+    assert_eq!(moderator.profile.display_name.as_deref(), Some("Some Mod"));
+    assert_eq!(moderator.message, "hello :yt: 😀");
+    assert_eq!(moderator.profile.badges, Some(vec!["moderator".to_owned()]));
+    assert_eq!(moderator.profile.color, Some((94, 132, 241)));
+    assert_eq!(moderator.timestamp.timestamp(), 1715699000);
+    assert_eq!(moderator.id, "1");
+
+    let member = &page.messages[1];
+    assert_eq!(member.profile.badges, Some(vec!["member".to_owned()]));
+    assert_eq!(member.profile.color, Some((43, 166, 64)));
+
+    let super_chat = &page.messages[2];
+    assert!(super_chat.msg_type == MessageType::Announcement);
+    assert_eq!(super_chat.message, "$5.00 Super Chat from Big Spender: great stream");
+
+    let membership = &page.messages[3];
+    assert!(membership.msg_type == MessageType::Announcement);
+    assert_eq!(membership.message, "New Member: Welcome to Test Channel");
+  }
+
+  #[tokio::test]
+  async fn irc_fake_server() {
+    use std::collections::HashSet;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use crate::provider::{generic_irc, channel::IrcNetwork, IncomingMessage, MessageType, OutgoingMessage};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let (read, mut write) = stream.into_split();
+      let mut lines = BufReader::new(read).lines();
+      let mut received : Vec<String> = Vec::new();
+      while let Some(line) = lines.next_line().await.unwrap() {
+        received.push(line.to_owned());
+        let reply = match line.as_str() {
+          "CAP REQ :sasl" | "CAP REQ sasl" => ":irc.test CAP * ACK :sasl\r\n",
+          "AUTHENTICATE PLAIN" => "AUTHENTICATE +\r\n",
+          x if x.starts_with("AUTHENTICATE ") => ":irc.test 903 tester :SASL authentication successful\r\n",
+          "CAP END" => ":irc.test 001 tester :Welcome to the test network\r\n",
+          "JOIN #test" => concat!(
+            ":tester!t@localhost JOIN #test\r\n",
+            ":irc.test 353 tester = #test :tester @alice +bob\r\n",
+            ":irc.test 366 tester #test :End of /NAMES list.\r\n",
+            ":alice!a@localhost PRIVMSG #test :hello world\r\n",
+            ":alice!a@localhost PRIVMSG #test :\u{1}ACTION waves\u{1}\r\n",
+            ":alice!a@localhost PRIVMSG tester :not shown\r\n",
+            ":carol!c@localhost JOIN #test\r\n",
+            ":carol!c@localhost PART #test :bye\r\n",
+            ":bob!b@localhost QUIT :gone\r\n"),
+          x if x.starts_with("QUIT") => break,
+          _ => continue
+        };
+        write.write_all(reply.as_bytes()).await.unwrap();
+      }
+      received
+    });
+
+    let network = IrcNetwork {
+      server: "127.0.0.1".to_owned(),
+      port: addr.port(),
+      use_tls: false,
+      nickname: "tester".to_owned(),
+      password: "hunter2".to_owned(),
+      use_sasl: true
+    };
+    let (tx, rx) = async_channel::unbounded::<IncomingMessage>();
+    let (in_tx, in_rx) = async_channel::unbounded::<OutgoingMessage>();
+    in_tx.send(OutgoingMessage::Join { channel_name: "#test".to_owned() }).await.unwrap();
+    let client = tokio::spawn(async move {
+      let mut channels : HashSet<String> = Default::default();
+      generic_irc::spawn_irc_client(&network, &mut channels, &tx, &in_rx, &egui::Context::default()).await.unwrap()
+    });
+
+    let mut messages = Vec::new();
+    tokio::time::timeout(std::time::Duration::from_secs(10), async {
+      while let Ok(msg) = rx.recv().await {
+        let done = matches!(&msg, IncomingMessage::UserLeave { username, .. } if username == "bob");
+        messages.push(msg);
+        if done {
+          break;
+        }
+      }
+    }).await.expect("timed out waiting for chat");
+
+    in_tx.send(OutgoingMessage::Chat { channel: "#test".to_owned(), message: "hi there".to_owned() }).await.unwrap();
+    in_tx.send(OutgoingMessage::Quit {}).await.unwrap();
+    assert!(client.await.unwrap(), "client should exit on quit");
+    let received = server.await.unwrap();
+    while let Ok(msg) = rx.try_recv() {
+      messages.push(msg);
+    }
+
+    assert!(received.contains(&"AUTHENTICATE dGVzdGVyAHRlc3RlcgBodW50ZXIy".to_owned()));
+    assert!(received.contains(&"JOIN #test".to_owned()));
+    assert!(received.contains(&"PRIVMSG #test :hi there".to_owned()));
+
+    let channel = generic_irc::channel_name("127.0.0.1", "#test");
+    assert_eq!(channel, "IRC:127.0.0.1/#test");
+    let chats = messages.iter().filter_map(|m| match m { IncomingMessage::PrivMsg { message } if message.msg_type == MessageType::Chat => Some(message), _ => None }).collect::<Vec<_>>();
+    assert_eq!(chats.len(), 3);
+    assert!(chats.iter().all(|m| m.channel == channel));
+    assert_eq!((chats[0].username.as_str(), chats[0].message.as_str()), ("alice", "hello world"));
+    assert_eq!(chats[1].message, "waves");
+    assert_eq!((chats[2].username.as_str(), chats[2].message.as_str()), ("tester", "hi there"));
+    assert!(messages.iter().any(|m| matches!(m, IncomingMessage::PrivMsg { message } if message.msg_type == MessageType::Information && message.message == "Joined #test.")));
+
+    let joined = messages.iter().filter_map(|m| match m { IncomingMessage::UserJoin { username, .. } => Some(username.as_str()), _ => None }).collect::<HashSet<_>>();
+    assert_eq!(joined, HashSet::from(["tester", "alice", "bob", "carol"]));
+    let left = messages.iter().filter_map(|m| match m { IncomingMessage::UserLeave { username, .. } => Some(username.as_str()), _ => None }).collect::<Vec<_>>();
+    assert_eq!(left, vec!["carol", "bob"]);
+  }
+
+  #[tokio::test]
+  async fn twitch_eventsub_mock_server() {
+    use std::{collections::HashMap, sync::{Arc, Mutex}};
+    use futures::{SinkExt, StreamExt};
+    use serde_json::{json, Value};
+    use warp::Filter;
+    use crate::provider::{twitch_eventsub::{self, EventSubConfig, EventSubState, StatusUpdate}, ChannelEvent, IncomingMessage};
+
+    let notification = |sub_type: &str, event: Value| json!({
+      "metadata": { "message_id": sub_type, "message_type": "notification", "subscription_type": sub_type, "subscription_version": "1" },
+      "payload": { "subscription": { "id": "sub", "type": sub_type, "version": "1" }, "event": event }
+    }).to_string();
+    let frames = vec![
+      json!({ "metadata": { "message_id": "1", "message_type": "session_welcome" }, "payload": { "session": { "id": "test-session", "status": "connected", "keepalive_timeout_seconds": 10, "reconnect_url": null } } }).to_string(),
+      notification("stream.online", json!({ "broadcaster_user_id": "71092938", "broadcaster_user_login": "xqc", "type": "live", "started_at": "2024-05-14T15:00:00Z" })),
+      notification("channel.raid", json!({ "from_broadcaster_user_name": "Destiny", "to_broadcaster_user_id": "71092938", "viewers": 1234 })),
+      notification("channel.poll.end", json!({ "broadcaster_user_id": "71092938", "title": "Best emote?", "status": "completed",
+        "choices": [{ "id": "a", "title": "KEKW", "votes": 10 }, { "id": "b", "title": "PogU", "votes": 4 }] })),
+      notification("channel.prediction.end", json!({ "broadcaster_user_id": "71092938", "title": "Win?", "status": "resolved", "winning_outcome_id": "2",
+        "outcomes": [{ "id": "1", "title": "Yes" }, { "id": "2", "title": "No" }] })),
+      notification("channel.follow", json!({ "broadcaster_user_id": "999", "user_name": "SomeoneElse" })),
+      notification("stream.offline", json!({ "broadcaster_user_id": "71092938" })),
+      json!({ "metadata": { "message_id": "9", "message_type": "session_reconnect" }, "payload": { "session": { "id": "test-session", "status": "reconnecting", "reconnect_url": "ws://127.0.0.1:1/ws?reconnect" } } }).to_string(),
+    ];
+
+    let subscriptions : Arc<Mutex<Vec<Value>>> = Default::default();
+    let subscriptions2 = subscriptions.clone();
+    let subscribe = warp::post()
+      .and(warp::path!("eventsub" / "subscriptions"))
+      .and(warp::header::<String>("authorization"))
+      .and(warp::body::json::<Value>())
+      .map(move |auth: String, body: Value| {
+        assert_eq!(auth, "Bearer test-token");
+        let mut subscriptions = subscriptions2.lock().unwrap();
+        // past the session's cost limit
+        let capped = body["type"] == "stream.online" && body["condition"]["broadcaster_user_id"] == "555";
+        subscriptions.push(body);
+        let reply = warp::reply::json(&json!({ "data": [{ "id": format!("sub-{}", subscriptions.len()), "status": "enabled" }] }));
+        warp::reply::with_status(reply, if capped { warp::http::StatusCode::TOO_MANY_REQUESTS } else { warp::http::StatusCode::ACCEPTED })
+      });
+    let ws = warp::path("ws").and(warp::ws()).map(move |ws: warp::ws::Ws| {
+      let frames = frames.to_owned();
+      ws.on_upgrade(|socket| async move {
+        let (mut tx, mut rx) = socket.split();
+        for frame in frames {
+          tx.send(warp::ws::Message::text(frame)).await.unwrap();
+        }
+        while rx.next().await.is_some() {}
+      })
+    });
+    let (addr, server) = warp::serve(subscribe.or(ws)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let config = EventSubConfig {
+      ws_url: format!("ws://{addr}/ws"),
+      subscription_url: format!("http://{addr}/eventsub/subscriptions"),
+      token: "test-token".to_owned(),
+      client_id: "test-client".to_owned()
+    };
+    let mut state = EventSubState {
+      user_id: Some("71092938".to_owned()),
+      channels: HashMap::from([("xqc".to_owned(), "71092938".to_owned()), ("capped".to_owned(), "555".to_owned())]),
+      ..Default::default()
+    };
+    let (command_tx, command_rx) = async_channel::unbounded();
+    let (tx, rx) = async_channel::unbounded::<IncomingMessage>();
+    let (update_tx, update_rx) = async_channel::unbounded::<StatusUpdate>();
+    let reconnect_url = tokio::time::timeout(std::time::Duration::from_secs(10),
+      twitch_eventsub::spawn_eventsub_client(&config, &config.ws_url, &mut state, &command_rx, &tx, &update_tx, &egui::Context::default(), &reqwest::Client::new()))
+      .await.expect("timed out").unwrap();
+    drop(command_tx);
+    assert_eq!(reconnect_url.as_deref(), Some("ws://127.0.0.1:1/ws?reconnect"));
+
+    let subscriptions = subscriptions.lock().unwrap();
+    // broadcaster-only types are only tried for the user's own channel
+    assert_eq!(subscriptions.len(), 14);
+    assert_eq!(state.subscription_ids["xqc"].len(), 12);
+    assert_eq!(state.subscription_ids["capped"].len(), 1);
+    assert!(subscriptions.iter().all(|s| s["transport"]["method"] == "websocket" && s["transport"]["session_id"] == "test-session"));
+    let raid = subscriptions.iter().find(|s| s["type"] == "channel.raid").unwrap();
+    assert_eq!(raid["condition"]["to_broadcaster_user_id"], "71092938");
+    let follow = subscriptions.iter().find(|s| s["type"] == "channel.follow").unwrap();
+    assert_eq!(follow["version"], "2");
+    assert_eq!(follow["condition"]["moderator_user_id"], "71092938");
+
+    let updates = std::iter::from_fn(|| update_rx.try_recv().ok()).collect::<Vec<_>>();
+    assert_eq!(updates.len(), 4);
+    let subscribed = updates[..2].iter().filter_map(|u| match u { StatusUpdate::Subscribed { channel, live_events } => Some((channel.as_str(), *live_events)), _ => None }).collect::<HashMap<_, _>>();
+    assert_eq!(subscribed, HashMap::from([("xqc", true), ("capped", false)]));
+    assert!(matches!(&updates[2], StatusUpdate::Live { channel, is_live: true } if channel == "xqc"));
+    assert!(matches!(&updates[3], StatusUpdate::Live { channel, is_live: false } if channel == "xqc"));
+
+    let messages = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+    let statuses = messages.iter().filter_map(|m| match m { IncomingMessage::StreamingStatus { channel, status: Some(status) } if channel == "xqc" => Some(status), _ => None }).collect::<Vec<_>>();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses[0].is_live);
+    assert_eq!(statuses[0].started_at.as_deref(), Some("2024-05-14T15:00:00Z"));
+    assert!(!statuses[1].is_live);
+
+    let events = messages.iter().filter_map(|m| match m { IncomingMessage::ChannelEvent { channel, event, .. } if channel == "xqc" => Some(event.to_owned()), _ => None }).collect::<Vec<_>>();
+    assert_eq!(events, vec![
+      ChannelEvent::Raid { from: "Destiny".to_owned(), viewers: 1234 },
+      ChannelEvent::PollEnd { title: "Best emote?".to_owned(), results: vec![("KEKW".to_owned(), 10), ("PogU".to_owned(), 4)] },
+      ChannelEvent::PredictionEnd { title: "Win?".to_owned(), winner: Some("No".to_owned()) },
+    ]);
+    assert_eq!(events[0].message(), "Destiny is raiding with 1234 viewers");
+  }
+
+  #[test]
+  fn twitch_usernotice_and_roomstate() {
+    use crate::provider::{twitch, channel::ChatModes, MessageType};
+
+    let tags = |line: &str| line.parse::<irc::proto::Message>().unwrap().tags.unwrap();
+
+    let resub = tags(r"@badges=subscriber/12;color=#FF0000;display-name=SomeUser;emotes=25:5-9;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;login=someuser;msg-id=resub;system-msg=SomeUser\ssubscribed\sfor\s12\smonths!;tmi-sent-ts=1715699000000 :tmi.twitch.tv USERNOTICE #xqc :year Kappa");
+    let messages = twitch::get_user_notice_messages(&resub, "xqc", Some(&"year Kappa".to_owned()));
+    assert_eq!(messages.len(), 2);
+    assert!(messages[0].msg_type == MessageType::Announcement);
+    assert_eq!(messages[0].message, "SomeUser subscribed for 12 months!");
+    assert!(messages[1].msg_type == MessageType::Chat);
+    assert_eq!(messages[1].username, "someuser");
+    assert_eq!(messages[1].message, "year Kappa");
+    assert_eq!(messages[1].id, "b34ccfc7-4977-403a-8a94-33c6bac34fb8");
+
+    let announcement = tags(r"@display-name=SomeMod;id=1;login=somemod;msg-id=announcement;msg-param-color=PRIMARY;system-msg= :tmi.twitch.tv USERNOTICE #xqc :be nice");
+    let messages = twitch::get_user_notice_messages(&announcement, "xqc", Some(&"be nice".to_owned()));
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].msg_type == MessageType::Announcement);
+    assert_eq!(messages[0].message, "SomeMod: be nice");
+
+    let initial = twitch::parse_room_modes(&tags("@emote-only=0;followers-only=-1;r9k=0;room-id=71092938;slow=0;subs-only=0 :tmi.twitch.tv ROOMSTATE #xqc"), &Default::default());
+    assert_eq!(initial, ChatModes::default());
+    let updated = twitch::parse_room_modes(&tags("@room-id=71092938;slow=30 :tmi.twitch.tv ROOMSTATE #xqc"), &initial);
+    let updated = twitch::parse_room_modes(&tags("@followers-only=10;room-id=71092938 :tmi.twitch.tv ROOMSTATE #xqc"), &updated);
+    assert_eq!(updated, ChatModes { slow: Some(30), followers_only: Some(10), emote_only: false, subs_only: false });
+    assert_eq!(updated.labels(), vec!["slow 30s", "followers-only 10m"]);
+    assert_eq!(updated.changes_from(&initial), vec!["Slow mode is on, 30 seconds between messages.", "Followers-only mode is on, must follow for 10 minutes."]);
+  }
+
+  #[tokio::test]
+  async fn twitch_helix_moderation() {
+    use std::collections::HashMap;
+    use warp::{Filter, http::StatusCode};
+    use crate::provider::{dgg, twitch_moderation::HelixModeration, ModerationAction};
+
+    let auth = warp::header::exact("authorization", "Bearer token123").and(warp::header::exact("client-id", "client123"));
+    let users = warp::get().and(warp::path!("users")).and(auth.clone()).and(warp::query::<HashMap<String, String>>())
+      .map(|query: HashMap<String, String>| match query.get("login").map(|x| x.as_str()) {
+        Some("someuser") => warp::reply::json(&serde_json::json!({ "data": [{ "id": "1234", "login": "someuser" }] })),
+        _ => warp::reply::json(&serde_json::json!({ "data": [] }))
+      });
+    let ban = warp::post().and(warp::path!("moderation" / "bans")).and(auth.clone()).and(warp::query::<HashMap<String, String>>()).and(warp::body::json::<serde_json::Value>())
+      .map(|query: HashMap<String, String>, body: serde_json::Value| {
+        assert_eq!(query.get("broadcaster_id").map(|x| x.as_str()), Some("71092938"));
+        assert_eq!(query.get("moderator_id").map(|x| x.as_str()), Some("5678"));
+        assert_eq!(body["data"]["user_id"], "1234");
+        assert_eq!(body["data"]["duration"], 600);
+        warp::reply::json(&serde_json::json!({ "data": [{ "user_id": "1234" }] }))
+      });
+    let unban = warp::delete().and(warp::path!("moderation" / "bans")).and(auth.clone())
+      .map(|| warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": "Bad Request", "status": 400, "message": "The user specified in the user_id field is not banned." })), StatusCode::BAD_REQUEST));
+    let delete = warp::delete().and(warp::path!("moderation" / "chat")).and(auth).and(warp::query::<HashMap<String, String>>())
+      .map(|query: HashMap<String, String>| {
+        assert_eq!(query.get("message_id").map(|x| x.as_str()), Some("b34ccfc7-4977-403a-8a94-33c6bac34fb8"));
+        StatusCode::NO_CONTENT
+      });
+    let (addr, server) = warp::serve(users.or(ban).or(unban).or(delete)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let helix = HelixModeration {
+      base_url: format!("http://{addr}"),
+      token: "token123".to_owned(),
+      client_id: "client123".to_owned(),
+      broadcaster_id: "71092938".to_owned(),
+      moderator_id: "5678".to_owned()
+    };
+    let client = reqwest::Client::new();
+    helix.send(&client, &ModerationAction::Timeout { username: "SomeUser".to_owned(), duration_sec: 600 }).await.unwrap();
+    helix.send(&client, &ModerationAction::DeleteMessage { id: "b34ccfc7-4977-403a-8a94-33c6bac34fb8".to_owned(), username: "someuser".to_owned() }).await.unwrap();
+    let unban = ModerationAction::Unban { username: "someuser".to_owned() };
+    let err = helix.send(&client, &unban).await.unwrap_err();
+    assert_eq!(unban.failure_message(&err.to_string()), "Failed to unban someuser: The user specified in the user_id field is not banned.");
+    let err = helix.send(&client, &ModerationAction::Ban { username: "nobody".to_owned() }).await.unwrap_err();
+    assert_eq!(err.to_string(), "user not found");
+
+    assert_eq!(dgg::moderation_command(&ModerationAction::Timeout { username: "Bob".to_owned(), duration_sec: 600 }).as_deref(), Some(r#"MUTE {"data":"Bob","duration":600000000000}"#));
+    assert_eq!(dgg::moderation_command(&ModerationAction::DeleteMessage { id: "1".to_owned(), username: "Bob".to_owned() }), None);
+
+    // DGG only reports a moderation result through the broadcast or an ERR
+    let pending = std::collections::VecDeque::from([
+      ModerationAction::Ban { username: "Bob".to_owned() },
+      ModerationAction::Timeout { username: "Alice".to_owned(), duration_sec: 600 }
+    ]);
+    assert_eq!(dgg::confirmed_moderation(&pending, "MUTE", "alice"), Some(1));
+    assert_eq!(dgg::confirmed_moderation(&pending, "BAN", "Bob"), Some(0));
+    assert_eq!(dgg::confirmed_moderation(&pending, "MUTE", "Bob"), None);
+    assert_eq!(dgg::confirmed_moderation(&pending, "BAN", "Carol"), None);
+    assert_eq!(dgg::moderation_error("nopermission"), Some("you are not a moderator"));
+    assert_eq!(dgg::moderation_error("duplicate"), None);
+
+    assert!(dgg::me_is_moderator(r#"{"nick":"Bob","features":["moderator","subscriber"],"timestamp":1680000000000}"#));
+    assert!(!dgg::me_is_moderator(r#"{"nick":"Bob","features":["subscriber"]}"#));
+    assert!(!dgg::me_is_moderator("null"));
+  }
+
+  #[test]
+  fn slash_commands() {
+    use crate::provider::ModerationAction;
+    use crate::ui::commands::{parse_command, parse_duration, ChatCommand};
+
+    assert_eq!(parse_command("hello /me"), None);
+    assert_eq!(parse_command("//not a command"), None);
+    assert_eq!(parse_command("/me waves hello"), Some(Ok(ChatCommand::Me { message: "waves hello".to_owned() })));
+    assert_eq!(parse_command("/JOIN #xqc"), Some(Ok(ChatCommand::Join { channel: "xqc".to_owned() })));
+    assert_eq!(parse_command("/timeout @Bob"), Some(Ok(ChatCommand::Moderate(ModerationAction::Timeout { username: "Bob".to_owned(), duration_sec: 600 }))));
+    assert_eq!(parse_command("/timeout Bob 1h"), Some(Ok(ChatCommand::Moderate(ModerationAction::Timeout { username: "Bob".to_owned(), duration_sec: 3600 }))));
+    assert_eq!(parse_command("/timeout Bob soon"), Some(Err("Usage: /timeout <user> [duration]".to_owned())));
+    assert_eq!(parse_command("/w Bob"), Some(Err("Usage: /w <user> <message>".to_owned())));
+    assert_eq!(parse_command("/w Bob hi there"), Some(Ok(ChatCommand::Whisper { username: "Bob".to_owned(), message: "hi there".to_owned() })));
+    assert_eq!(parse_command("/highlight cool bot"), Some(Ok(ChatCommand::Highlight { word: "cool bot".to_owned() })));
+    assert_eq!(parse_command("/search"), Some(Ok(ChatCommand::Search { text: "".to_owned() })));
+    assert_eq!(parse_command("/dance"), Some(Err("Unknown command /dance, type /help for a list of commands".to_owned())));
+
+    assert_eq!(parse_duration("90"), Some(90));
+    assert_eq!(parse_duration("10m"), Some(600));
+    assert_eq!(parse_duration("1d"), Some(86400));
+    assert_eq!(parse_duration("m"), None);
+  }
+
+  #[test]
+  fn ignored_users_excluded_from_combos() {
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use crate::emotes::Emote;
+    use crate::provider::{ChatMessage, ProviderName};
+    use crate::ui::addtl_functions::{is_user_ignored, push_history};
+
+    let global_emotes = HashMap::from([("KEKW".to_owned(), Emote { name: "KEKW".to_owned(), ..Default::default() })]);
+    let ignored_users = HashMap::from([(ProviderName::Twitch, HashSet::from(["spammer".to_owned()]))]);
+    let msg = |username: &str| ChatMessage { channel: "test".to_owned(), username: username.to_owned(), message: "KEKW".to_owned(), ..Default::default() };
+
+    let mut history = VecDeque::new();
+    for username in ["alice", "Spammer", "bob"] {
+      push_history(&mut history, msg(username), None, None, &global_emotes, &ignored_users, &[], &mut 0);
+    }
+
+    assert!(is_user_ignored(&ignored_users, &history[1].0));
+    assert!(history[1].0.combo_data.is_none());
+    let combo = history[2].0.combo_data.as_ref().unwrap();
+    assert_eq!(combo.count, 2);
+    assert_eq!(combo.users.iter().map(|(u, _)| u.as_str()).collect::<Vec<_>>(), vec!["alice", "bob"]);
+    assert!(!is_user_ignored(&ignored_users, &ChatMessage { provider: ProviderName::DGG, username: "spammer".to_owned(), ..Default::default() }));
+  }
+
+  #[test]
+  fn highlight_rules() {
+    use crate::provider::{ChatMessage, MessageType, UserProfile};
+    use crate::ui::highlights::{get_highlight, HighlightKind, HighlightRule};
+
+    let msg = |channel: &str, username: &str, message: &str| ChatMessage { channel: channel.to_owned(), username: username.to_owned(), message: message.to_owned(), ..Default::default() };
+    let mut rules = vec![
+      HighlightRule::keyword("Giveaway"),
+      HighlightRule { show_in_mentions: false, ..HighlightRule::new(HighlightKind::Regex, r"\bv\d+\.\d+\b", Some("dev")) },
+      HighlightRule::new(HighlightKind::User, "@Nightbot", None),
+    ];
+
+    assert!(get_highlight(&rules, &msg("a", "bob", "GIVEAWAY starting now")).is_some());
+    assert!(get_highlight(&rules, &ChatMessage { msg_type: MessageType::Information, ..msg("a", "", "giveaway") }).is_none());
+    assert!(get_highlight(&rules, &msg("dev", "bob", "released v1.20 today")).is_some_and(|r| !r.show_in_mentions));
+    assert!(get_highlight(&rules, &msg("other", "bob", "released v1.20 today")).is_none());
+    assert!(get_highlight(&rules, &ChatMessage { profile: UserProfile { display_name: Some("NightBot".to_owned()), ..Default::default() }, ..msg("a", "nightbot", "hi") }).is_some());
+
+    rules[1].rule.set_pattern("(".to_owned());
+    assert!(rules[1].error().is_some());
+    assert!(get_highlight(&rules, &msg("dev", "bob", "(")).is_none());
+    rules[0].rule.enabled = false;
+    assert!(get_highlight(&rules, &msg("a", "bob", "giveaway")).is_none());
+  }
+
+  #[test]
+  fn own_messages_do_not_alert() {
+    use crate::provider::ChatMessage;
+    use crate::sounds::AlertEvent;
+    use crate::ui::highlights::{message_alert, HighlightRule};
+
+    let rules = vec![HighlightRule { play_sound: false, ..HighlightRule::keyword("giveaway") }];
+    let msg = |username: &str, message: &str| ChatMessage { channel: "test".to_owned(), username: username.to_owned(), message: message.to_owned(), ..Default::default() };
+
+    assert_eq!(message_alert(Some("Me"), &rules, &msg("bob", "hey @me")), Some((AlertEvent::Mention, true)));
+    assert_eq!(message_alert(Some("Me"), &rules, &msg("bob", "giveaway time")), Some((AlertEvent::Highlight, false)));
+    assert_eq!(message_alert(Some("Me"), &rules, &msg("me", "giveaway time @me")), None);
+    assert_eq!(message_alert(None, &rules, &msg("me", "giveaway time")), Some((AlertEvent::Highlight, false)));
+    assert_eq!(message_alert(Some("Me"), &rules, &msg("bob", "hello")), None);
+  }
+
+  #[test]
+  fn message_filters() {
+    use std::collections::{HashMap, VecDeque};
+    use crate::emotes::Emote;
+    use crate::provider::{ChatMessage, FilterAction};
+    use crate::ui::addtl_functions::push_history;
+    use crate::ui::filters::MessageFilter;
+
+    let global_emotes = HashMap::from([("KEKW".to_owned(), Emote { name: "KEKW".to_owned(), ..Default::default() })]);
+    let filters = vec![
+      MessageFilter::new(r"(?i)spoiler", None, FilterAction::Blur),
+      MessageFilter::new(r"https?://", Some("test"), FilterAction::Hide),
+      MessageFilter::new(r"^KEKW$", None, FilterAction::Collapse),
+    ];
+    let msg = |channel: &str, message: &str| ChatMessage { channel: channel.to_owned(), username: "bob".to_owned(), message: message.to_owned(), ..Default::default() };
+
+    let mut history = VecDeque::new();
+    let mut hidden_count = 0;
+    for (channel, message) in [("test", "SPOILER: he dies"), ("test", "free stuff at https://example.com"), ("other", "https://example.com"), ("test", "KEKW"), ("test", "KEKW")] {
+      push_history(&mut history, msg(channel, message), None, None, &global_emotes, &HashMap::default(), &filters, &mut hidden_count);
+    }
+
+    assert_eq!(history.iter().map(|(m, _)| m.filtered).collect::<Vec<_>>(), vec![Some(FilterAction::Blur), Some(FilterAction::Hide), None, Some(FilterAction::Collapse), Some(FilterAction::Collapse)]);
+    assert!(history.iter().all(|(m, _)| m.combo_data.is_none()));
+    assert_eq!(hidden_count, 3);
+  }
+
+  #[test]
+  fn notification_rate_limit() {
+    use chrono::{Duration, Utc};
+    use crate::notifications::{Notifications, RecordingNotifier, NOTIFICATION_RATE_LIMIT, NOTIFICATION_RATE_WINDOW_SECS};
+
+    let recorder = RecordingNotifier::default();
+    let mut notifications = Notifications::new(Box::new(recorder.clone()));
+    let start = Utc::now();
+
+    for ix in 0..NOTIFICATION_RATE_LIMIT {
+      assert!(notifications.notify("bob in test", &format!("hey {ix}"), start));
+    }
+    assert!(!notifications.notify("bob in test", "dropped", start + Duration::seconds(1)));
+    assert!(notifications.notify("bob in test", "later", start + Duration::seconds(NOTIFICATION_RATE_WINDOW_SECS)));
+
+    let sent = recorder.notifications.lock().unwrap();
+    assert_eq!(sent.len(), NOTIFICATION_RATE_LIMIT + 1);
+    assert_eq!(sent.last().unwrap(), &("bob in test".to_owned(), "later".to_owned()));
+  }
+
+  #[test]
+  fn notification_text_not_in_script() {
+    use std::ffi::OsStr;
+    use crate::notifications::powershell_notify_command;
+
+    let body = "hi\u{2019}); Remove-Item -Recurse C:\\ ; $x = ('\u{201B}'";
+    let command = powershell_notify_command("bob in test", body);
+
+    let args = command.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    assert!(args.iter().all(|a| !a.contains("Remove-Item") && !a.contains("bob in test")));
+    let envs = command.get_envs().collect::<Vec<_>>();
+    assert!(envs.contains(&(OsStr::new("GIGACHAT_NOTIFY_BODY"), Some(OsStr::new(body)))));
+    assert!(envs.contains(&(OsStr::new("GIGACHAT_NOTIFY_TITLE"), Some(OsStr::new("bob in test")))));
+  }
+
+  #[test]
+  fn alert_sounds() {
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use crate::sounds::{sound_file_error, AlertEvent, NullSink, SoundPlayer, SoundSettings, SoundSink};
+
+    #[derive(Clone, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<(PathBuf, f32)>>>);
+    impl SoundSink for RecordingSink {
+      fn play(&mut self, path: &Path, volume: f32) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push((path.to_owned(), volume));
+        Ok(())
+      }
+    }
+
+    let sink = RecordingSink::default();
+    let mut player = SoundPlayer::new(Box::new(sink.clone()));
+    let mut settings = SoundSettings { mention_sound: Some(PathBuf::from("ping.ogg")), volume: 2., ..Default::default() };
+
+    assert!(!player.play_alert(&settings, AlertEvent::Mention));
+    settings.enabled = true;
+    assert!(player.play_alert(&settings, AlertEvent::Mention));
+    assert!(!player.play_alert(&settings, AlertEvent::Live));
+    assert_eq!(*sink.0.lock().unwrap(), vec![(PathBuf::from("ping.ogg"), 1.)]);
+
+    assert!(SoundPlayer::new(Box::new(NullSink)).play_alert(&settings, AlertEvent::Mention));
+    assert!(sound_file_error(Path::new("ping.mp3")).is_some());
+    assert_eq!(sound_file_error(Path::new("missing.WAV")), Some("File not found"));
+  }
+
+  #[test]
+  fn channel_status_changes() {
+    use crate::provider::channel::{ChannelStatus, StatusChange};
+
+    let offline = ChannelStatus { title: Some("old title".to_owned()), game_name: Some("Chess".to_owned()), ..Default::default() };
+    let live = ChannelStatus { is_live: true, title: Some("new title".to_owned()), game_name: Some("Chess".to_owned()), ..Default::default() };
+    let changes = live.changes_since(&offline);
+    assert_eq!(changes, vec![StatusChange::WentLive { title: Some("new title".to_owned()), game_name: Some("Chess".to_owned()) }]);
+    assert_eq!(changes[0].message("destiny"), "destiny went live: new title (Chess)");
+
+    let updated = ChannelStatus { game_name: Some("Just Chatting".to_owned()), ..live.clone() };
+    assert_eq!(updated.changes_since(&live), vec![StatusChange::Game("Just Chatting".to_owned())]);
+    // live state only updates don't clear the title
+    assert!(ChannelStatus { is_live: true, ..Default::default() }.changes_since(&live).is_empty());
+    assert!(ChannelStatus { is_live: false, ..live.clone() }.changes_since(&live).is_empty());
+  }
+
+  #[test]
+  fn unread_counter() {
+    use crate::provider::ChatMessage;
+    use crate::ui::models::UnreadCounter;
+
+    let msg = |username: &str| ChatMessage { channel: "test".to_owned(), username: username.to_owned(), message: "hi".to_owned(), ..Default::default() };
+    let first = msg("alice");
+    let mut unread = UnreadCounter::default();
+    unread.add(&first, false);
+    unread.add(&msg("bob"), true);
+    unread.add(&msg("carol"), false);
+
+    assert_eq!((unread.messages, unread.mentions), (3, 1));
+    assert!(unread.first_unread.as_ref().is_some_and(|m| m.matches(&first)));
+    assert_eq!(unread.label(), "3 unread messages, 1 mention");
+
+    // a second message from the same user at the same time is a different message
+    let again = ChatMessage { timestamp: first.timestamp, ..msg("alice") };
+    assert!(unread.first_unread.as_ref().is_some_and(|m| !m.matches(&again)));
+  }
+
+  #[tokio::test]
+  async fn twitch_whispers() {
+    use std::collections::HashMap;
+    use warp::{Filter, http::StatusCode};
+    use crate::provider::{twitch, twitch_whispers::HelixWhisper};
+
+    let line = "@badges=;color=#FF0000;display-name=SomeUser;message-id=1;thread-id=1234_5678;user-id=1234 :someuser!someuser@someuser.tmi.twitch.tv WHISPER me :hello there";
+    let (conversation, whisper) = twitch::get_whisper(&line.parse::<irc::proto::Message>().unwrap()).unwrap();
+    assert_eq!(conversation, "someuser");
+    assert_eq!(whisper.message, "hello there");
+    assert_eq!(whisper.profile.display_name.as_deref(), Some("SomeUser"));
+    assert_eq!(whisper.profile.color, Some((255, 0, 0)));
+    assert!(twitch::get_whisper(&"@badges= :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #chan :hello".parse::<irc::proto::Message>().unwrap()).is_none());
+
+    let auth = warp::header::exact("authorization", "Bearer token123").and(warp::header::exact("client-id", "client123"));
+    let users = warp::get().and(warp::path!("users")).and(auth.clone()).and(warp::query::<HashMap<String, String>>())
+      .map(|query: HashMap<String, String>| match query.get("login").map(|x| x.as_str()) {
+        Some("someuser") => warp::reply::json(&serde_json::json!({ "data": [{ "id": "1234", "login": "someuser" }] })),
+        Some("private") => warp::reply::json(&serde_json::json!({ "data": [{ "id": "4321", "login": "private" }] })),
+        _ => warp::reply::json(&serde_json::json!({ "data": [] }))
+      });
+    let whispers = warp::post().and(warp::path!("whispers")).and(auth).and(warp::query::<HashMap<String, String>>()).and(warp::body::json::<serde_json::Value>())
+      .map(|query: HashMap<String, String>, body: serde_json::Value| {
+        assert_eq!(query.get("from_user_id").map(|x| x.as_str()), Some("5678"));
+        assert_eq!(body["message"], "hello back");
+        match query.get("to_user_id").map(|x| x.as_str()) {
+          Some("1234") => warp::reply::with_status(warp::reply::json(&serde_json::json!({})), StatusCode::NO_CONTENT),
+          _ => warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": "Forbidden", "status": 403, "message": "The recipient's settings prevent this sender from whispering them." })), StatusCode::FORBIDDEN)
+        }
+      });
+    let (addr, server) = warp::serve(users.or(whispers)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let helix = HelixWhisper {
+      base_url: format!("http://{addr}"),
+      token: "token123".to_owned(),
+      client_id: "client123".to_owned(),
+      from_user_id: "5678".to_owned()
+    };
+    let client = reqwest::Client::new();
+    helix.send(&client, "SomeUser", "hello back").await.unwrap();
+    let err = helix.send(&client, "private", "hello back").await.unwrap_err();
+    assert_eq!(err.to_string(), "The recipient's settings prevent this sender from whispering them.");
+    assert_eq!(helix.send(&client, "nobody", "hello back").await.unwrap_err().to_string(), "user not found");
+  }
+
+  #[tokio::test]
+  async fn dgg_private_messages() {
+    use std::sync::{Arc, Mutex};
+    use warp::Filter;
+    use crate::provider::{ChatMessage, ProviderName};
+    use crate::ui::models::WhisperConversation;
+
+    let auth = warp::header::exact("cookie", "authtoken=token123");
+    let unread = warp::get().and(warp::path!("messages" / "unread")).and(auth.clone())
+      .map(|| warp::reply::json(&serde_json::json!([{ "username": "Bob", "unread": 1 }])));
+    let inbox = warp::get().and(warp::path!("messages" / "usr" / String / "inbox")).and(auth.clone())
+      .map(|username: String| {
+        assert_eq!(username, "Bob");
+        warp::reply::json(&serde_json::json!([
+          { "id": 3, "from": "Bob", "to": "Me", "message": "are you there?", "timestamp": "2023-03-17T19:47:00+0000", "isread": 0 },
+          { "id": 2, "from": "Me", "to": "Bob", "message": "hi", "timestamp": "2023-03-17T19:46:50+0000", "isread": 0 },
+          { "id": 1, "from": "Bob", "to": "Me", "message": "hello", "timestamp": "2023-03-17T19:46:40+0000", "isread": 1 }
+        ]))
+      });
+    let opened = Arc::new(Mutex::new(Vec::<String>::new()));
+    let opened_2 = opened.clone();
+    let open = warp::post().and(warp::path!("messages" / "msg" / String / "open")).and(auth)
+      .map(move |id: String| {
+        opened_2.lock().unwrap().push(id);
+        warp::reply::json(&serde_json::json!({}))
+      });
+    let (addr, server) = warp::serve(unread.or(inbox).or(open)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let api_url = format!("http://{addr}");
+    let client = reqwest::Client::new();
+    assert_eq!(dgg::load_unread_whispers(&client, &api_url, "token123").await.unwrap(), vec![("Bob".to_owned(), 1)]);
+    let (history, unread_ids) = dgg::load_whisper_history(&client, &api_url, "token123", "me", "Bob").await.unwrap();
+    assert_eq!(history.iter().map(|m| m.message.as_str()).collect::<Vec<_>>(), vec!["hello", "hi", "are you there?"]);
+    assert_eq!(history[0].timestamp.to_rfc3339(), "2023-03-17T19:46:40+00:00");
+    assert_eq!(history[1].username, "me");
+    assert_eq!(unread_ids, vec!["3".to_owned()]);
+    dgg::mark_whisper_read(&client, &api_url, "token123", "3").await.unwrap();
+    assert_eq!(*opened.lock().unwrap(), vec!["3".to_owned()]);
+    assert!(dgg::load_unread_whispers(&client, &api_url, "wrong").await.is_err());
+
+    let whisper = dgg::parse_private_message(r#"{"messageid":1234567,"timestamp":1679082420000,"nick":"Bob","data":"are you there?"}"#).unwrap();
+    assert_eq!((whisper.username.as_str(), whisper.id.as_str(), whisper.message.as_str()), ("bob", "1234567", "are you there?"));
+    assert_eq!(whisper.profile.display_name.as_deref(), Some("Bob"));
+    assert_eq!(whisper.timestamp.timestamp_millis(), 1679082420000);
+
+    // a message received while the history was loading is kept, a duplicate of one in the history is not
+    let mut convo = WhisperConversation::new(&ProviderName::DGG, "Bob");
+    convo.messages.push(ChatMessage { id: "3".to_owned(), username: "bob".to_owned(), message: "are you there?".to_owned(), ..Default::default() });
+    convo.messages.push(ChatMessage { id: "4".to_owned(), username: "bob".to_owned(), message: "hello?".to_owned(), ..Default::default() });
+    convo.unread = 2;
+    convo.add_history(history, unread_ids);
+    assert_eq!(convo.messages.iter().map(|m| m.message.as_str()).collect::<Vec<_>>(), vec!["hello", "hi", "are you there?", "hello?"]);
+    assert_eq!(convo.name(), "Bob");
+    assert_eq!((convo.unread, convo.unread_ids.len()), (2, 1));
+    assert!(convo.history_requested);
+  }
+
+  #[test]
+  fn dgg_polls() {
+    use chrono::{Duration, Utc};
+
+    let json = r#"{"canvote":true,"myvote":0,"nick":"Lemmiwinks","weighted":false,"start":"2023-03-17T19:46:40+0000","now":"2023-03-17T19:46:50+0000","time":30000,"question":"Are you wearing Green?","options":["PEPE","YEE"],"totals":[0,0],"totalvotes":0}"#;
+    let now = Utc::now();
+    let mut poll = serde_json::from_str::<dgg::DggPollStart>(json).unwrap().to_poll(now);
+    // 10 of the 30 seconds had passed on the server when the poll was sent
+    assert_eq!(poll.ends_at, now + Duration::seconds(20));
+    assert_eq!(poll.seconds_left(now + Duration::seconds(5)), 15);
+    assert_eq!(poll.seconds_left(now + Duration::seconds(60)), 0);
+    assert_eq!((poll.started_by.as_str(), poll.can_vote, poll.my_vote), ("Lemmiwinks", true, None));
+
+    poll.add_vote(1, 1);
+    poll.add_vote(1, 2);
+    poll.add_vote(0, 1);
+    poll.add_vote(5, 1);
+    assert_eq!((poll.totals.clone(), poll.total_votes()), (vec![1, 3], 4));
+    assert_eq!(poll.end_event().message(), "Poll ended: Are you wearing Green? (PEPE: 1, YEE: 3)");
+
+    let voted = json.replace(r#""myvote":0"#, r#""myvote":2"#);
+    assert_eq!(serde_json::from_str::<dgg::DggPollStart>(&voted).unwrap().to_poll(now).my_vote, Some(1));
+    // RFC 3339 times with fractional seconds are accepted too
+    let rfc3339 = json.replace("19:46:40+0000", "19:46:40.250Z").replace("19:46:50+0000", "19:46:50.250Z");
+    assert_eq!(serde_json::from_str::<dgg::DggPollStart>(&rfc3339).unwrap().to_poll(now).ends_at, now + Duration::seconds(20));
+
+    assert_eq!(dgg::parse_vote(r#"{"vote":"2","quantity":3}"#), Some((1, 3)));
+    assert_eq!(dgg::parse_vote(r#"{"vote":1}"#), Some((0, 1)));
+    assert_eq!(dgg::parse_vote(r#"{"vote":"0"}"#), None);
+  }
+
+  #[test]
+  fn dgg_events() {
+    use crate::provider::{channel::ChatModes, IncomingMessage, MessageType};
+
+    // text of the chat line, and whether it is an announcement rather than an information message
+    let message = |events: &Vec<IncomingMessage>, ix: usize| match events.get(ix) {
+      Some(IncomingMessage::PrivMsg { message }) if message.msg_type == MessageType::Information => Some((message.message.to_owned(), false)),
+      Some(IncomingMessage::PrivMsg { message }) if message.msg_type == MessageType::Announcement => Some((message.message.to_owned(), true)),
+      _ => None
+    };
+    let mut modes = ChatModes::default();
+    let mut parse = |frame: &str| {
+      let (command, json) = frame.split_once(' ').unwrap();
+      dgg::parse_event(command, json, &mut modes)
+    };
+
+    let events = parse(r#"BAN {"nick":"Destiny","features":["admin","moderator"],"timestamp":1680000000000,"data":"BobDole"}"#).unwrap();
+    assert!(matches!(events.as_slice(), [IncomingMessage::UserMuted { channel, username }] if channel == dgg::DGG_CHANNEL_NAME && username == "bobdole"));
+
+    let events = parse(r#"UNBAN {"nick":"Destiny","features":["admin"],"timestamp":1680000000000,"data":"BobDole"}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("BobDole was unbanned by Destiny".to_owned(), false)));
+    assert_eq!(events.len(), 1);
+    let events = parse(r#"UNMUTE {"nick":"Bot","features":["bot"],"timestamp":1680000000000,"data":"BobDole"}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("BobDole was unmuted by Bot".to_owned(), false)));
+
+    let events = parse(r#"SUBONLY {"nick":"Destiny","features":["admin"],"timestamp":1680000000000,"data":"on"}"#).unwrap();
+    assert!(matches!(&events[0], IncomingMessage::ChatModes { modes, .. } if modes.subs_only && modes.labels() == vec!["subs-only"]));
+    assert_eq!(message(&events, 1), Some(("Subscriber only mode enabled by Destiny".to_owned(), false)));
+    let events = parse(r#"SUBONLY {"nick":"Destiny","features":["admin"],"timestamp":1680000000000,"data":"off"}"#).unwrap();
+    assert!(matches!(&events[0], IncomingMessage::ChatModes { modes, .. } if !modes.subs_only));
+
+    let events = parse(r#"SUBSCRIPTION {"nick":"Alice","timestamp":1680000000000,"data":"love the stream","tier":2,"tierlabel":"Tier II","streak":3}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("Alice subscribed at Tier II (3 months): love the stream".to_owned(), true)));
+    let events = parse(r#"GIFTSUB {"nick":"Alice","giftee":"BobDole","timestamp":1680000000000,"tier":1}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("Alice gifted a Tier 1 sub to BobDole".to_owned(), true)));
+    let events = parse(r#"MASSGIFT {"nick":"Alice","quantity":5,"timestamp":1680000000000,"tier":1,"tierlabel":"Tier I","data":""}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("Alice gifted 5 Tier I subs".to_owned(), true)));
+    let events = parse(r#"DONATION {"nick":"Alice","amount":505,"timestamp":1680000000000,"data":"hi"}"#).unwrap();
+    assert_eq!(message(&events, 0), Some(("Alice donated $5.05: hi".to_owned(), true)));
+
+    assert!(parse(r#"BAN {"features":[]}"#).is_some_and(|events| events.is_empty()));
+    assert!(parse(r#"REFRESH {"nick":"Bob","features":["subscriber"],"timestamp":1660506127552}"#).is_none());
+  }
+
+  #[test]
+  fn dgg_live_status() {
+    use crate::provider::channel::ChannelStatus;
+
+    let mut status = ChannelStatus::default();
+    assert_eq!(dgg::update_live_status(r#"{"type":"dggApi:bannedPhrases","data":[]}"#, &mut status), None);
+
+    let embeds = r##"{"type":"dggApi:embeds","data":[
+      {"platform":"youtube","id":"abc123","title":"Some Video","channel":"Some Channel","count":3},
+      {"link":"#kick/somestreamer","count":12},
+      {"title":"no id","count":50}
+    ]}"##;
+    assert_eq!(dgg::update_live_status(embeds, &mut status), Some(dgg::LiveUpdate::Embeds));
+    assert_eq!(status.embeds.iter().map(|e| e.url.as_str()).collect::<Vec<_>>(), vec!["https://kick.com/somestreamer", "https://www.youtube.com/watch?v=abc123"]);
+    assert_eq!(status.embeds[1].label(), "Some Video (youtube, 3 watching)");
+    assert_eq!(status.embeds[0].label(), "somestreamer (kick, 12 watching)");
+
+    let stream_info = r#"{"type":"dggApi:streamInfo","data":{"streams":{
+      "youtube":{"live":true,"game":"Just Chatting","preview":null,"status_text":"title on youtube","started_at":"2024-05-01T18:00:00+0000","ended_at":null,"duration":3600,"viewers":5000,"id":"vid123","platform":"youtube","type":"livestream"},
+      "kick":{"live":true,"game":null,"status_text":"title on kick","started_at":"2024-05-01T18:01:00+0000","viewers":1500,"id":"destiny","platform":"kick"},
+      "twitch":{"live":false,"game":null,"status_text":null,"started_at":null,"viewers":null,"id":null},
+      "rumble":null
+    }}}"#;
+    assert_eq!(dgg::update_live_status(stream_info, &mut status), Some(dgg::LiveUpdate::StreamInfo));
+    assert!(status.is_live);
+    assert_eq!((status.title.as_deref(), status.game_name.as_deref(), status.viewer_count), (Some("title on youtube"), Some("Just Chatting"), Some(6500)));
+    assert_eq!(status.started_at.as_deref(), Some("2024-05-01T18:00:00+0000"));
+    assert_eq!(status.platforms.iter().map(|p| (p.platform.as_str(), p.is_live)).collect::<Vec<_>>(), vec![("YouTube", true), ("Kick", true), ("Twitch", false)]);
+    assert_eq!(status.platforms[1].url.as_deref(), Some("https://kick.com/destiny"));
+    assert_eq!(status.platforms[2].url, None);
+    // stream info doesn't clear the embeds
+    assert_eq!(status.embeds.len(), 2);
+
+    let offline = r#"{"type":"dggApi:streamInfo","data":{"streams":{"youtube":{"live":false,"status_text":"old title","viewers":0,"id":"vid123"}}}}"#;
+    assert_eq!(dgg::update_live_status(offline, &mut status), Some(dgg::LiveUpdate::StreamInfo));
+    assert!(!status.is_live);
+    assert_eq!((status.title.as_deref(), status.viewer_count, status.platforms.len()), (Some("old title"), Some(0), 1));
+    assert_eq!(dgg::stream_url("odysee", "xyz"), "https://www.destiny.gg/bigscreen#odysee/xyz");
+  }
+
+  #[test]
+  fn provider_info() {
+    use std::collections::HashSet;
+    use crate::provider::{channel::IrcNetwork, ProviderName};
+
+    let archive_dirs = ProviderName::ALL.iter().map(|p| p.info().archive_dir()).collect::<HashSet<_>>();
+    assert_eq!(archive_dirs.len(), ProviderName::ALL.len());
+    for provider in ProviderName::ALL {
+      assert!(provider.info().init_channel("Test", IrcNetwork { server: "irc.test".to_owned(), ..Default::default() }).provider() == provider);
+    }
+
+    let dgg = ProviderName::DGG.info();
+    assert_eq!(dgg.link_url("#twitch/xqc"), "https://twitch.tv/xqc");
+    assert_eq!(dgg.link_url("https://example.com/a"), "https://example.com/a");
+    assert!(dgg.message_color("> greentext").is_some());
+    assert!(ProviderName::Twitch.info().message_color("> not greentext").is_none());
+
+    let youtube = ProviderName::YouTube.info();
+    assert_eq!(youtube.clean_message("see https://www.youtube.com/redirect?event=live_chat&q=https%3A%2F%2Fexample.com%2F now".to_owned()), "see https://example.com/ now");
+    assert!(youtube.init_pushed_channel("YT:someone").is_some());
+    assert!(ProviderName::Twitch.info().init_pushed_channel("someone").is_none());
+  }
+
+  #[test]
+  fn chat_archive() {
+    use std::io::Write;
+    use tracing_unwrap::ResultExt;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use crate::archive::{self, ArchivedMessage, LogFileQuery};
+    use crate::provider::{ChatMessage, ProviderName};
+
+    let base_path = std::env::temp_dir().join(format!("gigachat-archive-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base_path);
+    let write_log = |provider: &ProviderName, channel: &str, date: NaiveDate, entries: &[ArchivedMessage]| {
+      let path = archive::log_file_path(&base_path, provider, channel, &date);
+      std::fs::create_dir_all(path.parent().unwrap_or_log()).unwrap_or_log();
+      let mut file = std::fs::File::create(&path).unwrap_or_log();
+      for entry in entries {
+        writeln!(file, "{}", serde_json::to_string(entry).unwrap_or_log()).unwrap_or_log();
+      }
+      path
+    };
+    let msg = |channel: &str, id: &str, date: NaiveDate, message: &str| ArchivedMessage::from(&ChatMessage {
+      provider: ProviderName::Twitch,
+      channel: channel.to_owned(),
+      username: "alice".to_owned(),
+      timestamp: Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap_or_log()),
+      message: message.to_owned(),
+      id: id.to_owned(),
+      ..Default::default()
+    });
+
+    // removals are logged as a second entry and fold back into the original
+    let today = Utc::now().date_naive();
+    let yesterday = today - chrono::Duration::days(1);
+    let mut removed = msg("xqc", "2", yesterday, "second");
+    removed.is_removed = Some("<message deleted>".to_owned());
+    let path = write_log(&ProviderName::Twitch, "xqc", yesterday, &[msg("xqc", "1", yesterday, "first"), msg("xqc", "2", yesterday, "second"), removed]);
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap_or_log().write_all(b"{\"provider\":\"Tw").unwrap_or_log();
+    let entries = archive::read_log_file(&path);
+    assert_eq!(entries.iter().map(|m| (m.id.as_str(), m.is_removed.is_some())).collect::<Vec<_>>(), vec![("1", false), ("2", true)]);
+
+    // recent history spans day files, oldest first
+    write_log(&ProviderName::Twitch, "xqc", today, &[msg("xqc", "3", today, "third"), msg("xqc", "4", today, "fourth")]);
+    let ids = |messages: Vec<ArchivedMessage>| messages.into_iter().map(|m| m.id).collect::<Vec<_>>();
+    assert_eq!(ids(archive::load_recent(&base_path, &ProviderName::Twitch, "xqc", 3)), vec!["2", "3", "4"]);
+    assert_eq!(ids(archive::load_recent(&base_path, &ProviderName::Twitch, "xqc", 10)), vec!["1", "2", "3", "4"]);
+    assert!(archive::load_recent(&base_path, &ProviderName::Twitch, "other", 10).is_empty());
+
+    // search reads only the matching channels and dates
+    write_log(&ProviderName::Twitch, "#other", today, &[msg("#other", "5", today, "third wheel")]);
+    let search = |query: LogFileQuery| {
+      let mut found = ids(archive::log_files(&base_path, &query).iter()
+        .flat_map(|path| archive::read_log_file(path))
+        .filter(|m| ChatMessage::from(m.to_owned()).message.contains("third"))
+        .collect());
+      found.sort();
+      found
+    };
+    assert_eq!(search(LogFileQuery { provider: None, channel: None, from: None, to: None }), vec!["3", "5"]);
+    assert_eq!(search(LogFileQuery { provider: Some(ProviderName::Twitch), channel: Some("#other".to_owned()), from: None, to: None }), vec!["5"]);
+    assert!(search(LogFileQuery { provider: Some(ProviderName::DGG), channel: None, from: None, to: None }).is_empty());
+    assert!(search(LogFileQuery { provider: None, channel: None, from: None, to: Some(yesterday) }).is_empty());
+
+    // old day files are pruned, recent ones kept
+    let old = write_log(&ProviderName::Twitch, "xqc", today - chrono::Duration::days(archive::ARCHIVE_RETENTION_DAYS + 1), &[msg("xqc", "0", today, "old")]);
+    archive::prune_old_logs(&base_path, archive::ARCHIVE_RETENTION_DAYS);
+    assert!(!old.exists());
+    assert!(path.exists());
+    assert_eq!(archive::load_recent(&base_path, &ProviderName::Twitch, "xqc", 10).len(), 4);
+
+    let _ = std::fs::remove_dir_all(&base_path);
+  }
+
   /*#[test]
   #[traced_test]
   fn load_emote() {
@@ -4,11 +4,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use egui::Vec2;
-use crate::provider::{twitch::TwitchChatManager, ChatMessage, Provider, ProviderName, ChatManager, channel::Channel};
+use crate::provider::{ChatMessage, ChatProvider, Poll, Provider, ProviderName, channel::Channel};
 
 use crate::emotes::{Emote, EmoteLoader};
+use crate::archive::ChatArchive;
 
 mod template_app;
 
@@ -16,12 +17,20 @@ pub mod addtl_functions;
 pub mod consts;
 pub mod models;
 pub mod chat;
+pub mod commands;
+pub mod highlights;
+pub mod filters;
+pub mod pattern_rule;
 
 mod channel_tabs;
 mod chat_frame;
 mod config_menus;
 mod user_chat_history;
 mod emote_selector;
+mod search;
+mod ignore_list;
+mod sound_settings;
+mod whispers;
 
 use models::*;
 
@@ -64,7 +73,7 @@ pub struct TemplateApp {
   #[cfg_attr(feature = "persistence", serde(skip))]
   show_channel_options: Option<(Vec2, String)>,
   #[cfg_attr(feature = "persistence", serde(skip))]
-  pub twitch_chat_manager: Option<TwitchChatManager>,
+  pub chat_managers: HashMap<ProviderName, Box<dyn ChatProvider>>,
   #[cfg_attr(feature = "persistence", serde(skip))]
   pub show_timestamps_changed: bool,
   #[cfg_attr(feature = "persistence", serde(skip))]
@@ -74,7 +83,70 @@ pub struct TemplateApp {
   #[cfg_attr(feature = "persistence", serde(skip))]
   rhs_tab_width: Option<f32>,
   #[cfg_attr(feature = "persistence", serde(skip))]
-  pub yt_chat_manager: Option<ChatManager>,
+  pub discarded_last_frame: bool,
+  #[cfg_attr(feature = "persistence", serde(default = "default_enable_chat_archive"))]
+  enable_chat_archive: bool,
   #[cfg_attr(feature = "persistence", serde(skip))]
-  pub discarded_last_frame: bool
+  chat_archive: Option<ChatArchive>,
+  /// `enable_yt_integration` as it was when the YouTube provider connected
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  yt_bridge_enabled: bool,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_search_window: bool,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  search_menu: SearchMenu,
+  /// Lowercase usernames whose messages are hidden, per provider
+  ignored_users: HashMap<ProviderName, HashSet<String>>,
+  ignore_mode: IgnoreMode,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_ignore_list: bool,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  ignore_list_menu: IgnoreListMenu,
+  highlight_rules: Vec<highlights::HighlightRule>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_highlights_window: bool,
+  message_filters: Vec<filters::MessageFilter>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_filters_window: bool,
+  /// Channels showing filtered messages anyway
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_filtered_channels: HashSet<String>,
+  /// Messages hidden or collapsed by a filter in each channel's history, kept up to date by `push_history`
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  hidden_message_counts: HashMap<String, usize>,
+  /// Desktop notifications for mentions and highlights while the window is unfocused
+  enable_notifications: bool,
+  notify_on_live: bool,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  notifications: crate::notifications::Notifications,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  window_focused: bool,
+  sound_settings: crate::sounds::SoundSettings,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  sound_player: crate::sounds::SoundPlayer,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_sound_settings: bool,
+  /// Channels that went live since their tab was last selected
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  went_live_channels: HashSet<String>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  unread: HashMap<String, UnreadCounter>,
+  /// Channels shown in a chat panel as of the last frame
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  viewed_channels: Vec<String>,
+  /// Most recently active first
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  whispers: Vec<WhisperConversation>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  show_whispers_window: bool,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  whisper_menu: WhisperMenu,
+  /// Poll running in each channel, removed once it ends or is dismissed
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  polls: HashMap<String, Poll>
+}
+
+#[cfg(feature = "persistence")]
+fn default_enable_chat_archive() -> bool {
+  true
 }
\ No newline at end of file
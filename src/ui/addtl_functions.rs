@@ -4,17 +4,18 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use tracing::info;
 use tracing_unwrap::OptionExt;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use egui::{emath::Rect, epaint::FontId, TextStyle, TextureHandle};
 use egui::{Vec2, FontDefinitions, FontData, text::LayoutJob, FontFamily, Color32};
 use itertools::Itertools;
 use crate::{
-    emotes::{Emote, OverlayItem}, provider::{channel::{Channel, ChannelUser}, dgg, ChatMessage, ComboCounter, Provider, ProviderName
+    emotes::{Emote, OverlayItem}, provider::{channel::{Channel, ChannelUser}, dgg, ChatMessage, ChatProvider, ComboCounter, MessageType, ModerationAction, Provider, ProviderName
     }};
 use crate::emotes::imaging::load_file_into_buffer;
 
-use super::{consts::MIN_LINE_HEIGHT, SelectorFormat, TemplateApp, UiChatMessage, UiChatMessageRow};
+use super::{consts::{MIN_LINE_HEIGHT, TIMEOUT_PRESETS}, filters::{get_filter_action, MessageFilter}, MessageAction, SelectorFormat, TemplateApp, UiChatMessage, UiChatMessageRow};
 
 pub fn update_font_sizes(r: &TemplateApp, ctx: &egui::Context) {
     let mut styles = egui::Style::default();
@@ -42,11 +43,11 @@ impl eframe::App for TemplateApp {
 
   fn on_exit(&mut self, _ctx : Option<&eframe::glow::Context>) {
     self.emote_loader.close();
-    if let Some(chat_mgr) = self.twitch_chat_manager.as_mut() {
+    for chat_mgr in self.chat_managers.values_mut() {
       chat_mgr.close();
     }
-    for (_, channel) in self.channels.iter_mut() {
-      channel.close();
+    if let Some(archive) = self.chat_archive.as_ref() {
+      archive.close();
     }
   }
 
@@ -197,23 +198,47 @@ pub fn create_uichatmessage<'a,'b>(
   }
 }
 
-pub fn set_selected_message(set_selected_msg: Option<ChatMessage>, ui: &egui::Ui, selected_msg: &mut Option<(Vec2, ChatMessage)>) {
+pub fn set_selected_message(set_selected_msg: Option<ChatMessage>, ui: &egui::Ui, selected_msg: &mut Option<(Vec2, ChatMessage)>, channels: &HashMap<String, Channel>) -> Option<MessageAction> {
     let mut area = Rect::NOTHING;
     let mut clicked = false;
+    let mut action = None;
     if let Some(x) = set_selected_msg.as_ref() {
       let pos = ui.ctx().pointer_hover_pos().unwrap_or_log().to_vec2();
       *selected_msg = Some((Vec2 { x: pos.x, y: pos.y - ui.clip_rect().min.y}, x.to_owned()));
     }
     if let Some((pos, msg)) = selected_msg.as_ref() {
-      (area, clicked) = msg_context_menu(ui, pos, msg);
+      let is_moderator = channels.get(&msg.channel).and_then(|c| c.transient()).is_some_and(|t| t.is_moderator);
+      (area, clicked, action) = msg_context_menu(ui, pos, msg, is_moderator);
     }
     if clicked || set_selected_msg.is_none() && ui.input(|i| i.pointer.any_click()) && ui.ctx().pointer_interact_pos().is_some() && !area.contains(ui.ctx().pointer_interact_pos().unwrap_or_log()) {
       *selected_msg = None;
     }
+    action
 }
 
-pub fn msg_context_menu(ui: &egui::Ui, point: &Vec2, msg: &ChatMessage) -> (Rect, bool) {
+/// Applies a context menu action to the chat panel or ignore list, or sends it to the message's provider
+pub fn handle_message_action(action: MessageAction, reply_to: &mut Option<ChatMessage>, chat_managers: &mut HashMap<ProviderName, Box<dyn ChatProvider>>, ignored_users: &mut HashMap<ProviderName, HashSet<String>>) {
+  match action {
+    MessageAction::Reply(msg) => *reply_to = Some(msg),
+    MessageAction::Moderate { provider, channel, action } => {
+      if let Some(chat_mgr) = chat_managers.get_mut(&provider) && let Err(e) = chat_mgr.send_moderation(&channel, action) {
+        info!("Failed to send moderation action: {}", e);
+      }
+    },
+    MessageAction::Ignore { provider, username } => {
+      ignored_users.entry(provider).or_default().insert(username.to_lowercase());
+    }
+  }
+}
+
+pub fn is_user_ignored(ignored_users: &HashMap<ProviderName, HashSet<String>>, msg: &ChatMessage) -> bool {
+  msg.msg_type == MessageType::Chat && ignored_users.get(&msg.provider).is_some_and(|users| users.contains(&msg.username.to_lowercase()))
+}
+
+/// Moderation actions are only offered when `is_moderator` is set for the message's channel
+pub fn msg_context_menu(ui: &egui::Ui, point: &Vec2, msg: &ChatMessage, is_moderator: bool) -> (Rect, bool, Option<MessageAction>) {
   let mut clicked = false;
+  let mut action = None;
   let window = egui::Window::new("ContextMenu")
   .anchor(egui::Align2::LEFT_TOP, point.to_owned())
   .title_bar(false)
@@ -227,14 +252,70 @@ pub fn msg_context_menu(ui: &egui::Ui, point: &Vec2, msg: &ChatMessage) -> (Rect
         ui.output_mut(|o| o.copied_text.clone_from(&msg.message));
         clicked = true;
       }
+      let info = msg.provider.info();
+      if info.supports_replies() && msg.msg_type == MessageType::Chat && ui.button("Reply").clicked() {
+        action = Some(MessageAction::Reply(msg.to_owned()));
+        clicked = true;
+      }
+      if msg.msg_type == MessageType::Chat && !msg.username.is_empty() && ui.button("Ignore User").on_hover_text("Hide messages from this user. Manage ignored users under Options.").clicked() {
+        action = Some(MessageAction::Ignore { provider: msg.provider.to_owned(), username: msg.username.to_owned() });
+        clicked = true;
+      }
+      if is_moderator && info.supports_moderation() && msg.msg_type == MessageType::Chat && !msg.username.is_empty() {
+        let moderate = |action: ModerationAction| Some(MessageAction::Moderate { provider: msg.provider.to_owned(), channel: msg.channel.to_owned(), action });
+        ui.separator();
+        ui.horizontal(|ui| {
+          ui.label("Timeout");
+          for (label, duration_sec) in TIMEOUT_PRESETS {
+            if ui.small_button(label).clicked() {
+              action = moderate(ModerationAction::Timeout { username: msg.username.to_owned(), duration_sec });
+              clicked = true;
+            }
+          }
+        });
+        // bans are permanent, so ask again before sending one
+        let confirm_ban_id = egui::Id::new("confirm_ban").with(&msg.id);
+        if ui.data(|d| d.get_temp::<bool>(confirm_ban_id)).unwrap_or(false) {
+          ui.horizontal(|ui| {
+            ui.label(format!("Ban {}?", msg.username));
+            if ui.button("Confirm").clicked() {
+              action = moderate(ModerationAction::Ban { username: msg.username.to_owned() });
+              clicked = true;
+            }
+            if clicked || ui.button("Cancel").clicked() {
+              ui.data_mut(|d| d.remove::<bool>(confirm_ban_id));
+            }
+          });
+        }
+        else if ui.button("Ban").clicked() {
+          ui.data_mut(|d| d.insert_temp(confirm_ban_id, true));
+        }
+        if ui.button("Unban").clicked() {
+          action = moderate(ModerationAction::Unban { username: msg.username.to_owned() });
+          clicked = true;
+        }
+        if info.supports_message_deletion() && ui.button("Delete Message").clicked() {
+          action = moderate(ModerationAction::DeleteMessage { id: msg.id.to_owned(), username: msg.username.to_owned() });
+          clicked = true;
+        }
+      }
     });
   });
-  (window.unwrap_or_log().response.rect, clicked)
+  (window.unwrap_or_log().response.rect, clicked, action)
 }
 
-pub fn push_history(chat_history: &mut VecDeque<(ChatMessage, Option<f32>)>, mut message: ChatMessage, provider_emotes: Option<&HashMap<String, Emote>>, channel_emotes: Option<&HashMap<String, Emote>>, global_emotes: &HashMap<String, Emote>) {
+pub fn push_history(chat_history: &mut VecDeque<(ChatMessage, Option<f32>)>, mut message: ChatMessage, provider_emotes: Option<&HashMap<String, Emote>>, channel_emotes: Option<&HashMap<String, Emote>>, global_emotes: &HashMap<String, Emote>, ignored_users: &HashMap<ProviderName, HashSet<String>>, filters: &[MessageFilter], hidden_count: &mut usize) {
+  message.filtered = get_filter_action(filters, &message);
+  if message.filtered.is_some_and(|action| action.hides_message()) {
+    *hidden_count += 1;
+  }
+  // ignored users and filtered messages never start or continue a combo
+  if message.filtered.is_some() || is_user_ignored(ignored_users, &message) {
+    chat_history.push_back((message, None));
+    return;
+  }
   let is_emote = !get_emotes_for_message(&message, provider_emotes, channel_emotes, global_emotes).is_empty();
-  let last = chat_history.iter_mut().rev().find_or_first(|f| f.0.channel == message.channel);
+  let last = chat_history.iter_mut().rev().filter(|f| f.0.filtered.is_none() && !is_user_ignored(ignored_users, &f.0)).find_or_first(|f| f.0.channel == message.channel);
   if let Some(last) = last && is_emote {
     let combo = combo_calculator(&message, last.0.combo_data.as_ref());
     if combo.as_ref().is_some_and(|c| !c.is_new && c.count > 1) && let Some(last_combo) = last.0.combo_data.as_mut() {
@@ -295,15 +376,11 @@ pub fn get_emotes_for_message<'a>(row: &ChatMessage, provider_emotes: Option<&'a
     if let Some(channel_emotes) = channel_emotes && let Some(emote) = channel_emotes.get(word) {
       Some(emote)
     }
-    else if row.provider != ProviderName::DGG && let Some(emote) = global_emotes.get(word) {
+    else if row.provider.info().uses_global_emotes() && let Some(emote) = global_emotes.get(word) {
       Some(emote)
     }
     else if let Some(provider_emotes) = provider_emotes && let Some(emote) = provider_emotes.get(word) {
-      match row.provider {
-        ProviderName::Twitch => Some(emote),
-        ProviderName::YouTube => Some(emote),
-        _ => None
-      }
+      Some(emote)
     }
     else {
       None
@@ -419,12 +496,7 @@ pub fn mentioned_in_message(usernames: &HashMap<ProviderName, String>, provider:
 }
 
 pub fn get_provider_color(provider : &ProviderName) -> Color32 {
-    match provider {
-      //ProviderName::Twitch => Color32::from_rgba_unmultiplied(145, 71, 255, 255),
-      ProviderName::Twitch => Color32::from_rgba_unmultiplied(169, 112, 255, 255),
-      ProviderName::YouTube => Color32::from_rgba_unmultiplied(255, 78, 69, 255),
-      ProviderName::DGG => Color32::from_rgba_unmultiplied(83, 140, 198, 255),
-    }
+    provider.info().color()
   }
 
 pub fn convert_color(input : &(u8, u8, u8)) -> Color32 {
@@ -8,12 +8,9 @@ use itertools::Itertools;
 use tracing::debug;
 use chrono::{DateTime, Utc};
 use egui::text::LayoutJob;
-use egui::{Align, Color32, Response, RichText, TextStyle, Ui};
+use egui::{Align, Color32, OpenUrl, Response, RichText, TextStyle, Ui};
 use tracing_unwrap::OptionExt;
 
-use crate::provider::channel::{Channel, ChannelTransient};
-use crate::provider::dgg;
-
 use super::TemplateApp;
 use super::addtl_functions::*;
 use super::models::*;
@@ -38,26 +35,9 @@ pub fn render_channel_tab_component(&mut self, ui: &mut Ui, ctx: &egui::Context)
     
           let mut tabs : Vec<(String, Response)> = Default::default();
           for channel in self.channel_tab_list.to_owned().iter() {
-            if let Some(sco) = self.channels.get_mut(channel) && sco.transient().is_none() {
+            if self.channels.get(channel).is_some_and(|sco| sco.transient().is_none()) {
                 debug!("Channel not opened yet, attempting to open: {}", channel);
-                match sco {
-                  Channel::Twitch { twitch, ref mut shared } => if let Some(chat_mgr) = self.twitch_chat_manager.as_mut() { 
-                    //chat_mgr.open_channel(shared, Some(twitch.room_id.to_owned())); 
-                    chat_mgr.open_channel(twitch, shared);
-                  },
-                  Channel::DGG { dgg, shared } => {
-                    if let Some(chat_mgr) = dgg.dgg_chat_manager.as_mut() {
-                      chat_mgr.close();
-                    }
-                    dgg.dgg_chat_manager = Some(dgg::open_channel(&self.auth_tokens.dgg_username, &self.auth_tokens.dgg_auth_token, dgg, shared, self.runtime.as_ref().unwrap_or_log(), &self.emote_loader, ctx));
-                  },
-                  Channel::Youtube { youtube: _, shared } => {
-                    shared.transient = Some(ChannelTransient { 
-                      channel_emotes: None,
-                      badge_emotes: None,
-                      status: None });
-                  }
-                }
+                self.open_channel(channel, ctx);
               }
 
             let show_channel = self.rhs_selected_channel.as_ref() != Some(channel) && (
@@ -120,12 +100,19 @@ fn ui_channel_tab(&mut self, channel: &String, ui: &mut egui::Ui, ctx: &egui::Co
 
     let mut channel_removed : Option<String> = None;
 
+    let hidden_count = if self.show_filtered_channels.contains(channel) { 0 } else {
+      self.hidden_message_counts.get(channel).copied().unwrap_or_default()
+    };
+
+    if self.selected_channel.as_ref() == Some(channel) || self.rhs_selected_channel.as_ref() == Some(channel) {
+      self.went_live_channels.remove(channel);
+    }
+    let went_live = self.went_live_channels.contains(channel);
+    let unread = self.unread.get(channel).filter(|u| u.messages > 0 && !self.viewed_channels.contains(channel)).map(|u| (u.messages, u.mentions, u.label()));
+    let (unread_messages, unread_mentions) = unread.as_ref().map(|(messages, mentions, _)| (*messages, *mentions)).unwrap_or_default();
+
     if let Some(sco) = self.channels.get_mut(channel) {
-      let provider = match sco {
-        Channel::Twitch { twitch: _, shared: _ } => "Twitch",
-        Channel::DGG { dgg: _, shared: _ } => "DGG Chat",
-        Channel::Youtube { youtube: _, shared: _ } => "YouTube"
-      };
+      let provider = sco.provider().display_name();
       let shared = sco.shared_mut();
       if let Some(t) = shared.transient.as_ref() {            
         let mut job = LayoutJob { ..Default::default() };
@@ -150,6 +137,38 @@ fn ui_channel_tab(&mut self, channel: &String, ui: &mut egui::Ui, ctx: &egui::Co
             ..Default::default()
           });
         }
+        if unread_mentions > 0 {
+          job.append(&format!("@{}", badge_count(unread_mentions)), 3., egui::TextFormat {
+            font_id: get_text_style(TextStyle::Small, ctx),
+            color: Color32::from_rgb(255, 130, 40),
+            valign: Align::BOTTOM,
+            ..Default::default()
+          });
+        }
+        if unread_messages > 0 {
+          job.append(&badge_count(unread_messages), 3., egui::TextFormat {
+            font_id: get_text_style(TextStyle::Small, ctx),
+            color: Color32::WHITE,
+            valign: Align::BOTTOM,
+            ..Default::default()
+          });
+        }
+        if went_live {
+          job.append("NEW", 3., egui::TextFormat {
+            font_id: get_text_style(TextStyle::Small, ctx),
+            color: Color32::GOLD,
+            valign: Align::BOTTOM,
+            ..Default::default()
+          });
+        }
+        if hidden_count > 0 {
+          job.append(&format!("⊘{hidden_count}"), 3., egui::TextFormat {
+            font_id: get_text_style(TextStyle::Small, ctx),
+            color: Color32::GRAY,
+            valign: Align::BOTTOM,
+            ..Default::default()
+          });
+        }
 
         let clblx = crate::mod_selected_label::SelectableLabel::new(self.selected_channel == Some(channel.to_owned()), job);
         //let clblx = egui::SelectableLabel::new(self.selected_channel == Some(channel.to_owned()), job);
@@ -189,6 +208,12 @@ fn ui_channel_tab(&mut self, channel: &String, ui: &mut egui::Ui, ctx: &egui::Co
               if let Some(viewers) = status.viewer_count.as_ref() {
                 ui.label(format!("{viewers} viewers"));
               }
+              // e.g. DGG streaming to YouTube and Kick at the same time
+              if status.platforms.iter().filter(|p| p.is_live).count() > 1 {
+                for platform in status.platforms.iter().filter(|p| p.is_live) {
+                  ui.label(format!("{}: {} viewers", platform.platform, platform.viewer_count.unwrap_or_default()));
+                }
+              }
           
               if let Some(started_at) = status.started_at.as_ref() { 
                 if let Ok(dt) = DateTime::parse_from_rfc3339(started_at) {
@@ -206,8 +231,28 @@ fn ui_channel_tab(&mut self, channel: &String, ui: &mut egui::Ui, ctx: &egui::Co
             else {
               ui.label(format!("{channel} ({provider})"));
             }
+            if let Some((_, _, label)) = unread.as_ref() {
+              ui.label(label);
+            }
+            if went_live {
+              ui.label("Went live since you last looked at this channel");
+            }
+            if hidden_count > 0 {
+              ui.label(format!("{hidden_count} filtered messages, right click to show them"));
+            }
           });
         //}
+
+        if let Some(status) = t.status.as_ref() && !status.embeds.is_empty() {
+          ui.menu_button(RichText::new(format!("📺{}", status.embeds.len())).text_style(TextStyle::Small), |ui| {
+            for embed in &status.embeds {
+              if ui.button(embed.label()).on_hover_text(&embed.url).clicked() {
+                ctx.open_url(OpenUrl::new_tab(&embed.url));
+                ui.close_menu();
+              }
+            }
+          }).response.on_hover_text("Streams chat is watching");
+        }
         
         return ChannelTabResponse {
             response: Some(clbl),
@@ -226,4 +271,9 @@ fn ui_channel_tab(&mut self, channel: &String, ui: &mut egui::Ui, ctx: &egui::Co
 struct ChannelTabResponse {
     response: Option<Response>,
     channel_removed: Option<String>
-}
\ No newline at end of file
+}
+
+/// Unread count shown on a tab
+fn badge_count(count: usize) -> String {
+  if count > 99 { "99+".to_owned() } else { count.to_string() }
+}
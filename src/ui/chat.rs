@@ -15,7 +15,7 @@ use itertools::Itertools;
 use tracing::warn;
 
 use crate::provider::ChatMessage;
-use crate::{emotes::*, provider::{ProviderName, MessageType}};
+use crate::{emotes::*, provider::MessageType};
 
 use super::addtl_functions::{convert_color, get_body_text_style, get_text_style};
 use super::UiChatMessage;
@@ -79,8 +79,8 @@ pub fn display_chat_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, highlig
 	if chat_msg.message.is_removed.is_some() {
 		message_color =  (180, 180, 180);
 	}
-	else if chat_msg.message.provider == ProviderName::DGG && chat_msg.message.message.starts_with('>') {
-		message_color =  (99, 151, 37);
+	else if let Some(color) = chat_msg.message.provider.info().message_color(&chat_msg.message.message) {
+		message_color = color;
 	}
 
 	let mut msg_right_clicked = false;
@@ -111,6 +111,15 @@ pub fn display_chat_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, highlig
 			highlight_ui_row(ui, highlight);
 			}
 
+			if chat_msg.message.from_archive {
+			ui.set_opacity(ARCHIVED_MESSAGE_OPACITY);
+			}
+
+			if row_ix == 0 && let Some(reply_to) = chat_msg.message.reply_to.as_ref() {
+			ui.add(egui::Label::new(RichText::new(format!("↪ replying to @{}: {}", reply_to.username, reply_to.message)).small().color(Color32::GRAY)).truncate());
+			ui.end_row();
+			}
+
 			if row_ix == 0 {
 			let username = determine_name_to_display(chat_msg.message);
 			let job = get_chat_msg_header_layoutjob(true, ui, chat_msg.channel_display_info(), chat_msg.username_display(), chat_msg.timestamp());
@@ -122,27 +131,9 @@ pub fn display_chat_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, highlig
 					let resp = ui.image(ImageSource::Texture(SizedTexture::new(tex.id(), egui::vec2(tex.size_vec2().x * (BADGE_HEIGHT / tex.size_vec2().y), BADGE_HEIGHT))));
 					if interactable {
 					resp.on_hover_ui(|ui| {
-						match chat_msg.message.provider {
-							ProviderName::Twitch => {
-							let parts = emote.name.split('/').collect_tuple::<(&str, &str)>().unwrap_or(("",""));
-							match parts.0 {
-								"subscriber" => {
-								let num = parts.1.parse::<usize>().unwrap_or(0);
-								let tier = match num / 1000 {
-									3 => "T3",
-									2 => "T2",
-									_ => "T1",
-								};
-								ui.label(format!("{} Month Sub ({})", num % 1000, tier))
-								}, 
-								"sub-gifter" => ui.label(format!("{}\nGift Subs", parts.1)),
-								"bits" => ui.label(format!("{} Bits", parts.1)),
-								_ => ui.label(parts.0)
-							};
-							},
-							ProviderName::DGG => { ui.label(emote.display_name.as_ref().unwrap_or(&emote.name.to_owned())); },
-							ProviderName::YouTube => {}
-						};
+						if let Some(tooltip) = chat_msg.message.provider.info().badge_tooltip(emote) {
+							ui.label(tooltip);
+						}
 	
 						ui.image(ImageSource::Texture(SizedTexture::new(tex.id(), tex.size_vec2())));
 					});
@@ -222,15 +213,7 @@ pub fn display_chat_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, highlig
 						if link.clicked() {
 						let modifiers = ui.ctx().input(|i| i.modifiers);
 
-						let url = if chat_msg.message.provider == ProviderName::DGG && let Some((prefix, suffix)) = url.split('/').collect_tuple() {
-							match prefix {
-							"#twitch" => format!("https://twitch.tv/{suffix}"),
-							"#youtube" => format!("https://www.youtube.com/watch?v={suffix}"),
-							_ => url.to_string()
-							}
-						} else {
-							url.to_string()
-						};
+						let url = chat_msg.message.provider.info().link_url(url);
 
 						ui.ctx().output_mut(|o| o.open_url = Some(egui::output::OpenUrl {
 							url,
@@ -275,6 +258,33 @@ pub fn display_chat_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, highlig
 	(height, user_selected, msg_right_clicked)
 }
 
+/// One line placeholder for a message from an ignored user, the message text is shown on hover
+pub fn display_ignored_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage) -> f32 {
+	let username = determine_name_to_display(chat_msg.message).unwrap_or(&chat_msg.message.username);
+	display_collapsed_message(ui, chat_msg, format!("<message from ignored user {username}>"))
+}
+
+pub fn display_filtered_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage) -> f32 {
+	let username = determine_name_to_display(chat_msg.message).unwrap_or(&chat_msg.message.username);
+	display_collapsed_message(ui, chat_msg, format!("<filtered message from {username}>"))
+}
+
+fn display_collapsed_message(ui: &mut egui::Ui, chat_msg: &UiChatMessage, label: String) -> f32 {
+	let resp = ui.add(egui::Label::new(RichText::new(label).small().color(Color32::DARK_GRAY)).sense(egui::Sense::hover()));
+	let height = resp.rect.height();
+	resp.on_hover_text(&chat_msg.message.message);
+	height
+}
+
+/// Paint over an already rendered message, unless the pointer is over it
+pub fn cover_message(ui: &egui::Ui, rect: egui::Rect) {
+	if ui.rect_contains_pointer(rect) {
+		return;
+	}
+	ui.painter().rect_filled(rect, Rounding::same(2.), Color32::from_gray(32));
+	ui.painter().text(rect.left_center() + Vec2::new(4., 0.), Align2::LEFT_CENTER, "filtered message, hover to show", get_text_style(TextStyle::Small, ui.ctx()), Color32::GRAY);
+}
+
 pub fn determine_name_to_display(chat_msg: &ChatMessage) -> Option<&String> {
   match &chat_msg.profile.display_name {
 	_ if chat_msg.msg_type != MessageType::Chat => None,
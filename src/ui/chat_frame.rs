@@ -18,14 +18,15 @@ use tracing::error;
 use tracing::warn;
 use tracing_unwrap::OptionExt;
 
-use crate::provider::channel::Channel;
 use crate::provider::ChatMessage;
+use crate::provider::FilterAction;
 use crate::provider::MessageType;
 use crate::provider::ProviderName;
 
 use super::addtl_functions::*;
 use super::chat;
 use super::emote_selector::TextboxAndEmoteSelectorResponse;
+use super::highlights::get_highlight;
 use super::TemplateApp;
 use super::consts::*;
 use super::models::*;
@@ -63,10 +64,11 @@ impl TemplateApp {
             let mut overlay_viewport : Rect = Rect::NOTHING;
             let mut y_size = 0.;
             let mut y_size_new = 0.;
+            let mut scroll_target_y : Option<f32> = None;
             let area = chat_area.show_viewport(ui, |ui, viewport| {  
                 ui.with_layout(egui::Layout::top_down(Align::LEFT), |ui| {
                     overlay_viewport = viewport;
-                    (y_size, y_size_new) = self.show_variable_height_rows(&mut chat_panel, ui, viewport);
+                    (y_size, y_size_new, scroll_target_y) = self.show_variable_height_rows(&mut chat_panel, ui, viewport);
                 });
             });
 
@@ -85,6 +87,14 @@ impl TemplateApp {
                 chat_panel.chat_scroll = Some(area.state.offset);
             }
 
+            // jump to a message selected elsewhere (e.g. search results), centered in the view.
+            // the target is kept until its row has been laid out.
+            if chat_panel.scroll_to_message.is_some() && let Some(target_y) = scroll_target_y {
+                chat_panel.scroll_to_message = None;
+                chat_panel.chat_scroll_lock_to_bottom = false;
+                chat_panel.chat_scroll = Some(Vec2::new(0., (target_y - area.inner_rect.height() / 2.).clamp(0., max_scroll_offset.max(0.))));
+            }
+
             let jump_rect = if area.content_size.y - (area.state.offset.y + y_size_new + area.inner_rect.height()) > 8. {
                 let rect = Rect {
                     min: Pos2 { x: area.inner_rect.max.x - 60., y: area.inner_rect.max.y - 70. },
@@ -114,6 +124,90 @@ impl TemplateApp {
             } else { 
                 Rect::NOTHING 
             };
+
+            // messages received while the channel was not viewed
+            let unread_rect = if let Some(channel) = chat_panel.selected_channel.as_ref()
+            && let Some(unread) = self.unread.get(channel)
+            && unread.messages > 0 {
+                let (mut jump_to_unread, mut mark_all_read) = (false, false);
+                let unreadwin = egui::Window::new(format!("Unread {id}"))
+                .fixed_pos(area.inner_rect.min)
+                .title_bar(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(unread.label());
+                        if let Some(first_unread) = unread.first_unread.as_ref() && ui.button("Jump to first unread").clicked() {
+                            chat_panel.scroll_to_message = Some(first_unread.to_owned());
+                            chat_panel.highlighted_message = Some(first_unread.to_owned());
+                            jump_to_unread = true;
+                        }
+                        if ui.button("Mark all read").clicked() {
+                            mark_all_read = true;
+                        }
+                    });
+                });
+                if mark_all_read {
+                    self.unread.clear();
+                } else if jump_to_unread {
+                    self.unread.remove(channel);
+                }
+                unreadwin.unwrap_or_log().response.rect
+            } else {
+                Rect::NOTHING
+            };
+
+            // poll running in the channel, below the unread counter if both are shown
+            let poll_rect = if let Some(channel) = chat_panel.selected_channel.to_owned()
+            && let Some(poll) = self.polls.get(&channel) {
+                let seconds_left = poll.seconds_left(chrono::Utc::now());
+                let (mut vote, mut dismiss) = (None, false);
+                let pos = if unread_rect == Rect::NOTHING { area.inner_rect.min } else { Pos2::new(area.inner_rect.min.x, unread_rect.max.y) };
+                let pollwin = egui::Window::new(format!("Poll {id}"))
+                .fixed_pos(pos)
+                .title_bar(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.set_max_width(area.inner_rect.width() * 0.6);
+                    ui.horizontal(|ui| {
+                        ui.strong(&poll.question);
+                        if ui.small_button("✖").on_hover_text("Hide poll").clicked() {
+                            dismiss = true;
+                        }
+                    });
+                    let status = match seconds_left {
+                        0 => "Ended".to_owned(),
+                        sec => format!("{sec}s left")
+                    };
+                    ui.label(RichText::new(format!("{} · {} votes{} · started by {}", status, poll.total_votes(), if poll.weighted { " (weighted)" } else { "" }, poll.started_by)).small().weak());
+                    let total = poll.total_votes().max(1) as f32;
+                    for (ix, (option, votes)) in poll.options.iter().zip(poll.totals.iter()).enumerate() {
+                        let can_vote = poll.can_vote && poll.my_vote.is_none() && seconds_left > 0;
+                        let text = if poll.my_vote == Some(ix) { format!("✔ {option}") } else { option.to_owned() };
+                        let resp = ui.add(egui::ProgressBar::new(*votes as f32 / total).text(format!("{text}: {votes} ({:.0}%)", *votes as f32 / total * 100.)))
+                            .interact(egui::Sense::click());
+                        if can_vote && resp.on_hover_text("Click to vote").clicked() {
+                            vote = Some(ix);
+                        }
+                    }
+                });
+                // the vote is only shown as cast once the provider confirms it
+                if let Some(option) = vote {
+                    let provider = self.channels.get(&channel).map(|c| c.provider());
+                    if let Some(chat_mgr) = provider.and_then(|p| self.chat_managers.get_mut(&p))
+                    && let Err(e) = chat_mgr.send_vote(&channel, option) {
+                        error!("Failed to send vote: {e}");
+                    }
+                }
+                if dismiss {
+                    self.polls.remove(&channel);
+                }
+                // keep the countdown moving
+                ctx.request_repaint_after(std::time::Duration::from_secs(1));
+                pollwin.unwrap_or_log().response.rect
+            } else {
+                Rect::NOTHING
+            };
             
             response.y_size = y_size;
             
@@ -126,7 +220,9 @@ impl TemplateApp {
             && let Some(pos) = ctx.input(|i| i.pointer.interact_pos())
             && area.inner_rect.contains(pos) 
             && !history_rect.contains(pos)
-            && !jump_rect.contains(pos) {
+            && !jump_rect.contains(pos)
+            && !unread_rect.contains(pos)
+            && !poll_rect.contains(pos) {
                 chat_panel.selected_user = None;
             }
         });
@@ -135,7 +231,7 @@ impl TemplateApp {
         response
     }
     
-    pub fn show_variable_height_rows(&mut self, chat_panel: &mut ChatPanelOptions, ui: &mut egui::Ui, viewport: Rect) -> (f32, f32) {
+    pub fn show_variable_height_rows(&mut self, chat_panel: &mut ChatPanelOptions, ui: &mut egui::Ui, viewport: Rect) -> (f32, f32, Option<f32>) {
         let TemplateApp {
             chat_history_limit: _,
             body_text_size: _,
@@ -160,15 +256,43 @@ impl TemplateApp {
             emote_loader,
             show_auth_ui : _,
             show_channel_options : _,
-            twitch_chat_manager : _,
+            chat_managers,
             show_timestamps_changed,
             dragged_channel_tab : _,
             rhs_tab_width: _,
-            yt_chat_manager: _,
             enable_yt_integration: _,
             last_frame_ui_events: _,
             force_compact_emote_selector: _,
-            discarded_last_frame: _
+            discarded_last_frame: _,
+            enable_chat_archive: _,
+            yt_bridge_enabled: _,
+            chat_archive: _,
+            show_search_window: _,
+            search_menu: _,
+            ignored_users,
+            ignore_mode,
+            show_ignore_list: _,
+            ignore_list_menu: _,
+            highlight_rules,
+            show_highlights_window: _,
+            message_filters: _,
+            show_filters_window: _,
+            show_filtered_channels,
+            hidden_message_counts: _,
+            enable_notifications: _,
+            notify_on_live: _,
+            notifications: _,
+            window_focused: _,
+            sound_settings: _,
+            sound_player: _,
+            show_sound_settings: _,
+            went_live_channels: _,
+            unread: _,
+            viewed_channels: _,
+            whispers: _,
+            show_whispers_window: _,
+            whisper_menu: _,
+            polls: _
         } = self;
         
         let ChatPanelOptions {
@@ -180,7 +304,10 @@ impl TemplateApp {
             selected_user,
             selected_msg,
             selected_emote: _,
-            selected_emote_input: _
+            selected_emote_input: _,
+            scroll_to_message,
+            highlighted_message,
+            reply_to
         } = chat_panel;
         
         let mut y_pos = 0.0;
@@ -188,6 +315,7 @@ impl TemplateApp {
         let mut y_pos_visible = 0.0;
         let mut y_size_from_new_messages = 0.0;
         let mut set_selected_msg : Option<ChatMessage> = None;
+        let mut scroll_target_y : Option<f32> = None;
         let mut skipped_rows = 0;
         
         let mut _visible_rows: usize = 0;
@@ -208,12 +336,9 @@ impl TemplateApp {
         
         let mut usernames : HashMap<ProviderName, String> = HashMap::default();
         if selected_channel.is_none() {
-            if let Some(twitch_chat_manager) = self.twitch_chat_manager.as_ref() {
-                usernames.insert(ProviderName::Twitch, twitch_chat_manager.username.to_lowercase());
-            }
-            for (_, channel) in channels.iter_mut() {
-                if let Channel::DGG { ref mut dgg, shared: _ } = channel && let Some(chat_mgr) = dgg.dgg_chat_manager.as_ref() {
-                    usernames.insert(ProviderName::DGG, chat_mgr.username.to_lowercase()); 
+            for (provider, chat_mgr) in chat_managers.iter() {
+                if chat_mgr.is_logged_in() {
+                    usernames.insert(provider.to_owned(), chat_mgr.username().to_lowercase());
                 }
             }
         }
@@ -240,9 +365,26 @@ impl TemplateApp {
         //let mut last_row;
         
         while let Some((row, cached_y)) = history_iters.get_next() {
-            if selected_channel.is_none() && !mentioned_in_message(&usernames, &row.provider, &row.message) {
+            let highlight = get_highlight(highlight_rules, row);
+            if selected_channel.is_none() && !mentioned_in_message(&usernames, &row.provider, &row.message) && !highlight.is_some_and(|h| h.show_in_mentions) {
                 continue;
             }
+            let highlight_color = highlight.map(|h| h.color);
+
+            // messages received before the user was ignored are still in the history
+            let is_ignored = is_user_ignored(ignored_users, row);
+            if is_ignored && (*ignore_mode == IgnoreMode::Hide || selected_channel.is_none()) {
+                continue;
+            }
+
+            let filtered = row.filtered.filter(|_| !show_filtered_channels.contains(&row.channel));
+            if filtered == Some(FilterAction::Hide) || filtered == Some(FilterAction::Collapse) && selected_channel.is_none() {
+                continue;
+            }
+
+            if scroll_to_message.as_ref().is_some_and(|m| m.matches(row)) {
+                scroll_target_y = Some(y_pos);
+            }
             
             // amount to render above and below the viewport area
             let overdraw_height = viewport.height() / 2.;
@@ -289,18 +431,39 @@ impl TemplateApp {
                 self.discarded_last_frame = true;
             }
             
-            let rendered_height = if !*enable_combos || cached_y.is_none() || chat_msg.message.combo_data.is_none() || chat_msg.message.combo_data.as_ref().is_some_and(|c| c.count == 1 && c.is_end) {
-                let highlight_msg = match chat_msg.message.msg_type {
+            let rendered_height = if is_ignored || filtered == Some(FilterAction::Collapse) {
+                let height = if is_ignored {
+                    chat::display_ignored_message(ui, &chat_msg)
+                } else {
+                    chat::display_filtered_message(ui, &chat_msg)
+                };
+
+                if cached_y.is_none() {
+                    y_size_from_new_messages += height + CHAT_ITEM_SPACING_Y;
+                }
+
+                *cached_y = Some(height);
+
+                height
+            }
+            else if !*enable_combos || cached_y.is_none() || chat_msg.message.combo_data.is_none() || chat_msg.message.combo_data.as_ref().is_some_and(|c| c.count == 1 && c.is_end) {
+                let highlight_msg = if highlighted_message.as_ref().is_some_and(|m| m.matches(chat_msg.message)) {
+                    Some(Color32::from_rgba_unmultiplied(120, 100, 20, 90))
+                } else { match chat_msg.message.msg_type {
                     MessageType::Announcement => Some(get_provider_color(&chat_msg.message.provider).linear_multiply(0.25)),
                     MessageType::Error => Some(Color32::from_rgba_unmultiplied(90, 0, 0, 90)),
                     MessageType::Information => Some(Color32::TRANSPARENT),
                     MessageType::Chat => if selected_user.as_ref() == Some(&chat_msg.message.profile.display_name.as_ref().unwrap_or(&chat_msg.message.username).to_lowercase()) {
                         Some(Color32::from_rgba_unmultiplied(90, 90, 90, 90))
                     } else {
-                        None
+                        highlight_color
                     }
-                };
+                }};
+                let (msg_top_left, msg_width) = (ui.cursor().min, ui.available_width());
                 let (height, user_selected, msg_right_clicked) = chat::display_chat_message(ui, &chat_msg, highlight_msg, chat_panel.selected_emote.is_none(), emote_loader);
+                if filtered == Some(FilterAction::Blur) {
+                    chat::cover_message(ui, Rect::from_min_size(msg_top_left, Vec2::new(msg_width, height)));
+                }
                 
                 if user_selected.is_some() {
                     if *selected_user == user_selected {
@@ -359,8 +522,10 @@ impl TemplateApp {
             *show_timestamps_changed = false;
         }
         
-        set_selected_message(set_selected_msg, ui, selected_msg);
+        if let Some(action) = set_selected_message(set_selected_msg, ui, selected_msg, channels) {
+            handle_message_action(action, reply_to, chat_managers, ignored_users);
+        }
         
-        (y_pos, y_size_from_new_messages)
+        (y_pos, y_size_from_new_messages, scroll_target_y)
     }
 }
\ No newline at end of file
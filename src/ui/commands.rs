@@ -0,0 +1,258 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use egui::Context;
+use itertools::Itertools;
+
+use crate::emotes::OverlayItem;
+use crate::provider::{channel::Channel, MessageType, ModerationAction};
+
+use super::TemplateApp;
+use super::highlights::{HighlightKind, HighlightRule};
+use super::models::*;
+
+/// Default /timeout length, same as Twitch's
+const DEFAULT_TIMEOUT_SEC : u32 = 600;
+
+pub struct CommandInfo {
+  pub name: &'static str,
+  pub usage: &'static str,
+  pub description: &'static str,
+  /// Sent through the chat provider, so only available where the provider lists it in `supported_commands`
+  pub needs_provider: bool
+}
+
+pub const COMMANDS : [CommandInfo; 14] = [
+  CommandInfo { name: "help", usage: "/help", description: "List the commands available in this channel", needs_provider: false },
+  CommandInfo { name: "join", usage: "/join <channel>", description: "Open a channel on the same platform as this one", needs_provider: false },
+  CommandInfo { name: "part", usage: "/part", description: "Close this channel", needs_provider: false },
+  CommandInfo { name: "ignore", usage: "/ignore <user>", description: "Hide messages from a user", needs_provider: false },
+  CommandInfo { name: "unignore", usage: "/unignore <user>", description: "Show messages from an ignored user again", needs_provider: false },
+  CommandInfo { name: "highlight", usage: "/highlight <word>", description: "Highlight messages containing the word and show them in Mentions, or stop if already highlighted", needs_provider: false },
+  CommandInfo { name: "me", usage: "/me <message>", description: "Send an action message", needs_provider: true },
+  CommandInfo { name: "w", usage: "/w <user> <message>", description: "Send a whisper", needs_provider: true },
+  CommandInfo { name: "clear", usage: "/clear", description: "Clear the chat history of this channel", needs_provider: false },
+  CommandInfo { name: "search", usage: "/search [text]", description: "Search the chat history", needs_provider: false },
+  CommandInfo { name: "timeout", usage: "/timeout <user> [duration]", description: "Time out a user for e.g. 30s, 10m, 1h or 1d (default 10m)", needs_provider: true },
+  CommandInfo { name: "ban", usage: "/ban <user>", description: "Permanently ban a user", needs_provider: true },
+  CommandInfo { name: "unban", usage: "/unban <user>", description: "Lift a ban or timeout", needs_provider: true },
+  CommandInfo { name: "reconnect", usage: "/reconnect", description: "Reconnect to the platform of this channel", needs_provider: false },
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChatCommand {
+  Help,
+  Join { channel: String },
+  Part,
+  Ignore { username: String },
+  Unignore { username: String },
+  Highlight { word: String },
+  Me { message: String },
+  Whisper { username: String, message: String },
+  Clear,
+  Search { text: String },
+  Moderate(ModerationAction),
+  Reconnect
+}
+
+impl ChatCommand {
+  pub fn name(&self) -> &'static str {
+    match self {
+      ChatCommand::Help => "help",
+      ChatCommand::Join { .. } => "join",
+      ChatCommand::Part => "part",
+      ChatCommand::Ignore { .. } => "ignore",
+      ChatCommand::Unignore { .. } => "unignore",
+      ChatCommand::Highlight { .. } => "highlight",
+      ChatCommand::Me { .. } => "me",
+      ChatCommand::Whisper { .. } => "w",
+      ChatCommand::Clear => "clear",
+      ChatCommand::Search { .. } => "search",
+      ChatCommand::Moderate(ModerationAction::Timeout { .. }) => "timeout",
+      ChatCommand::Moderate(ModerationAction::Ban { .. }) => "ban",
+      ChatCommand::Moderate(ModerationAction::Unban { .. }) => "unban",
+      ChatCommand::Moderate(ModerationAction::DeleteMessage { .. }) => "delete",
+      ChatCommand::Reconnect => "reconnect"
+    }
+  }
+}
+
+pub fn get_command_info(name: &str) -> Option<&'static CommandInfo> {
+  COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Whether the command can be used with a provider that supports `provider_commands`
+pub fn is_command_available(info: &CommandInfo, provider_commands: &[&str]) -> bool {
+  !info.needs_provider || provider_commands.contains(&info.name)
+}
+
+/// Parse a message box entry. Returns None if it is a normal chat message,
+/// or an error with the command's usage if the arguments are missing or invalid.
+pub fn parse_command(input: &str) -> Option<Result<ChatCommand, String>> {
+  let input = input.trim();
+  let rest = input.strip_prefix('/')?;
+  if rest.is_empty() || rest.starts_with('/') || rest.starts_with(char::is_whitespace) {
+    return None;
+  }
+  let (name, args) = rest.split_once(char::is_whitespace).map(|(n, a)| (n, a.trim())).unwrap_or((rest, ""));
+  let name = name.to_lowercase();
+  let Some(info) = get_command_info(&name) else {
+    return Some(Err(format!("Unknown command /{name}, type /help for a list of commands")));
+  };
+  let usage = || Err(format!("Usage: {}", info.usage));
+  let mut words = args.split_whitespace();
+  let username = words.next().map(|u| u.trim_start_matches('@').to_owned());
+
+  let command = match (info.name, username) {
+    ("help", _) => Ok(ChatCommand::Help),
+    ("join", Some(channel)) => Ok(ChatCommand::Join { channel: channel.trim_start_matches('#').to_owned() }),
+    ("part", _) => Ok(ChatCommand::Part),
+    ("ignore", Some(username)) => Ok(ChatCommand::Ignore { username }),
+    ("unignore", Some(username)) => Ok(ChatCommand::Unignore { username }),
+    ("highlight", Some(_)) => Ok(ChatCommand::Highlight { word: args.to_owned() }),
+    ("me", Some(_)) => Ok(ChatCommand::Me { message: args.to_owned() }),
+    ("w", Some(username)) => match words.join(" ") {
+      message if !message.is_empty() => Ok(ChatCommand::Whisper { username, message }),
+      _ => usage()
+    },
+    ("clear", _) => Ok(ChatCommand::Clear),
+    ("search", _) => Ok(ChatCommand::Search { text: args.to_owned() }),
+    ("timeout", Some(username)) => match words.next().map(parse_duration) {
+      None => Ok(ChatCommand::Moderate(ModerationAction::Timeout { username, duration_sec: DEFAULT_TIMEOUT_SEC })),
+      Some(Some(duration_sec)) if duration_sec > 0 => Ok(ChatCommand::Moderate(ModerationAction::Timeout { username, duration_sec })),
+      Some(_) => usage()
+    },
+    ("ban", Some(username)) => Ok(ChatCommand::Moderate(ModerationAction::Ban { username })),
+    ("unban", Some(username)) => Ok(ChatCommand::Moderate(ModerationAction::Unban { username })),
+    ("reconnect", _) => Ok(ChatCommand::Reconnect),
+    _ => usage()
+  };
+  Some(command)
+}
+
+/// Seconds from e.g. "90", "30s", "10m", "1h", "1d" or "1w"
+pub fn parse_duration(input: &str) -> Option<u32> {
+  let input = input.to_lowercase();
+  let (number, multiplier) = match input.chars().last()? {
+    's' => (&input[..input.len() - 1], 1),
+    'm' => (&input[..input.len() - 1], 60),
+    'h' => (&input[..input.len() - 1], 60 * 60),
+    'd' => (&input[..input.len() - 1], 60 * 60 * 24),
+    'w' => (&input[..input.len() - 1], 60 * 60 * 24 * 7),
+    _ => (input.as_str(), 1)
+  };
+  number.parse::<u32>().ok()?.checked_mul(multiplier)
+}
+
+impl TemplateApp {
+  /// Commands matching the word typed at the start of the message box, for the autocomplete overlay
+  pub fn get_possible_commands(&self, selected_channel: Option<&String>, word: Option<&String>) -> Option<Vec<(String, Option<OverlayItem>)>> {
+    let word = word?.strip_prefix('/')?.to_lowercase();
+    let provider = self.channels.get(selected_channel?)?.provider();
+    let provider_commands = self.chat_managers.get(&provider).map(|m| m.supported_commands()).unwrap_or_default();
+    Some(COMMANDS.iter()
+      .filter(|c| c.name.starts_with(&word) && is_command_available(c, provider_commands))
+      .map(|c| (format!("/{}", c.name), None))
+      .collect_vec())
+  }
+
+  /// Run a parsed command for the channel. Errors are meant to be shown in the channel's chat.
+  pub fn run_command(&mut self, channel_name: &str, command: ChatCommand, ctx: &Context) -> Result<(), String> {
+    let Some(provider) = self.channels.get(channel_name).map(|c| c.provider()) else {
+      return Err("Channel not found".to_owned());
+    };
+    let provider_commands = self.chat_managers.get(&provider).map(|m| m.supported_commands()).unwrap_or_default();
+    if let Some(info) = get_command_info(command.name()) && !is_command_available(info, provider_commands) {
+      return Err(format!("/{} is not supported for {}", info.name, provider.display_name()));
+    }
+    if get_command_info(command.name()).is_some_and(|info| info.needs_provider) && let Some(chat_mgr) = self.chat_managers.get(&provider) {
+      chat_mgr.can_send(channel_name)?;
+    }
+
+    match command {
+      ChatCommand::Help => {
+        let help = COMMANDS.iter()
+          .filter(|c| is_command_available(c, provider_commands))
+          .map(|c| format!("{} - {}", c.usage, c.description))
+          .collect_vec();
+        for line in help {
+          self.show_system_message(channel_name, &provider, line, MessageType::Information);
+        }
+      },
+      ChatCommand::Join { channel } => {
+        let irc_network = match self.channels.get(channel_name) {
+          Some(Channel::Irc { irc, .. }) => irc.network.to_owned(),
+          _ => Default::default()
+        };
+        if provider.info().single_channel() {
+          return Err(format!("{} only has one channel", provider.display_name()));
+        }
+        self.add_channel(&provider, &channel, irc_network);
+      },
+      ChatCommand::Part => {
+        self.last_frame_ui_events.push_back(UiEvent::ChannelRemoved(channel_name.to_owned()));
+      },
+      ChatCommand::Ignore { username } => {
+        let username = username.to_lowercase();
+        if self.ignored_users.entry(provider.to_owned()).or_default().insert(username.to_owned()) {
+          self.show_system_message(channel_name, &provider, format!("Ignoring {username} on {}.", provider.display_name()), MessageType::Information);
+        } else {
+          return Err(format!("{username} is already ignored"));
+        }
+      },
+      ChatCommand::Unignore { username } => {
+        let username = username.to_lowercase();
+        if self.ignored_users.get_mut(&provider).is_some_and(|users| users.remove(&username)) {
+          self.show_system_message(channel_name, &provider, format!("No longer ignoring {username}."), MessageType::Information);
+        } else {
+          return Err(format!("{username} is not ignored"));
+        }
+      },
+      ChatCommand::Highlight { word } => {
+        if let Some(ix) = self.highlight_rules.iter().position(|r| r.kind == HighlightKind::Keyword && r.rule.channel.is_none() && r.rule.pattern.to_lowercase() == word.to_lowercase()) {
+          self.highlight_rules.remove(ix);
+          self.show_system_message(channel_name, &provider, format!("No longer highlighting \"{word}\"."), MessageType::Information);
+        } else {
+          self.show_system_message(channel_name, &provider, format!("Highlighting \"{word}\". Colors and other rules can be set under Options > Highlights."), MessageType::Information);
+          self.highlight_rules.push(HighlightRule::keyword(&word));
+        }
+      },
+      ChatCommand::Me { message } => {
+        if let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+          chat_mgr.send_action(channel_name, &message).map_err(|e| e.to_string())?;
+        }
+      },
+      ChatCommand::Whisper { username, message } => {
+        if let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+          chat_mgr.send_whisper(&username, &message).map_err(|e| e.to_string())?;
+        }
+        self.open_whisper(&provider, &username);
+      },
+      ChatCommand::Clear => {
+        if let Some(history) = self.chat_histories.get_mut(channel_name) {
+          history.clear();
+        }
+        self.hidden_message_counts.remove(channel_name);
+      },
+      ChatCommand::Search { text } => {
+        self.search_menu.text = text;
+        self.search_menu.channel = Some(channel_name.to_owned());
+        self.show_search_window = true;
+        self.run_search();
+      },
+      ChatCommand::Moderate(action) => {
+        if let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+          chat_mgr.send_moderation(channel_name, action).map_err(|e| e.to_string())?;
+        }
+      },
+      ChatCommand::Reconnect => {
+        self.show_system_message(channel_name, &provider, format!("Reconnecting to {}...", provider.display_name()), MessageType::Information);
+        self.reconnect_provider(&provider, ctx);
+      }
+    }
+    Ok(())
+  }
+}
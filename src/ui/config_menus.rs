@@ -4,13 +4,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use tracing::{error, warn};
+use tracing::warn;
 use tracing_unwrap::{OptionExt, ResultExt};
-use std::collections::HashMap;
 use egui::{Color32, Key, OpenUrl, RichText, TextStyle};
-use crate::provider::{dgg, twitch::{self, TwitchChatManager}, Provider, ProviderName};
-use crate::provider::channel::{Channel, YoutubeChannel, ChannelShared};
+use crate::provider::{channel::IrcNetwork, twitch, Provider, ProviderName};
 use crate::emotes::EmoteRequest;
+use crate::archive::ARCHIVE_RETENTION_DAYS;
 
 use super::models::*;
 
@@ -28,6 +27,18 @@ impl TemplateApp {
                     self.show_auth_ui = true;
                 }
                 ui.separator();
+                if ui.button(RichText::new("Search").text_style(TextStyle::Small)).clicked() {
+                    self.show_search_window = true;
+                }
+                ui.separator();
+                let whispers_label = match self.unread_whispers() {
+                    0 => "Whispers".to_owned(),
+                    unread => format!("Whispers ({unread})")
+                };
+                if ui.button(RichText::new(whispers_label).text_style(TextStyle::Small)).clicked() {
+                    self.show_whispers_window = true;
+                }
+                ui.separator();
                 ui.menu_button(RichText::new("Options").text_style(TextStyle::Small), |ui| {
                     ui.scope(|ui| {
                         let fontid = TextStyle::Button.resolve(ui.style().as_ref());
@@ -43,18 +54,34 @@ impl TemplateApp {
                         if ui.checkbox(&mut self.show_muted, "Show Muted/Banned Messages").changed() {
                             self.show_timestamps_changed = true;
                         };
+                        if ui.button("Ignored Users...").clicked() {
+                            self.show_ignore_list = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Highlights...").on_hover_text("Highlight messages by keyword, regex or user, and choose which show in the Mentions tab.").clicked() {
+                            self.show_highlights_window = true;
+                            ui.close_menu();
+                        }
+                        ui.checkbox(&mut self.enable_notifications, "Desktop Notifications").on_hover_text("Show a desktop notification for mentions and highlighted messages while the window is not focused. Can be muted per channel using the channel level options menu.");
+                        ui.add_enabled(self.enable_notifications, egui::Checkbox::new(&mut self.notify_on_live, "Notify When Channels Go Live"));
+                        if ui.button("Sounds...").on_hover_text("Play a sound for mentions, highlights and channels going live.").clicked() {
+                            self.show_sound_settings = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Filters...").on_hover_text("Hide, collapse or blur messages matching a regex, such as spoilers or link spam.").clicked() {
+                            self.show_filters_window = true;
+                            ui.close_menu();
+                        }
                         ui.checkbox(&mut self.force_compact_emote_selector, "Force Compact Emote Selector").on_hover_text("Only show emote images in selector. If disabled, selector will show emote text alongside images, if all emotes can fit into displayable area.");
-                        ui.checkbox(&mut self.enable_yt_integration, "Enable YT Integration");
+                        ui.checkbox(&mut self.enable_yt_integration, "Enable YT Integration").on_hover_text("Receive and send YouTube chat through the Tampermonkey script. Not needed to read YouTube channels added to the channel list.");
+                        ui.checkbox(&mut self.enable_chat_archive, "Archive Chat Logs").on_hover_text(format!("Save all chat messages to daily per-channel log files in the cache folder. Logs older than {ARCHIVE_RETENTION_DAYS} days are deleted."));
                         ui.add(egui::Slider::new(&mut self.chat_history_limit, 100..=10000).step_by(100.).text(RichText::new("Chat history limit").text_style(TextStyle::Small)));
                         if ui.button("Reload Global and TTV Sub Emotes").clicked() {
                             if let Err(e) = self.emote_loader.tx.try_send(EmoteRequest::GlobalEmoteListRequest { force_redownload: true }) {
                                 warn!("Failed to send request: {e}");
                             }
-                            if let Err(e) = self.emote_loader.tx.try_send(EmoteRequest::TwitchGlobalBadgeListRequest { 
-                                token: self.auth_tokens.twitch_auth_token.to_owned(), 
-                                force_redownload: true 
-                            }) {
-                                warn!("Failed to send request: {e}");
+                            for chat_mgr in self.chat_managers.values() {
+                                chat_mgr.request_global_emotes(&self.emote_loader, true);
                             }
                             let twitch_auth = &self.auth_tokens.twitch_auth_token;
                             if let Some(provider) = self.providers.get(&ProviderName::Twitch) {
@@ -193,93 +220,34 @@ impl TemplateApp {
             }
         }
         if changed_twitch_token {
-            if let Some(mgr) = self.twitch_chat_manager.as_mut() {
-                mgr.close();
-            }
-            if !self.auth_tokens.twitch_auth_token.is_empty() {
-                let mut mgr = TwitchChatManager::new(&self.auth_tokens.twitch_username, &self.auth_tokens.twitch_auth_token, self.runtime.as_ref().unwrap_or_log(), ctx);
-                for (_, channel) in self.channels.iter_mut() {
-                    if let Channel::Twitch { twitch, ref mut shared } = channel {
-                        //mgr.open_channel(shared, Some(twitch.room_id.to_owned()))
-                        mgr.open_channel(twitch, shared);
-                    }
-                }
-                self.twitch_chat_manager = Some(mgr);
-                match self.emote_loader.tx.try_send(EmoteRequest::TwitchGlobalBadgeListRequest { token: self.auth_tokens.twitch_auth_token.to_owned(), force_redownload: false }) {  
-                    Ok(_) => {},
-                    Err(e) => { error!("Failed to request global emote json due to error {:?}", e); }
-                };
-            }
+            self.reconnect_provider(&ProviderName::Twitch, ctx);
         }
         if changed_dgg_token {
-            for (_, channel) in self.channels.iter_mut() {
-                if let Channel::DGG { dgg, ref mut shared } = channel {
-                    if let Some(chat_mgr) = dgg.dgg_chat_manager.as_mut() {
-                        chat_mgr.close();
-                    }
-                    dgg.dgg_chat_manager = Some(dgg::open_channel(&self.auth_tokens.dgg_username, &self.auth_tokens.dgg_auth_token, dgg, shared, self.runtime.as_ref().unwrap_or_log(), &self.emote_loader, ctx));
-                }
-            }       
+            self.reconnect_provider(&ProviderName::DGG, ctx);
         }
     }
     
+    /// Add a channel tab, or select it if it is already open
+    pub fn add_channel(&mut self, provider: &ProviderName, channel_name: &str, irc_network: IrcNetwork) {
+        self.providers.entry(provider.to_owned()).or_insert(Provider {
+            name: provider.info().archive_dir().to_owned(),
+            my_sub_emotes: Default::default(),
+            emotes: Default::default(),
+            global_badges: Default::default(),
+            username: Default::default(),
+            my_emote_sets: Default::default()
+        });
+        let c = provider.info().init_channel(channel_name, irc_network);
+        
+        let name = c.channel_name().to_owned();
+        if self.channels.try_insert(name.to_owned(), c).is_ok() {
+            self.channel_tab_list.push(name.to_owned());
+        }
+        self.selected_channel = Some(name);
+    }
+
     pub fn ui_add_channel_menu(&mut self, ctx: &egui::Context) {
-        let mut add_channel = |providers: &mut HashMap<ProviderName, Provider>, auth_tokens: &mut AuthTokens, channel_options: &mut AddChannelMenu| {
-            let c = match channel_options.provider {
-                ProviderName::Twitch => { 
-                    providers.entry(ProviderName::Twitch).or_insert(Provider {
-                        name: "twitch".to_owned(),
-                        my_sub_emotes: Default::default(),
-                        emotes: Default::default(),
-                        global_badges: Default::default(),
-                        username: Default::default(),
-                        my_emote_sets: Default::default()
-                    });
-                    match self.emote_loader.tx.try_send(EmoteRequest::TwitchGlobalBadgeListRequest { token: auth_tokens.twitch_auth_token.to_owned(), force_redownload: false }) {  
-                        Ok(_) => {},
-                        Err(e) => { error!("Failed to request global emote json due to error {:?}", e); }
-                    };
-                    if self.twitch_chat_manager.is_none() {
-                        self.twitch_chat_manager = Some(TwitchChatManager::new(&auth_tokens.twitch_username, &auth_tokens.twitch_auth_token, self.runtime.as_ref().unwrap_or_log(), ctx));
-                    }
-                    self.twitch_chat_manager.as_mut().unwrap_or_log().init_channel(&channel_options.channel_name)
-                },
-                ProviderName::DGG => dgg::init_channel(),
-                ProviderName::YouTube => {
-                    providers.entry(ProviderName::YouTube).or_insert(Provider {
-                        name: "YouTube".to_owned(),
-                        my_sub_emotes: Default::default(),
-                        emotes: Default::default(),
-                        global_badges: Default::default(),
-                        username: Default::default(),
-                        my_emote_sets: Default::default()
-                    });
-                    
-                    Channel::Youtube { 
-                        youtube: YoutubeChannel {}, 
-                        shared: ChannelShared { ..Default::default() } 
-                    }
-                }
-                /*ProviderName::YouTube => {
-                if providers.contains_key(&ProviderName::Twitch) == false {
-                providers.insert(ProviderName::Twitch, Provider {
-                name: "youtube".to_owned(),
-                my_sub_emotes: Default::default(),
-                emotes: Default::default(),
-                global_badges: Default::default()
-                });
-                }
-                youtube::init_channel(channel_options.channel_name.to_owned(), channel_options.channel_id.to_owned(), auth_tokens.youtube_auth_token.to_owned(), self.runtime.as_ref().unwrap_or_log())
-                }*/
-            };
-            
-            let name = c.channel_name().to_owned();
-            if self.channels.try_insert(name.to_owned(), c).is_ok() {
-                self.channel_tab_list.push(name.to_owned());
-            }
-            self.selected_channel = Some(name);
-            channel_options.channel_name = Default::default();
-        };
+        let mut add_channel = false;
         if self.show_add_channel_menu {
             let add_menu = egui::Window::new("Add Channel").collapsible(false).show(ctx, |ui| {
                 ui.scope(|ui| {
@@ -289,14 +257,42 @@ impl TemplateApp {
                     let mut name_input : Option<egui::Response> = None;
                     ui.horizontal(|ui| {
                         ui.label("Provider:");
-                        ui.selectable_value(&mut self.add_channel_menu.provider, ProviderName::Twitch, "Twitch");
-                        //ui.selectable_value(&mut self.add_channel_menu.provider, ProviderName::YouTube, "Youtube");
-                        ui.selectable_value(&mut self.add_channel_menu.provider, ProviderName::DGG, "destiny.gg");
+                        for provider in ProviderName::ALL {
+                            let label = provider.display_name();
+                            ui.selectable_value(&mut self.add_channel_menu.provider, provider, label);
+                        }
                     });
-                    if self.add_channel_menu.provider == ProviderName::Twitch {
+                    let info = self.add_channel_menu.provider.info();
+                    if self.add_channel_menu.provider == ProviderName::Irc {
+                        let network = &mut self.add_channel_menu.irc_network;
+                        egui::Grid::new("irc_network").num_columns(2).show(ui, |ui| {
+                            ui.label("Server:");
+                            ui.add(egui::TextEdit::singleline(&mut network.server).hint_text("irc.libera.chat"));
+                            ui.end_row();
+                            ui.label("Port:");
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut network.port));
+                                ui.checkbox(&mut network.use_tls, "TLS");
+                            });
+                            ui.end_row();
+                            ui.label("Nickname:");
+                            ui.text_edit_singleline(&mut network.nickname);
+                            ui.end_row();
+                            ui.label("Password:");
+                            ui.horizontal(|ui| {
+                                ui.add(egui::TextEdit::singleline(&mut network.password).password(true));
+                                ui.checkbox(&mut network.use_sasl, "SASL").on_hover_text("Log in to the nickname's account with SASL PLAIN instead of sending a server password.");
+                            });
+                            ui.end_row();
+                            ui.label("Channel:");
+                            name_input = Some(ui.add(egui::TextEdit::singleline(&mut self.add_channel_menu.channel_name).hint_text(info.channel_name_hint())));
+                            ui.end_row();
+                        });
+                    }
+                    else if !info.single_channel() {
                         ui.horizontal(|ui| {
-                            ui.label("Channel Name:");
-                            name_input = Some(ui.text_edit_singleline(&mut self.add_channel_menu.channel_name));
+                            ui.label("Channel:");
+                            name_input = Some(ui.add(egui::TextEdit::singleline(&mut self.add_channel_menu.channel_name).hint_text(info.channel_name_hint())));
                             //name_input.request_focus();
                         });
                     }
@@ -308,7 +304,7 @@ impl TemplateApp {
                     }*/
                     
                     if name_input.is_some() && !self.add_channel_menu.channel_name.starts_with("YT:") && name_input.unwrap_or_log().has_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) || ui.button("Add channel").clicked() {
-                        add_channel(&mut self.providers, &mut self.auth_tokens, &mut self.add_channel_menu); 
+                        add_channel = true;
                         self.show_add_channel_menu = false;
                     }
                     if ui.button("Cancel").clicked() {
@@ -325,6 +321,11 @@ impl TemplateApp {
                 self.show_add_channel_menu = false;
             }
         }
+        if add_channel {
+            let channel_name = std::mem::take(&mut self.add_channel_menu.channel_name);
+            let (provider, irc_network) = (self.add_channel_menu.provider.to_owned(), self.add_channel_menu.irc_network.to_owned());
+            self.add_channel(&provider, &channel_name, irc_network);
+        }
     } 
 
     pub fn ui_channel_options(&mut self, ctx: &egui::Context) -> Option<String> {
@@ -339,9 +340,15 @@ impl TemplateApp {
             if !channel.is_empty() {
               if let Some(ch) = self.channels.get_mut(&channel) {
                 let resp = ui.checkbox(&mut ch.shared_mut().show_tab_when_offline, "Always Show Tab").on_hover_text("Ignore the Hide Offline setting and always display this channel in tab list.");
-                if resp.changed() && let Some(mgr) = self.twitch_chat_manager.as_mut() && let Channel::Twitch { twitch, shared } = ch {
-                    mgr.open_channel(twitch, shared);
+                if resp.changed() && let Some(mgr) = self.chat_managers.get_mut(&ch.provider()) {
+                    mgr.open_channel(ch);
                 }
+                ui.checkbox(&mut ch.shared_mut().mute_notifications, "Mute Notifications").on_hover_text("No desktop notifications or sounds for mentions, highlights and going live in this channel.");
+              }
+              let hidden_count = self.hidden_message_counts.get(&channel).copied().unwrap_or_default();
+              let mut show_filtered = self.show_filtered_channels.contains(&channel);
+              if ui.checkbox(&mut show_filtered, format!("Show Filtered Messages ({hidden_count})")).on_hover_text("Show messages hidden or collapsed by filters in this channel.").changed() {
+                self.set_show_filtered(&channel, show_filtered);
               }
               ui.separator();
               if ui.button("Remove channel").clicked() {
@@ -349,29 +356,8 @@ impl TemplateApp {
                 self.show_channel_options = None;
               }
               if ui.button("Reload channel emotes").clicked() {
-                if let Some(ch) = self.channels.get_mut(&channel) {
-                  match ch {
-                    Channel::Twitch { twitch, shared } => {
-                      if let Some(room_id) = twitch.room_id.as_ref() && let Err(e) = self.emote_loader.tx.try_send(EmoteRequest::TwitchBadgeEmoteListRequest { 
-                        channel_id: room_id.to_owned(), 
-                        channel_name: shared.channel_name.to_owned(),
-                        token: self.auth_tokens.twitch_auth_token.to_owned(), 
-                        force_redownload: true
-                      }) {  
-                        warn!("Failed to send load emote json request for channel {channel} due to error {e:?}");
-                      }
-                    },
-                    Channel::DGG { dgg, shared } => {
-                      if let Err(e) = self.emote_loader.tx.try_send(EmoteRequest::DggFlairEmotesRequest { 
-                        channel_name: shared.channel_name.to_owned(),
-                        cdn_base_url: dgg.dgg_cdn_url.to_owned(),
-                        force_redownload: true
-                      }) {
-                        error!("Failed to load badge/emote json for channel {channel} due to error {e:?}");
-                      }
-                    },
-                    Channel::Youtube { youtube: _, shared: _ } => {}
-                  };
+                if let Some(ch) = self.channels.get(&channel) && let Some(mgr) = self.chat_managers.get(&ch.provider()) {
+                  mgr.request_channel_emotes(ch, &self.emote_loader, true);
                 }
                 self.show_channel_options = None;
               }
@@ -13,4 +13,13 @@ pub const DEFAULT_USER_COLOR : (u8,u8,u8) = (255,255,255);
 
 pub const NEW_MESSAGES_PER_FRAME : usize = 50;
 
-pub const CHAT_ITEM_SPACING_Y : f32 = 2.;
\ No newline at end of file
+/// Oldest messages are dropped from a whisper conversation past this
+pub const WHISPER_HISTORY_LIMIT : usize = 500;
+
+/// Messages loaded back from the chat log archive are drawn faded
+pub const ARCHIVED_MESSAGE_OPACITY : f32 = 0.55;
+
+pub const CHAT_ITEM_SPACING_Y : f32 = 2.;
+
+/// (label, seconds) choices for timeouts in the message context menu
+pub const TIMEOUT_PRESETS : [(&str, u32); 4] = [("1m", 60), ("10m", 600), ("1h", 3600), ("1d", 86400)];
@@ -19,12 +19,11 @@ use egui::Vec2;
 use tracing::info;
 use tracing::warn;
 
-use crate::provider::channel::Channel;
-use crate::provider::ChatManagerRx;
-use crate::provider::OutgoingMessage;
+use crate::provider::MessageType;
 
 use super::TemplateApp;
 use super::addtl_functions::*;
+use super::commands::*;
 use super::consts::*;
 use super::models::*;
 
@@ -51,8 +50,21 @@ impl TemplateApp {
         };
         
         //ui.painter().rect_stroke(ui.max_rect(), Rounding::none(), Stroke::new(2.0, Color32::DARK_RED));
-        let outgoing_msg_hint : egui::WidgetText = "Type a message to send".into();
+        let chat_modes = chat_panel.selected_channel.as_ref()
+            .and_then(|sc| self.channels.get(sc))
+            .and_then(|sco| sco.transient())
+            .map(|t| t.chat_modes.labels())
+            .unwrap_or_default();
+        let outgoing_msg_hint : egui::WidgetText = if chat_modes.is_empty() {
+            "Type a message to send".into()
+        } else {
+            format!("Type a message to send ({})", chat_modes.join(", ")).into()
+        };
         
+        if chat_panel.reply_to.as_ref().is_some_and(|msg| chat_panel.selected_channel.as_ref() != Some(&msg.channel)) {
+            chat_panel.reply_to = None;
+        }
+
         ui.style_mut().visuals.extreme_bg_color = Color32::from_rgba_premultiplied(0, 0, 0, 120);
         let mut draft_message = chat_panel.draft_message.to_owned();
         let mut outgoing_msg = egui::TextEdit::multiline(&mut draft_message)
@@ -62,6 +74,23 @@ impl TemplateApp {
         .font(egui::TextStyle::Body)
         .lock_focus(chat_panel.selected_emote_input.is_some())
         .show(ui);
+
+        // chat frame is laid out bottom up, so this shows above the message box
+        if let Some(reply_to) = chat_panel.reply_to.as_ref() {
+            let mut cancel_reply = false;
+            ui.horizontal(|ui| {
+                cancel_reply = ui.small_button("✖").on_hover_text("Cancel reply").clicked();
+                ui.add(egui::Label::new(egui::RichText::new(format!("Replying to @{}: {}", reply_to.profile.display_name.as_ref().unwrap_or(&reply_to.username), reply_to.message)).small().weak()).truncate());
+            });
+            if cancel_reply {
+                chat_panel.reply_to = None;
+            }
+        }
+
+        // usage of the command being typed
+        if let Some(info) = draft_message.strip_prefix('/').and_then(|cmd| cmd.split_whitespace().next()).and_then(|name| get_command_info(&name.to_lowercase())) {
+            ui.add(egui::Label::new(egui::RichText::new(format!("{} - {}", info.usage, info.description)).small().weak()).truncate());
+        }
         
         let msg_box_id = Some(outgoing_msg.response.id);
         
@@ -121,24 +150,50 @@ impl TemplateApp {
         }
         
         if outgoing_msg.response.has_focus() && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) && !draft_message.is_empty() {
-            if let Some(sc) = chat_panel.selected_channel.as_ref() && let Some(sco) = self.channels.get_mut(sc) {
-                let (chat_tx, shared) = match sco {
-                    Channel::Twitch { twitch: _, ref mut shared } => (self.twitch_chat_manager.as_mut().map(|m| m.in_tx()), shared),
-                    Channel::DGG { ref mut dgg, ref mut shared } => (dgg.dgg_chat_manager.as_mut().map(|m| m.in_tx()), shared),
-                    Channel::Youtube { youtube: _, ref mut shared } => (self.yt_chat_manager.as_mut().map(|m| m.in_tx()), shared)
-                };
-                if let Some(chat_tx) = chat_tx {
-                    match chat_tx.try_send(OutgoingMessage::Chat { channel: shared.channel_name.to_owned(), message: draft_message.replace('\n', " ") }) {
-                        Err(e) => info!("Failed to send message: {}", e), //TODO: emit this into UI
+            if let Some(command) = parse_command(&draft_message) && let Some(sc) = chat_panel.selected_channel.to_owned() {
+                match command.and_then(|command| self.run_command(&sc, command, ctx)) {
+                    Err(e) => if let Some(provider) = self.channels.get(&sc).map(|c| c.provider()) {
+                        self.show_system_message(&sc, &provider, e, MessageType::Error);
+                    },
+                    Ok(_) => {
+                        if let Some(sco) = self.channels.get_mut(&sc) {
+                            sco.shared_mut().send_history.push(draft_message.trim_end().to_owned());
+                            sco.shared_mut().send_history_ix = None;
+                        }
+                        draft_message = String::new();
+                        chat_panel.selected_emote = None;
+                        chat_panel.selected_emote_input = None;
+                    }
+                }
+            }
+            else if let Some(sc) = chat_panel.selected_channel.as_ref() && let Some(sco) = self.channels.get_mut(sc) {
+                let provider = sco.provider();
+                let chat_mgr = self.chat_managers.get_mut(&provider);
+                let shared = sco.shared_mut();
+                let mut send_error = None;
+                if let Some(chat_mgr) = chat_mgr {
+                    let result = chat_mgr.can_send(&shared.channel_name).and_then(|_| match chat_panel.reply_to.as_ref() {
+                        Some(reply_to) => chat_mgr.send_reply(&shared.channel_name, &draft_message.replace('\n', " "), reply_to),
+                        None => chat_mgr.send_chat(&shared.channel_name, &draft_message.replace('\n', " "))
+                    }.map_err(|e| e.to_string()));
+                    match result {
+                        Err(e) => {
+                            info!("Failed to send message: {}", e);
+                            send_error = Some(e);
+                        },
                         _ => {
                             shared.send_history.push(draft_message.trim_end().to_owned());
                             draft_message = String::new();
                             shared.send_history_ix = None;
                             chat_panel.selected_emote = None;
                             chat_panel.selected_emote_input = None;
+                            chat_panel.reply_to = None;
                         }
                     }
                 }
+                if let Some(e) = send_error {
+                    self.show_system_message(sc, &provider, e, MessageType::Error);
+                }
             } 
         }
         else if (outgoing_msg.response.has_focus() || !self.last_frame_ui_events.is_empty()) && !draft_message.is_empty() && let Some(cursor_pos) = outgoing_msg.state.cursor.char_range() {
@@ -158,8 +213,12 @@ impl TemplateApp {
             if let Some((pos, word)) = chat_panel.selected_emote_input.as_ref().or(word_input.as_ref()) {
                 
                 let force_compact = !self.force_compact_emote_selector;
-                let is_user_list = word.starts_with('@');
-                let emotes = if is_user_list { 
+                let is_command_list = *pos == 0 && word.starts_with('/');
+                // users and commands are listed as text only
+                let is_user_list = word.starts_with('@') || is_command_list;
+                let emotes = if is_command_list {
+                    self.get_possible_commands(chat_panel.selected_channel.as_ref(), Some(word))
+                } else if is_user_list { 
                     self.get_possible_users(chat_panel.selected_channel.as_ref(), Some(word)) 
                 } else { 
                     self.get_possible_emotes(chat_panel.selected_channel.as_ref(), Some(word), ctx) 
@@ -0,0 +1,123 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use egui::TextStyle;
+
+use crate::provider::{ChatMessage, FilterAction};
+
+use super::TemplateApp;
+use super::pattern_rule::{ui_add_rule_row, PatternRule};
+
+impl FilterAction {
+  pub fn label(&self) -> &'static str {
+    match self {
+      FilterAction::Hide => "Hide",
+      FilterAction::Collapse => "Collapse",
+      FilterAction::Blur => "Blur"
+    }
+  }
+
+  /// Whether the message is left out of the chat, rather than shown blurred
+  pub fn hides_message(&self) -> bool {
+    matches!(self, FilterAction::Hide | FilterAction::Collapse)
+  }
+}
+
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))]
+pub struct MessageFilter {
+  /// Regex matched against the message text
+  #[cfg_attr(feature = "persistence", serde(flatten))]
+  pub rule: PatternRule,
+  pub action: FilterAction
+}
+
+impl MessageFilter {
+  pub fn new(pattern: &str, channel: Option<&str>, action: FilterAction) -> Self {
+    Self { rule: PatternRule::new(pattern, channel), action }
+  }
+
+  pub fn error(&self) -> Option<&String> {
+    self.rule.regex().err()
+  }
+
+  pub fn matches(&self, msg: &ChatMessage) -> bool {
+    self.rule.applies_to(msg) && self.rule.regex().is_ok_and(|r| r.is_match(&msg.message))
+  }
+}
+
+/// Action of the first filter matching the message
+pub fn get_filter_action(filters: &[MessageFilter], msg: &ChatMessage) -> Option<FilterAction> {
+  filters.iter().find(|f| f.matches(msg)).map(|f| f.action)
+}
+
+impl TemplateApp {
+  /// Show filtered messages of the channel as if they did not match any filter
+  pub fn set_show_filtered(&mut self, channel: &str, show: bool) {
+    if show {
+      self.show_filtered_channels.insert(channel.to_owned());
+    } else {
+      self.show_filtered_channels.remove(channel);
+    }
+    // row heights change for every filtered message
+    if let Some(history) = self.chat_histories.get_mut(channel) {
+      for (msg, cached_y) in history.iter_mut() {
+        if msg.filtered.is_some() {
+          *cached_y = None;
+        }
+      }
+    }
+  }
+
+  pub fn ui_filters_window(&mut self, ctx: &egui::Context) {
+    if !self.show_filters_window {
+      return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Filters").open(&mut open).collapsible(false).default_width(500.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        ui.label("Filters apply to new messages. Right click a channel tab to show its filtered messages anyway.");
+        ui.separator();
+
+        let mut removed : Option<usize> = None;
+        egui::ScrollArea::vertical().id_salt("message_filters").max_height(400.).auto_shrink([false, true]).show(ui, |ui| {
+          egui::Grid::new("message_filters_grid").num_columns(5).show(ui, |ui| {
+            for (ix, filter) in self.message_filters.iter_mut().enumerate() {
+              filter.rule.ui_enabled(ui);
+              filter.rule.ui_pattern(ui, "Regex", true);
+              filter.rule.ui_channel(ui, format!("filter_channel_{ix}"), &self.channel_tab_list);
+              egui::ComboBox::from_id_salt(format!("filter_action_{ix}")).width(80.).selected_text(filter.action.label()).show_ui(ui, |ui| {
+                for action in [FilterAction::Hide, FilterAction::Collapse, FilterAction::Blur] {
+                  ui.selectable_value(&mut filter.action, action, action.label());
+                }
+              });
+              if ui.small_button("✖").on_hover_text("Remove filter").clicked() {
+                removed = Some(ix);
+              }
+              ui.end_row();
+            }
+          });
+        });
+        if let Some(ix) = removed {
+          self.message_filters.remove(ix);
+        }
+
+        if ui_add_rule_row(ui, "Add filter", self.message_filters.iter().any(|f| f.error().is_some())) {
+          self.message_filters.push(Default::default());
+        }
+      });
+    });
+
+    if !open {
+      self.show_filters_window = false;
+    }
+  }
+}
@@ -0,0 +1,169 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::HashMap;
+use egui::{Color32, TextStyle};
+
+use crate::provider::ChatMessage;
+use crate::sounds::AlertEvent;
+
+use super::TemplateApp;
+use super::addtl_functions::mentioned_in_message;
+use super::pattern_rule::{ui_add_rule_row, PatternRule};
+
+pub const DEFAULT_HIGHLIGHT_COLOR : Color32 = Color32::from_rgba_premultiplied(35, 21, 0, 90);
+
+#[derive(Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub enum HighlightKind {
+  /// Case insensitive substring of the message
+  #[default]
+  Keyword,
+  Regex,
+  /// Username or display name of the sender
+  User
+}
+
+impl HighlightKind {
+  pub fn label(&self) -> &'static str {
+    match self {
+      HighlightKind::Keyword => "Keyword",
+      HighlightKind::Regex => "Regex",
+      HighlightKind::User => "User"
+    }
+  }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))]
+pub struct HighlightRule {
+  #[cfg_attr(feature = "persistence", serde(flatten))]
+  pub rule: PatternRule,
+  pub kind: HighlightKind,
+  pub color: Color32,
+  /// Also show matching messages in the Mentions tab
+  pub show_in_mentions: bool,
+  /// Play the highlight alert sound for matching messages
+  pub play_sound: bool
+}
+
+impl Default for HighlightRule {
+  fn default() -> Self {
+    Self {
+      rule: Default::default(),
+      kind: Default::default(),
+      color: DEFAULT_HIGHLIGHT_COLOR,
+      show_in_mentions: true,
+      play_sound: true
+    }
+  }
+}
+
+impl HighlightRule {
+  pub fn new(kind: HighlightKind, pattern: &str, channel: Option<&str>) -> Self {
+    Self { rule: PatternRule::new(pattern, channel), kind, ..Default::default() }
+  }
+
+  pub fn keyword(keyword: &str) -> Self {
+    Self::new(HighlightKind::Keyword, keyword, None)
+  }
+
+  /// The regex compile error, if this is an invalid regex rule
+  pub fn error(&self) -> Option<&String> {
+    match self.kind {
+      HighlightKind::Regex => self.rule.regex().err(),
+      _ => None
+    }
+  }
+
+  pub fn matches(&self, msg: &ChatMessage) -> bool {
+    if !self.rule.applies_to(msg) {
+      return false;
+    }
+    let pattern = &self.rule.pattern;
+    match self.kind {
+      HighlightKind::Keyword => msg.message.to_lowercase().contains(&pattern.to_lowercase()),
+      HighlightKind::Regex => self.rule.regex().is_ok_and(|r| r.is_match(&msg.message)),
+      HighlightKind::User => {
+        let pattern = pattern.trim_start_matches('@');
+        msg.username.eq_ignore_ascii_case(pattern) || msg.profile.display_name.as_ref().is_some_and(|d| d.eq_ignore_ascii_case(pattern))
+      }
+    }
+  }
+}
+
+/// First rule matching the message
+pub fn get_highlight<'a>(rules: &'a [HighlightRule], msg: &ChatMessage) -> Option<&'a HighlightRule> {
+  rules.iter().find(|r| r.matches(msg))
+}
+
+/// Alert for a new message mentioning `username` (the logged in user) or matching a highlight rule, and whether it plays a sound.
+/// The user's own messages never alert, even when they match a rule.
+pub fn message_alert(username: Option<&str>, rules: &[HighlightRule], msg: &ChatMessage) -> Option<(AlertEvent, bool)> {
+  if username.is_some_and(|u| u.eq_ignore_ascii_case(&msg.username)) {
+    return None;
+  }
+  let usernames = HashMap::from_iter(username.map(|u| (msg.provider.to_owned(), u.to_lowercase())));
+  if mentioned_in_message(&usernames, &msg.provider, &msg.message) {
+    return Some((AlertEvent::Mention, true));
+  }
+  get_highlight(rules, msg).map(|h| (AlertEvent::Highlight, h.play_sound))
+}
+
+impl TemplateApp {
+  pub fn ui_highlights_window(&mut self, ctx: &egui::Context) {
+    if !self.show_highlights_window {
+      return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Highlights").open(&mut open).collapsible(false).default_width(600.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        let mut removed : Option<usize> = None;
+        egui::ScrollArea::vertical().id_salt("highlight_rules").max_height(400.).auto_shrink([false, true]).show(ui, |ui| {
+          egui::Grid::new("highlight_rules_grid").num_columns(8).show(ui, |ui| {
+            for (ix, rule) in self.highlight_rules.iter_mut().enumerate() {
+              rule.rule.ui_enabled(ui);
+              egui::ComboBox::from_id_salt(format!("highlight_kind_{ix}")).width(80.).selected_text(rule.kind.label()).show_ui(ui, |ui| {
+                for kind in [HighlightKind::Keyword, HighlightKind::Regex, HighlightKind::User] {
+                  ui.selectable_value(&mut rule.kind, kind, kind.label());
+                }
+              });
+              let hint = match rule.kind {
+                HighlightKind::User => "Username",
+                _ => "Text to match"
+              };
+              rule.rule.ui_pattern(ui, hint, rule.kind == HighlightKind::Regex);
+              rule.rule.ui_channel(ui, format!("highlight_channel_{ix}"), &self.channel_tab_list);
+              egui::color_picker::color_edit_button_srgba(ui, &mut rule.color, egui::color_picker::Alpha::OnlyBlend);
+              ui.checkbox(&mut rule.show_in_mentions, "Mentions").on_hover_text("Also show matching messages in the Mentions tab");
+              ui.checkbox(&mut rule.play_sound, "Sound").on_hover_text("Play the highlight sound for matching messages, if set under Options > Sounds");
+              if ui.small_button("✖").on_hover_text("Remove rule").clicked() {
+                removed = Some(ix);
+              }
+              ui.end_row();
+            }
+          });
+        });
+        if let Some(ix) = removed {
+          self.highlight_rules.remove(ix);
+        }
+
+        if ui_add_rule_row(ui, "Add rule", self.highlight_rules.iter().any(|r| r.error().is_some())) {
+          self.highlight_rules.push(Default::default());
+        }
+      });
+    });
+
+    if !open {
+      self.show_highlights_window = false;
+    }
+  }
+}
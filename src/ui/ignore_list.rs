@@ -0,0 +1,83 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use egui::{Key, TextStyle};
+use itertools::Itertools;
+
+use crate::provider::ProviderName;
+
+use super::TemplateApp;
+use super::models::*;
+
+impl TemplateApp {
+  pub fn ui_ignore_list_window(&mut self, ctx: &egui::Context) {
+    if !self.show_ignore_list {
+      return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Ignored Users").open(&mut open).collapsible(false).default_width(300.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        ui.horizontal(|ui| {
+          ui.label("Messages from ignored users:");
+          ui.radio_value(&mut self.ignore_mode, IgnoreMode::Hide, "Hide");
+          ui.radio_value(&mut self.ignore_mode, IgnoreMode::Collapse, "Collapse").on_hover_text("Show a placeholder line instead, hover over it to see the message");
+        });
+        ui.separator();
+
+        let menu = &mut self.ignore_list_menu;
+        let mut add_user = false;
+        ui.horizontal(|ui| {
+          egui::ComboBox::from_id_salt("ignore_provider").selected_text(menu.provider.display_name()).show_ui(ui, |ui| {
+            for provider in ProviderName::ALL {
+              let label = provider.display_name();
+              ui.selectable_value(&mut menu.provider, provider, label);
+            }
+          });
+          let resp = ui.add(egui::TextEdit::singleline(&mut menu.username).hint_text("Username"));
+          if resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) || ui.button("Ignore").clicked() {
+            add_user = true;
+          }
+        });
+        let username = menu.username.trim().trim_start_matches('@').to_lowercase();
+        if add_user && !username.is_empty() {
+          self.ignored_users.entry(menu.provider.to_owned()).or_default().insert(username);
+          menu.username.clear();
+        }
+        ui.separator();
+
+        let mut unignore : Option<(ProviderName, String)> = None;
+        egui::ScrollArea::vertical().id_salt("ignored_users").max_height(300.).auto_shrink([false, true]).show(ui, |ui| {
+          let users = self.ignored_users.iter()
+            .flat_map(|(provider, users)| users.iter().map(move |u| (provider, u)))
+            .sorted_by_key(|(provider, username)| (provider.display_name(), username.to_owned()))
+            .collect_vec();
+          if users.is_empty() {
+            ui.label("No ignored users. Right click a message to ignore its user.");
+          }
+          for (provider, username) in users {
+            ui.horizontal(|ui| {
+              if ui.small_button("✖").on_hover_text("Stop ignoring").clicked() {
+                unignore = Some((provider.to_owned(), username.to_owned()));
+              }
+              ui.label(format!("{username} ({})", provider.display_name()));
+            });
+          }
+        });
+        if let Some((provider, username)) = unignore && let Some(users) = self.ignored_users.get_mut(&provider) {
+          users.remove(&username);
+        }
+      });
+    });
+
+    if !open {
+      self.show_ignore_list = false;
+    }
+  }
+}
@@ -7,11 +7,11 @@
 use chrono::{DateTime, Utc};
 use egui::{Color32, Pos2, Rect, Vec2};
 
-use crate::{emotes::Emote, provider::{ChatMessage, ProviderName}};
+use crate::{emotes::Emote, provider::{channel::IrcNetwork, ChatMessage, MessageType, ModerationAction, ProviderName}};
 
 use super::addtl_functions::get_provider_color;
 
-use std::{collections::{vec_deque::IterMut, HashMap}, iter::Peekable, ops::{Range, RangeFrom}};
+use std::{collections::{vec_deque::IterMut, HashMap, HashSet}, iter::Peekable, ops::{Range, RangeFrom}};
 
 pub enum ChatPanel {
     Left,
@@ -72,6 +72,7 @@ pub struct AddChannelMenu {
   pub channel_name: String,
   //pub channel_id: String,
   pub provider: ProviderName,
+  pub irc_network: IrcNetwork,
 }
 
 impl Default for AddChannelMenu {
@@ -79,10 +80,168 @@ impl Default for AddChannelMenu {
         Self { 
           channel_name: Default::default(), 
           //channel_id: Default::default(), 
-          provider: ProviderName::Twitch }
+          provider: ProviderName::Twitch,
+          irc_network: IrcNetwork { port: 6697, use_tls: true, ..Default::default() } }
     }
 }
 
+#[derive(Default)]
+pub struct SearchMenu {
+  pub text: String,
+  pub username: String,
+  pub channel: Option<String>,
+  pub provider: Option<ProviderName>,
+  pub msg_type: Option<MessageType>,
+  pub date_from: String,
+  pub date_to: String,
+  pub emote: String,
+  pub include_archive: bool,
+  pub results: Vec<SearchResult>,
+  pub error: Option<String>,
+  /// Incremented on each search so results from an earlier archive read are dropped
+  pub search_id: usize,
+  /// Waiting for the archive read of the current search
+  pub searching_archive: bool
+}
+
+/// Private messages with one user
+pub struct WhisperConversation {
+  pub provider: ProviderName,
+  /// Lowercase username of the other user
+  pub username: String,
+  pub display_name: Option<String>,
+  pub messages: Vec<ChatMessage>,
+  pub unread: usize,
+  /// Received messages to mark as read on the provider's side once the conversation is viewed
+  pub unread_ids: Vec<String>,
+  pub history_requested: bool
+}
+
+impl WhisperConversation {
+  pub fn new(provider: &ProviderName, username: &str) -> Self {
+    Self {
+      provider: provider.to_owned(),
+      username: username.to_lowercase(),
+      display_name: None,
+      messages: Default::default(),
+      unread: 0,
+      unread_ids: Default::default(),
+      history_requested: false
+    }
+  }
+
+  /// Merge in messages loaded from the provider, keeping the ones received since that are not part of it
+  pub fn add_history(&mut self, history: Vec<ChatMessage>, unread_ids: Vec<String>) {
+    let history_ids : HashSet<String> = history.iter().map(|m| m.id.to_owned()).collect();
+    let mut messages = history;
+    messages.extend(self.messages.drain(..).filter(|m| m.id.is_empty() || !history_ids.contains(&m.id)));
+    messages.sort_by_key(|m| m.timestamp);
+    if let Some(display_name) = messages.iter().rev().filter(|m| m.username == self.username).find_map(|m| m.profile.display_name.to_owned()) {
+      self.display_name = Some(display_name);
+    }
+    self.messages = messages;
+
+    for id in unread_ids {
+      if !self.unread_ids.contains(&id) {
+        self.unread_ids.push(id);
+      }
+    }
+    self.unread = self.unread.max(self.unread_ids.len());
+    self.history_requested = true;
+  }
+
+  pub fn name(&self) -> &str {
+    self.display_name.as_deref().unwrap_or(&self.username)
+  }
+}
+
+#[derive(Default)]
+pub struct WhisperMenu {
+  pub selected: Option<(ProviderName, String)>,
+  pub draft_message: String
+}
+
+pub struct SearchResult {
+  pub message: ChatMessage,
+  /// false if the message was only found in the on-disk archive and cannot be scrolled to
+  pub in_history: bool
+}
+
+/// Action chosen from a message's context menu
+pub enum MessageAction {
+  Reply(ChatMessage),
+  Moderate { provider: ProviderName, channel: String, action: ModerationAction },
+  Ignore { provider: ProviderName, username: String }
+}
+
+/// How messages from ignored users are shown
+#[derive(Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+pub enum IgnoreMode {
+  /// Dropped when received
+  #[default]
+  Hide,
+  /// Kept, but shown as a one line placeholder
+  Collapse
+}
+
+#[derive(Default)]
+pub struct IgnoreListMenu {
+  pub provider: ProviderName,
+  pub username: String
+}
+
+/// Identifies a message in the chat histories by its id
+#[derive(Clone, PartialEq)]
+pub struct MessageRef {
+  pub channel: String,
+  pub id: String
+}
+
+impl MessageRef {
+  pub fn matches(&self, msg: &ChatMessage) -> bool {
+    self.id == msg.id && self.channel == msg.channel
+  }
+}
+
+impl From<&ChatMessage> for MessageRef {
+  fn from(msg: &ChatMessage) -> Self {
+    Self {
+      channel: msg.channel.to_owned(),
+      id: msg.id.to_owned()
+    }
+  }
+}
+
+/// Activity in a channel since it was last viewed
+#[derive(Default)]
+pub struct UnreadCounter {
+  pub messages: usize,
+  pub mentions: usize,
+  /// First message received while the channel was not viewed, found again by its id for "Jump to first unread"
+  pub first_unread: Option<MessageRef>
+}
+
+impl UnreadCounter {
+  pub fn add(&mut self, msg: &ChatMessage, is_mention: bool) {
+    self.messages += 1;
+    if is_mention {
+      self.mentions += 1;
+    }
+    if self.first_unread.is_none() {
+      self.first_unread = Some(MessageRef::from(msg));
+    }
+  }
+
+  pub fn label(&self) -> String {
+    match self.mentions {
+      0 => format!("{} unread messages", self.messages),
+      1 => format!("{} unread messages, 1 mention", self.messages),
+      mentions => format!("{} unread messages, {mentions} mentions", self.messages)
+    }
+  }
+}
+
 #[derive(Default)]
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 pub struct AuthTokens {
@@ -124,6 +283,13 @@ pub struct ChatPanelOptions {
   pub selected_emote: Option<String>,
   #[cfg_attr(feature = "persistence", serde(skip))]
   pub selected_emote_input: Option<(usize, String)>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  pub scroll_to_message: Option<MessageRef>,
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  pub highlighted_message: Option<MessageRef>,
+  /// Message that the next sent message will reply to
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  pub reply_to: Option<ChatMessage>,
 }
 
 impl Default for ChatPanelOptions {
@@ -138,7 +304,10 @@ impl Default for ChatPanelOptions {
             selected_emote: None,
             selected_emote_input: None,
             selected_msg: None,
-            selected_user: None
+            selected_user: None,
+            scroll_to_message: None,
+            highlighted_message: None,
+            reply_to: None
         }
     }
 }
@@ -0,0 +1,94 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::cell::OnceCell;
+use egui::Color32;
+use regex::Regex;
+
+use crate::provider::{ChatMessage, MessageType};
+
+/// Pattern matched against chat messages, optionally limited to one channel. Shared by highlight rules and message filters.
+#[derive(Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))]
+pub struct PatternRule {
+  pub enabled: bool,
+  pub pattern: String,
+  /// Only match messages in this channel, or in all channels if None
+  pub channel: Option<String>,
+  /// Compiled on first use, reset when the pattern changes. Keeps the compile error for invalid patterns.
+  #[cfg_attr(feature = "persistence", serde(skip))]
+  regex: OnceCell<Result<Regex, String>>
+}
+
+impl Default for PatternRule {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      pattern: Default::default(),
+      channel: None,
+      regex: Default::default()
+    }
+  }
+}
+
+impl PatternRule {
+  pub fn new(pattern: &str, channel: Option<&str>) -> Self {
+    Self { pattern: pattern.to_owned(), channel: channel.map(|c| c.to_owned()), ..Default::default() }
+  }
+
+  pub fn set_pattern(&mut self, pattern: String) {
+    self.pattern = pattern;
+    self.regex = Default::default();
+  }
+
+  pub fn regex(&self) -> Result<&Regex, &String> {
+    self.regex.get_or_init(|| Regex::new(&self.pattern).map_err(|e| e.to_string())).as_ref()
+  }
+
+  /// Whether the rule should be checked against the message at all
+  pub fn applies_to(&self, msg: &ChatMessage) -> bool {
+    self.enabled && !self.pattern.is_empty() && msg.msg_type == MessageType::Chat && self.channel.as_ref().is_none_or(|c| c == &msg.channel)
+  }
+
+  /// Enabled checkbox for the rule's row in a grid
+  pub fn ui_enabled(&mut self, ui: &mut egui::Ui) {
+    ui.checkbox(&mut self.enabled, "").on_hover_text("Enabled");
+  }
+
+  /// Pattern text box for the rule's row in a grid. For regex patterns the compile error is shown as hover text.
+  pub fn ui_pattern(&mut self, ui: &mut egui::Ui, hint: &str, is_regex: bool) {
+    let mut pattern = self.pattern.to_owned();
+    let resp = ui.add(egui::TextEdit::singleline(&mut pattern).hint_text(hint));
+    if resp.changed() {
+      self.set_pattern(pattern);
+    }
+    if is_regex && let Err(error) = self.regex() {
+      resp.on_hover_text(error.as_str());
+    }
+  }
+
+  /// Channel picker for the rule's row in a grid
+  pub fn ui_channel(&mut self, ui: &mut egui::Ui, id: impl std::hash::Hash, channels: &[String]) {
+    egui::ComboBox::from_id_salt(id).width(100.).selected_text(self.channel.as_deref().unwrap_or("All channels")).show_ui(ui, |ui| {
+      ui.selectable_value(&mut self.channel, None, "All channels");
+      for channel in channels {
+        ui.selectable_value(&mut self.channel, Some(channel.to_owned()), channel);
+      }
+    });
+  }
+}
+
+/// Add button and invalid regex notice shown below a grid of rules. Returns true if the button was clicked.
+pub fn ui_add_rule_row(ui: &mut egui::Ui, label: &str, has_error: bool) -> bool {
+  ui.horizontal(|ui| {
+    let clicked = ui.button(label).clicked();
+    if has_error {
+      ui.colored_label(Color32::RED, "Invalid regex, hover over the pattern for details");
+    }
+    clicked
+  }).inner
+}
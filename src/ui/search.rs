@@ -0,0 +1,268 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::HashSet;
+use chrono::{Days, NaiveDate};
+use egui::{Color32, Key, RichText, TextStyle};
+use itertools::Itertools;
+
+use crate::archive::LogFileQuery;
+use crate::provider::{ChatMessage, MessageType, ProviderName};
+
+use super::TemplateApp;
+use super::models::*;
+
+/// Max number of results shown, newest first
+const SEARCH_RESULT_LIMIT : usize = 500;
+
+pub struct SearchFilter {
+  pub text: Option<String>,
+  pub username: Option<String>,
+  pub channel: Option<String>,
+  pub provider: Option<ProviderName>,
+  pub msg_type: Option<MessageType>,
+  pub date_from: Option<NaiveDate>,
+  pub date_to: Option<NaiveDate>,
+  pub emote: Option<String>
+}
+
+impl SearchFilter {
+  pub fn new(menu: &SearchMenu) -> Result<Self, String> {
+    let parse_date = |text: &str, name: &str| -> Result<Option<NaiveDate>, String> {
+      if text.trim().is_empty() {
+        Ok(None)
+      } else {
+        NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").map(Some).map_err(|_| format!("Invalid {name} date, expected YYYY-MM-DD"))
+      }
+    };
+    let lowercase_or_none = |text: &str| if text.trim().is_empty() { None } else { Some(text.trim().to_lowercase()) };
+
+    Ok(Self {
+      text: lowercase_or_none(&menu.text),
+      username: lowercase_or_none(&menu.username),
+      channel: menu.channel.to_owned(),
+      provider: menu.provider.to_owned(),
+      msg_type: menu.msg_type.to_owned(),
+      date_from: parse_date(&menu.date_from, "From")?,
+      date_to: parse_date(&menu.date_to, "To")?,
+      // emote names are case sensitive
+      emote: if menu.emote.trim().is_empty() { None } else { Some(menu.emote.trim().to_owned()) }
+    })
+  }
+
+  /// Date range is compared against the local date the message was sent
+  pub fn matches(&self, msg: &ChatMessage) -> bool {
+    let date = msg.timestamp.with_timezone(&chrono::Local).date_naive();
+    self.channel.as_ref().is_none_or(|c| *c == msg.channel)
+      && self.provider.as_ref().is_none_or(|p| *p == msg.provider)
+      && self.msg_type.as_ref().is_none_or(|t| *t == msg.msg_type)
+      && self.date_from.is_none_or(|from| date >= from)
+      && self.date_to.is_none_or(|to| date <= to)
+      && self.username.as_ref().is_none_or(|u| msg.username.to_lowercase() == *u || msg.profile.display_name.as_ref().is_some_and(|d| d.to_lowercase() == *u))
+      && self.emote.as_ref().is_none_or(|e| msg.message.split_whitespace().any(|word| word == e))
+      && self.text.as_ref().is_none_or(|t| msg.message.to_lowercase().contains(t))
+  }
+}
+
+impl TemplateApp {
+  pub fn ui_search_window(&mut self, ctx: &egui::Context) {
+    if !self.show_search_window {
+      return;
+    }
+
+    let mut open = true;
+    let mut run_search = false;
+    let mut jump_to : Option<ChatMessage> = None;
+    egui::Window::new("Search Chat History").open(&mut open).collapsible(false).default_width(600.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        let menu = &mut self.search_menu;
+        egui::Grid::new("search_filters").num_columns(2).show(ui, |ui| {
+          let mut text_filter = |ui: &mut egui::Ui, label: &str, text: &mut String, hint: &str| {
+            ui.label(label);
+            let resp = ui.add(egui::TextEdit::singleline(text).hint_text(hint));
+            if resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+              run_search = true;
+            }
+            ui.end_row();
+          };
+          text_filter(ui, "Text:", &mut menu.text, "");
+          text_filter(ui, "Username:", &mut menu.username, "");
+          text_filter(ui, "Contains Emote:", &mut menu.emote, "");
+          text_filter(ui, "From:", &mut menu.date_from, "YYYY-MM-DD");
+          text_filter(ui, "To:", &mut menu.date_to, "YYYY-MM-DD");
+
+          ui.label("Channel:");
+          egui::ComboBox::from_id_salt("search_channel").selected_text(menu.channel.as_deref().unwrap_or("Any")).show_ui(ui, |ui| {
+            ui.selectable_value(&mut menu.channel, None, "Any");
+            for channel in &self.channel_tab_list {
+              ui.selectable_value(&mut menu.channel, Some(channel.to_owned()), channel);
+            }
+          });
+          ui.end_row();
+
+          ui.label("Provider:");
+          egui::ComboBox::from_id_salt("search_provider").selected_text(menu.provider.as_ref().map(ProviderName::display_name).unwrap_or("Any")).show_ui(ui, |ui| {
+            ui.selectable_value(&mut menu.provider, None, "Any");
+            for provider in ProviderName::ALL {
+              let label = provider.display_name();
+              ui.selectable_value(&mut menu.provider, Some(provider), label);
+            }
+          });
+          ui.end_row();
+
+          ui.label("Message Type:");
+          egui::ComboBox::from_id_salt("search_msg_type").selected_text(menu.msg_type.as_ref().map(msg_type_label).unwrap_or("Any")).show_ui(ui, |ui| {
+            ui.selectable_value(&mut menu.msg_type, None, "Any");
+            for msg_type in [MessageType::Chat, MessageType::Announcement, MessageType::Information, MessageType::Error] {
+              let label = msg_type_label(&msg_type);
+              ui.selectable_value(&mut menu.msg_type, Some(msg_type), label);
+            }
+          });
+          ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+          if ui.button("Search").clicked() {
+            run_search = true;
+          }
+          if self.chat_archive.is_some() {
+            ui.checkbox(&mut menu.include_archive, "Include chat log archive");
+          }
+        });
+
+        ui.separator();
+        if let Some(error) = menu.error.as_ref() {
+          ui.colored_label(Color32::RED, error);
+        }
+        else if !menu.results.is_empty() || menu.searching_archive {
+          ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("{} results{}", menu.results.len(), if menu.results.len() >= SEARCH_RESULT_LIMIT { " (showing newest)" } else { "" })).text_style(TextStyle::Small));
+            if menu.searching_archive {
+              ui.spinner();
+              ui.label(RichText::new("Searching chat log archive...").text_style(TextStyle::Small));
+            }
+          });
+        }
+
+        egui::ScrollArea::vertical().id_salt("search_results").max_height(400.).auto_shrink([false, true]).show(ui, |ui| {
+          for result in &menu.results {
+            let msg = &result.message;
+            let mut text = RichText::new(format!("{} #{} {}: {}",
+              msg.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+              msg.channel,
+              msg.profile.display_name.as_ref().unwrap_or(&msg.username),
+              msg.message));
+            if !result.in_history {
+              text = text.color(Color32::GRAY);
+            }
+            if msg.is_removed.is_some() {
+              text = text.strikethrough();
+            }
+            let resp = ui.add(egui::Label::new(text).truncate().sense(egui::Sense::click()));
+            if result.in_history {
+              if resp.on_hover_text("Click to jump to message").clicked() {
+                jump_to = Some(msg.to_owned());
+              }
+            } else {
+              resp.on_hover_text("Only in the chat log archive");
+            }
+          }
+        });
+      });
+    });
+
+    if run_search {
+      self.run_search();
+    }
+    if let Some(msg) = jump_to {
+      self.jump_to_message(&msg);
+    }
+    if !open {
+      self.show_search_window = false;
+      self.lhs_chat_state.highlighted_message = None;
+      self.rhs_chat_state.highlighted_message = None;
+    }
+  }
+
+  /// Searches the chat histories right away. The archive, if included, is read in the background and its results
+  /// are merged in by `add_archive_search_results`.
+  pub fn run_search(&mut self) {
+    let menu = &mut self.search_menu;
+    menu.results.clear();
+    menu.search_id += 1;
+    menu.searching_archive = false;
+    let filter = match SearchFilter::new(menu) {
+      Ok(filter) => filter,
+      Err(e) => { menu.error = Some(e); return; }
+    };
+    menu.error = None;
+
+    let results = self.chat_histories.values()
+      .flat_map(|history| history.iter())
+      .filter(|(msg, _)| filter.matches(msg))
+      .map(|(msg, _)| SearchResult { message: msg.to_owned(), in_history: true })
+      .collect_vec();
+
+    if menu.include_archive && let Some(archive) = self.chat_archive.as_ref() {
+      // messages are logged with their id, so archive copies of messages still in the history are skipped by id
+      let seen : HashSet<String> = results.iter().map(|r| r.message.id.to_owned()).collect();
+      // log files are split by UTC date, so widen the range by a day and let the filter handle the rest
+      let query = LogFileQuery {
+        provider: filter.provider.to_owned(),
+        channel: filter.channel.to_owned(),
+        from: filter.date_from.and_then(|d| d.checked_sub_days(Days::new(1))),
+        to: filter.date_to.and_then(|d| d.checked_add_days(Days::new(1)))
+      };
+      archive.search(menu.search_id, query, move |msg| {
+        filter.matches(msg) && !seen.contains(&msg.id)
+      });
+      menu.searching_archive = true;
+    }
+
+    menu.results = results;
+    sort_results(&mut menu.results);
+  }
+
+  pub fn add_archive_search_results(&mut self, search_id: usize, messages: Vec<ChatMessage>) {
+    let menu = &mut self.search_menu;
+    if search_id != menu.search_id {
+      return;
+    }
+    menu.searching_archive = false;
+    menu.results.extend(messages.into_iter().map(|message| SearchResult { message, in_history: false }));
+    sort_results(&mut menu.results);
+  }
+
+  /// Scrolls the chat panel showing the message's channel (switching the main panel to it if needed) to the message
+  fn jump_to_message(&mut self, msg: &ChatMessage) {
+    let target = MessageRef::from(msg);
+    let chat_state = if self.rhs_selected_channel.as_ref() == Some(&msg.channel) {
+      &mut self.rhs_chat_state
+    } else {
+      self.selected_channel = Some(msg.channel.to_owned());
+      &mut self.lhs_chat_state
+    };
+    chat_state.scroll_to_message = Some(target.to_owned());
+    chat_state.highlighted_message = Some(target);
+  }
+}
+
+fn sort_results(results: &mut Vec<SearchResult>) {
+  results.sort_by_key(|r| std::cmp::Reverse(r.message.timestamp));
+  results.truncate(SEARCH_RESULT_LIMIT);
+}
+
+fn msg_type_label(msg_type: &MessageType) -> &'static str {
+  match msg_type {
+    MessageType::Chat => "Chat",
+    MessageType::Error => "Error",
+    MessageType::Information => "Information",
+    MessageType::Announcement => "Announcement"
+  }
+}
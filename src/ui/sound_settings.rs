@@ -0,0 +1,72 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::path::PathBuf;
+use egui::{Color32, TextStyle};
+
+use crate::sounds::{sound_file_error, AlertEvent};
+
+use super::TemplateApp;
+
+impl TemplateApp {
+  pub fn ui_sound_settings_window(&mut self, ctx: &egui::Context) {
+    if !self.show_sound_settings {
+      return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Sounds").open(&mut open).collapsible(false).default_width(450.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        let settings = &mut self.sound_settings;
+        ui.checkbox(&mut settings.enabled, "Play alert sounds");
+        ui.add(egui::Slider::new(&mut settings.volume, 0.0..=1.0).text("Volume"));
+        ui.separator();
+
+        let mut test_sound : Option<PathBuf> = None;
+        egui::Grid::new("sound_settings_grid").num_columns(3).show(ui, |ui| {
+          for (event, label) in [(AlertEvent::Mention, "Mention"), (AlertEvent::Highlight, "Highlight"), (AlertEvent::Live, "Went live")] {
+            let sound = match event {
+              AlertEvent::Mention => &mut settings.mention_sound,
+              AlertEvent::Highlight => &mut settings.highlight_sound,
+              AlertEvent::Live => &mut settings.live_sound
+            };
+            ui.label(label);
+            let mut path = sound.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+            let resp = ui.add(egui::TextEdit::singleline(&mut path).hint_text("Path to a .wav or .ogg file"));
+            if resp.changed() {
+              *sound = Some(PathBuf::from(path.trim())).filter(|p| !p.as_os_str().is_empty());
+            }
+            let error = sound.as_ref().and_then(|p| sound_file_error(p));
+            if let Some(error) = error {
+              resp.on_hover_text(error);
+            }
+            ui.horizontal(|ui| {
+              if ui.add_enabled(sound.is_some() && error.is_none(), egui::Button::new("Test")).clicked() {
+                test_sound = sound.to_owned();
+              }
+              if let Some(error) = error {
+                ui.colored_label(Color32::RED, error);
+              }
+            });
+            ui.end_row();
+          }
+        });
+
+        if let Some(path) = test_sound {
+          self.sound_player.play(&path, self.sound_settings.volume);
+        }
+        ui.label("Highlight sounds can be turned off per rule under Options > Highlights, and all sounds can be muted per channel from the channel tab right click menu.");
+      });
+    });
+
+    if !open {
+      self.show_sound_settings = false;
+    }
+  }
+}
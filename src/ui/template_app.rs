@@ -11,12 +11,14 @@ use egui::{Context, Rounding};
 use egui::{Vec2, Color32};
 use image::DynamicImage;
 use itertools::Itertools;
-use crate::{provider::{twitch::TwitchChatManager, youtube_server, IncomingMessage, MessageType, ProviderName}, ui::addtl_functions::update_font_sizes};
-use crate::provider::channel::{Channel, ChannelTransient, ChannelUser, YoutubeChannel, ChannelShared};
+use crate::{provider::{self, ChatMessage, ChatProvider, IncomingMessage, MessageType, ProviderName}, ui::addtl_functions::update_font_sizes};
+use crate::provider::channel::{Channel, ChannelUser, StatusChange};
 use crate::emotes::{LoadEmote, AddEmote, OverlayItem, EmoteSource};
-use crate::{emotes, emotes::{Emote, EmoteLoader, EmoteRequest, EmoteResponse, imaging::load_image_into_texture_handle}};
+use crate::archive::{ChatArchive, ARCHIVE_RESTORE_MESSAGE_COUNT};
+use crate::sounds::AlertEvent;
+use crate::{emotes, emotes::{EmoteLoader, EmoteRequest, EmoteResponse, imaging::load_image_into_texture_handle}};
 
-use super::{addtl_functions::*, consts::*, ChatPanelOptions, TemplateApp, UiEvent};
+use super::{addtl_functions::*, consts::*, filters::get_filter_action, highlights::message_alert, ChatPanelOptions, TemplateApp, UiEvent};
 
 use super::models::*;
 
@@ -37,25 +39,60 @@ impl TemplateApp {
       } else {
           r = TemplateApp { ..Default::default() };
           r.chat_history_limit = 100;
+          r.enable_chat_archive = true;
       }
       r.emote_loader = EmoteLoader::new("Gigachat", &runtime);
       r.emote_loader.transparent_img = Some(load_image_into_texture_handle(&cc.egui_ctx, emotes::imaging::to_egui_image(DynamicImage::from(image::ImageBuffer::from_pixel(112, 112, image::Rgba::<u8>([100, 100, 100, 0]) )))));
       r.runtime = Some(runtime);
       info!("{} channels", r.channels.len());
   
-      if r.twitch_chat_manager.is_none() && !r.auth_tokens.twitch_username.is_empty() && !r.auth_tokens.twitch_auth_token.is_empty() {
-        r.twitch_chat_manager = Some(TwitchChatManager::new(&r.auth_tokens.twitch_username, &r.auth_tokens.twitch_auth_token, r.runtime.as_ref().unwrap_or_log(), &cc.egui_ctx));
-  
-        match r.emote_loader.tx.try_send(EmoteRequest::TwitchGlobalBadgeListRequest { token: r.auth_tokens.twitch_auth_token.to_owned(), force_redownload: false }) {  
-          Ok(_) => {},
-          Err(e) => { error!("Failed to request global emote json due to error {:?}", e); }
-        };
-      }
+      r.connect_provider(&ProviderName::Twitch, &cc.egui_ctx);
 
       r
     }
 
+  /// Connect to the provider if not already connected
+  pub fn connect_provider(&mut self, provider: &ProviderName, ctx: &Context) -> Option<&mut Box<dyn ChatProvider>> {
+    if !self.chat_managers.contains_key(provider) && let Some(chat_mgr) = provider::connect(provider, &self.auth_tokens, self.enable_yt_integration, self.runtime.as_ref().unwrap_or_log(), ctx) {
+      chat_mgr.request_global_emotes(&self.emote_loader, false);
+      self.chat_managers.insert(provider.to_owned(), chat_mgr);
+      if *provider == ProviderName::YouTube {
+        self.yt_bridge_enabled = self.enable_yt_integration;
+      }
+    }
+    self.chat_managers.get_mut(provider)
+  }
+
+  /// Drop the provider's connection and connect again (e.g. after login changes), reopening its channels
+  pub fn reconnect_provider(&mut self, provider: &ProviderName, ctx: &Context) {
+    if let Some(mut chat_mgr) = self.chat_managers.remove(provider) {
+      chat_mgr.close();
+    }
+    self.connect_provider(provider, ctx);
+    if let Some(chat_mgr) = self.chat_managers.get_mut(provider) {
+      for channel in self.channels.values_mut().filter(|c| c.provider() == *provider) {
+        chat_mgr.open_channel(channel);
+        chat_mgr.request_channel_emotes(channel, &self.emote_loader, false);
+      }
+    }
+  }
+
+  pub fn open_channel(&mut self, channel_name: &str, ctx: &Context) {
+    let Some(provider) = self.channels.get(channel_name).map(|c| c.provider()) else { return; };
+    self.connect_provider(&provider, ctx);
+    if let Some(channel) = self.channels.get_mut(channel_name) {
+      if let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+        chat_mgr.open_channel(channel);
+        chat_mgr.request_channel_emotes(channel, &self.emote_loader, false);
+      }
+    }
+    self.restore_chat_history(channel_name);
+  }
+
   pub fn update_inner(&mut self, ctx: &egui::Context) {
+    self.window_focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+    self.update_viewed_channels();
+
     if self.emote_loader.transparent_img.is_none() {
       self.emote_loader.transparent_img = Some(load_image_into_texture_handle(ctx, emotes::imaging::to_egui_image(DynamicImage::from(image::ImageBuffer::from_pixel(112, 112, image::Rgba::<u8>([100, 100, 100, 0]) )))));
     }
@@ -64,8 +101,21 @@ impl TemplateApp {
         self.emote_loader.red_img = Some(load_image_into_texture_handle(ctx, emotes::imaging::to_egui_image(DynamicImage::from(image::ImageBuffer::from_pixel(112, 112, image::Rgba::<u8>([254, 100, 100, 254]) )))));
     }
 
-    if self.yt_chat_manager.is_none() && self.enable_yt_integration {
-      self.yt_chat_manager = Some(youtube_server::start_listening(self.runtime.as_ref().unwrap()));
+    if !self.chat_managers.contains_key(&ProviderName::YouTube) {
+      if self.enable_yt_integration {
+        self.connect_provider(&ProviderName::YouTube, ctx);
+      }
+    }
+    else if self.yt_bridge_enabled != self.enable_yt_integration {
+      // the Tampermonkey bridge only starts or stops with a new connection
+      self.reconnect_provider(&ProviderName::YouTube, ctx);
+    }
+
+    if self.chat_archive.is_none() && self.enable_chat_archive {
+      self.chat_archive = ChatArchive::new("Gigachat", self.runtime.as_ref().unwrap_or_log(), ctx);
+    }
+    else if !self.enable_chat_archive && let Some(archive) = self.chat_archive.take() {
+      archive.close();
     }
 
     while let Ok(event) = self.emote_loader.rx.try_recv() {
@@ -103,29 +153,16 @@ impl TemplateApp {
         },
         EmoteResponse::ChannelEmoteImageLoaded { name, channel_name, data } => {
           if let Some(channel) = self.channels.get_mut(&channel_name) {
-            match channel {
-              Channel::DGG { dgg: _, shared } |
-              Channel::Twitch { twitch: _, shared } | 
-              Channel::Youtube { youtube: _, shared } => shared.update_emote(&name, ctx, data, loading_emotes),
-            }
+            channel.shared_mut().update_emote(&name, ctx, data, loading_emotes);
           }
         },
         EmoteResponse::ChannelBadgeImageLoaded { name, channel_name, data } => {
           if let Some(channel) = self.channels.get_mut(&channel_name) {
-            match channel {
-              Channel::DGG { dgg: _, shared } | 
-              Channel::Twitch { twitch: _, shared } | 
-              Channel::Youtube { youtube: _, shared } => shared.update_badge(&name, ctx, data, loading_emotes),
-            }
-          }
-        },
-        EmoteResponse::TwitchMsgEmoteLoaded { name, id: _, data } => {
-          if let Some(provider) = self.providers.get_mut(&ProviderName::Twitch) {
-            provider.update_emote(&name, ctx, data, loading_emotes);
+            channel.shared_mut().update_badge(&name, ctx, data, loading_emotes);
           }
         },
-        EmoteResponse::YouTubeMsgEmoteLoaded { name, data } => {
-          if let Some(provider) = self.providers.get_mut(&ProviderName::YouTube) {
+        EmoteResponse::ProviderMsgEmoteLoaded { provider, name, data } => {
+          if let Some(provider) = self.providers.get_mut(&provider) {
             provider.update_emote(&name, ctx, data, loading_emotes);
           }
         },
@@ -158,6 +195,17 @@ impl TemplateApp {
 
     self.ui_add_channel_menu(ctx);
 
+    self.ui_search_window(ctx);
+
+    self.ui_ignore_list_window(ctx);
+
+    self.ui_highlights_window(ctx);
+    self.ui_filters_window(ctx);
+
+    self.ui_whispers_window(ctx);
+
+    self.ui_sound_settings_window(ctx);
+
     self.ui_auth_menu(ctx);
     
     let mut channel_removed = self.ui_channel_options(ctx);
@@ -165,33 +213,23 @@ impl TemplateApp {
 
     if !self.discarded_last_frame {
 
-        let mut msgs = 0;
-        while let Some(chat_mgr) = self.twitch_chat_manager.as_mut() && let Ok(x) = chat_mgr.out_rx.try_recv() {
-            self.handle_incoming_message(x);
-            msgs += 1;
-            if msgs > NEW_MESSAGES_PER_FRAME { break; } // Limit to prevent bad UI lag
-        }
-        msgs = 0;
         let mut msglist : Vec<IncomingMessage> = Vec::new();
-        for (_, channel) in self.channels.iter_mut() {
-        if let Channel::DGG { dgg, shared: _ } = channel {
-            while let Some(chat_mgr) = dgg.dgg_chat_manager.as_mut() && let Ok(x) = chat_mgr.out_rx.try_recv() {
+        for chat_mgr in self.chat_managers.values_mut() {
+            let mut msgs = 0;
+            while let Ok(x) = chat_mgr.out_rx().try_recv() {
                 msglist.push(x);
                 msgs += 1;
                 if msgs > NEW_MESSAGES_PER_FRAME { break; } // Limit to prevent bad UI lag
             }
-            msgs = 0;
         }
+        if let Some(archive) = self.chat_archive.as_ref() {
+            while let Ok(x) = archive.out_rx.try_recv() {
+                msglist.push(x);
+            }
         }
         for x in msglist {
             self.handle_incoming_message(x);
         }
-        
-        while let Some(chat_mgr) = self.yt_chat_manager.as_mut()  && let Ok(x) = chat_mgr.out_rx.try_recv() {
-            self.handle_incoming_message(x);
-            msgs += 1;
-            if msgs > NEW_MESSAGES_PER_FRAME { break; } // Limit to prevent bad UI lag
-        }
     }
 
     let body_font_size = self.body_text_size;
@@ -229,14 +267,23 @@ impl TemplateApp {
         selected_user: self.lhs_chat_state.selected_user.to_owned(),
         selected_msg: self.lhs_chat_state.selected_msg.to_owned(),
         selected_emote: self.lhs_chat_state.selected_emote.to_owned(),
-        selected_emote_input: self.lhs_chat_state.selected_emote_input.to_owned()
+        selected_emote_input: self.lhs_chat_state.selected_emote_input.to_owned(),
+        scroll_to_message: self.lhs_chat_state.scroll_to_message.to_owned(),
+        highlighted_message: self.lhs_chat_state.highlighted_message.to_owned(),
+        reply_to: self.lhs_chat_state.reply_to.to_owned()
     };
 
     let mut popped_height = 0.;
     let mut rhs_popped_height = 0.;
     for (channel, history) in self.chat_histories.iter_mut() {
-      if history.len() > self.chat_history_limit && let Some(popped) = history.pop_front()
-      && let Some(size_y) = popped.1.as_ref().map(|y| match popped.0.combo_data.as_ref() {
+      if history.len() <= self.chat_history_limit {
+        continue;
+      }
+      let Some(popped) = history.pop_front() else { continue; };
+      if popped.0.filtered.is_some_and(|action| action.hides_message()) && let Some(count) = self.hidden_message_counts.get_mut(channel) {
+        *count = count.saturating_sub(1);
+      }
+      if let Some(size_y) = popped.1.as_ref().map(|y| match popped.0.combo_data.as_ref() {
             None => y + CHAT_ITEM_SPACING_Y,
             Some(combo_data) => 
                 if !self.enable_combos || combo_data.is_end && combo_data.count == 1 { y + CHAT_ITEM_SPACING_Y }
@@ -287,7 +334,10 @@ impl TemplateApp {
                 selected_user: self.rhs_chat_state.selected_user.to_owned(),
                 selected_msg: self.rhs_chat_state.selected_msg.to_owned(),
                 selected_emote: self.rhs_chat_state.selected_emote.to_owned(),
-                selected_emote_input: self.rhs_chat_state.selected_emote_input.to_owned()
+                selected_emote_input: self.rhs_chat_state.selected_emote_input.to_owned(),
+                scroll_to_message: self.rhs_chat_state.scroll_to_message.to_owned(),
+                highlighted_message: self.rhs_chat_state.highlighted_message.to_owned(),
+                reply_to: self.rhs_chat_state.reply_to.to_owned()
             };
             self.rhs_chat_state.selected_channel.clone_from(&self.rhs_selected_channel);
 
@@ -306,6 +356,7 @@ impl TemplateApp {
                 self.rhs_chat_state.chat_scroll_lock_to_bottom = true;
                 self.rhs_chat_state.chat_scroll = None;
             },
+            Some(UiEvent::ChannelRemoved(channel)) => channel_removed = Some(channel),
             Some(event) => self.last_frame_ui_events.push_back(event),
             _ => warn!("unexpected failure to pop last_frame_ui_events")
           }
@@ -353,47 +404,143 @@ impl TemplateApp {
     });
 
     if let Some(channel) = channel_removed {
-      if let Some(chat_mgr) = self.twitch_chat_manager.as_mut() {
-        chat_mgr.leave_channel(&channel);
-      }
-      self.channels.remove(&channel);
-      self.channel_tab_list = self.channel_tab_list.iter().filter_map(|f| if f != &channel { Some(f.to_owned()) } else { None }).collect_vec();
+      self.remove_channel(&channel);
     }
 
     self.discarded_last_frame = ctx.will_discard();
   }
 
+  pub fn remove_channel(&mut self, channel: &str) {
+    if let Some(provider) = self.channels.get(channel).map(|c| c.provider()) && let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+      chat_mgr.leave_channel(channel);
+    }
+    self.channels.remove(channel);
+    self.unread.remove(channel);
+    self.went_live_channels.remove(channel);
+    self.channel_tab_list = self.channel_tab_list.iter().filter_map(|f| if f != channel { Some(f.to_owned()) } else { None }).collect_vec();
+  }
+
+  /// Show a client side message (e.g. command output) in the channel's chat
+  pub fn show_system_message(&mut self, channel: &str, provider: &ProviderName, message: String, msg_type: MessageType) {
+    self.handle_incoming_message(IncomingMessage::PrivMsg { message: ChatMessage {
+      provider: provider.to_owned(),
+      channel: channel.to_owned(),
+      message,
+      msg_type,
+      ..Default::default()
+    }});
+  }
+
+  /// Logged in user for the message's provider, None if connected anonymously
+  fn own_username(&self, provider: &ProviderName) -> Option<&str> {
+    self.chat_managers.get(provider).filter(|m| m.is_logged_in()).map(|m| m.username())
+  }
+
+  /// Message from someone else that mentions the logged in user
+  fn is_mention(&self, message: &ChatMessage) -> bool {
+    let username = self.own_username(&message.provider).map(|u| u.to_lowercase());
+    if username.as_ref().is_some_and(|u| *u == message.username.to_lowercase()) {
+      return false;
+    }
+    let usernames = HashMap::from_iter(username.map(|u| (message.provider.to_owned(), u)));
+    mentioned_in_message(&usernames, &message.provider, &message.message)
+  }
+
+  /// Count a new message towards the unread badges of its channel, unless the channel is being viewed
+  fn count_unread(&mut self, message: &ChatMessage) {
+    if message.msg_type != MessageType::Chat
+      || self.selected_channel.as_ref() == Some(&message.channel)
+      || self.rhs_selected_channel.as_ref() == Some(&message.channel)
+      || is_user_ignored(&self.ignored_users, message)
+      || get_filter_action(&self.message_filters, message).is_some_and(|action| action.hides_message()) {
+      return;
+    }
+    let is_mention = self.is_mention(message);
+    self.unread.entry(message.channel.to_owned()).or_default().add(message, is_mention);
+  }
+
+  /// Channels are marked read once they are switched away from
+  fn update_viewed_channels(&mut self) {
+    let viewed = [self.selected_channel.to_owned(), self.rhs_selected_channel.to_owned()].into_iter().flatten().collect_vec();
+    for channel in self.viewed_channels.iter().filter(|c| !viewed.contains(c)) {
+      self.unread.remove(channel);
+    }
+    self.viewed_channels = viewed;
+  }
+
+  /// Sound and desktop notification for a message mentioning the user or matching a highlight rule. 
+  /// Notifications are only shown while the window is in the background.
+  fn alert_for_message(&mut self, message: &ChatMessage) {
+    if message.msg_type != MessageType::Chat
+      || self.channels.get(&message.channel).is_none_or(|c| c.shared().mute_notifications)
+      || is_user_ignored(&self.ignored_users, message)
+      || get_filter_action(&self.message_filters, message).is_some() {
+      return;
+    }
+    let Some((event, play_sound)) = message_alert(self.own_username(&message.provider), &self.highlight_rules, message) else { return; };
+    if play_sound {
+      self.sound_player.play_alert(&self.sound_settings, event);
+    }
+    if self.enable_notifications && !self.window_focused {
+      let name = message.profile.display_name.as_ref().unwrap_or(&message.username);
+      self.notifications.notify(&format!("{name} in {}", message.channel), &message.message, chrono::Utc::now());
+    }
+  }
+
+  /// Post status changes in chat, and mark the tab and play the live alert when the channel goes live
+  fn handle_status_changes(&mut self, channel: &str, provider: &ProviderName, changes: Vec<StatusChange>) {
+    for change in changes {
+      let message = change.message(channel);
+      if matches!(change, StatusChange::WentLive { .. }) {
+        if self.selected_channel.as_deref() != Some(channel) && self.rhs_selected_channel.as_deref() != Some(channel) {
+          self.went_live_channels.insert(channel.to_owned());
+        }
+        if self.channels.get(channel).is_some_and(|c| !c.shared().mute_notifications) {
+          self.sound_player.play_alert(&self.sound_settings, AlertEvent::Live);
+          if self.enable_notifications && self.notify_on_live && !self.window_focused {
+            self.notifications.notify(provider.display_name(), &message, chrono::Utc::now());
+          }
+        }
+      }
+      self.show_system_message(channel, provider, message, MessageType::Information);
+    }
+  }
+
   fn handle_incoming_message(&mut self, x: IncomingMessage) {
     match x {
       IncomingMessage::PrivMsg { mut message } => {
-        let provider_emotes = self.providers.get(&message.provider).map(|f| &f.emotes);
+        // skip messages seen before a reconnect, or restored from the chat log
+        if self.chat_histories.get(&message.channel).is_some_and(|history| history.iter().rev().any(|(msg, _)| msg.id == message.id)) {
+          return;
+        }
         let channel = message.channel.to_owned();
         // remove any extra whitespace between words
         let rgx = regex::Regex::new("\\s+").unwrap_or_log();
         message.message = rgx.replace_all(message.message.trim_matches(' '), " ").to_string();
 
-        // strip down links in YT chats
-        if message.provider == ProviderName::YouTube && message.message.contains("https://www.youtube.com/redirect") {
-          let rgx = regex::Regex::new("http[^\\s]*q=([^\\s]*)").unwrap_or_log();
-          let str = rgx.replace_all(&message.message, "$1");
-          message.message = urlencoding::decode(&str).map(|x| x.into_owned()).unwrap_or_else(|_| str.to_string());
+        let info = message.provider.info();
+        message.message = info.clean_message(message.message);
+
+        // log every message, including ones hidden from ignored users
+        if let Some(archive) = self.chat_archive.as_ref() {
+          archive.append(&message);
+        }
+
+        if self.ignore_mode == IgnoreMode::Hide && is_user_ignored(&self.ignored_users, &message) {
+          return;
         }
 
-        if message.provider == ProviderName::YouTube && !self.channels.contains_key(&message.channel) {
+        if !self.channels.contains_key(&message.channel) && let Some(channel) = info.init_pushed_channel(&message.channel) {
           self.channel_tab_list.push(message.channel.to_owned());
-          self.channels.insert(message.channel.to_owned(), Channel::Youtube { 
-            youtube: YoutubeChannel {}, 
-            shared: ChannelShared { 
-              channel_name: message.channel.to_owned(),  
-              transient: Some(ChannelTransient { 
-                channel_emotes: None,
-                badge_emotes: None,
-                status: None }),
-              ..Default::default() 
-            }    
-          });
+          self.channels.insert(message.channel.to_owned(), channel);
+          self.restore_chat_history(&message.channel);
         }
 
+        self.alert_for_message(&message);
+        self.count_unread(&message);
+
+        let provider_emotes = self.providers.get(&message.provider).map(|f| &f.emotes);
+
         if message.username.is_empty() && message.channel.is_empty() && message.msg_type != MessageType::Chat {
           let provider_channels = self.channels.iter().filter_map(|(_, c)| {
             if c.provider() == message.provider { 
@@ -409,7 +556,10 @@ impl TemplateApp {
               message.to_owned(),
               provider_emotes, 
               self.channels.get(&channel).and_then(|f| f.transient()).and_then(|f| f.channel_emotes.as_ref()),
-              &self.global_emotes);
+              &self.global_emotes,
+              &self.ignored_users,
+              &self.message_filters,
+              self.hidden_message_counts.entry(channel.to_owned()).or_default());
           }
         } else {
           let chat_history = self.chat_histories.entry(channel.to_owned()).or_default();
@@ -435,41 +585,44 @@ impl TemplateApp {
             message,
             provider_emotes, 
             self.channels.get(&channel).and_then(|f| f.transient()).and_then(|f| f.channel_emotes.as_ref()),
-            &self.global_emotes);
+            &self.global_emotes,
+            &self.ignored_users,
+            &self.message_filters,
+            self.hidden_message_counts.entry(channel.to_owned()).or_default());
         }
       },
       IncomingMessage::StreamingStatus { channel, status } => {
-        if let Some(t) = self.channels.get_mut(&channel).and_then(|f| f.transient_mut()) {
-          t.status = status;
+        let mut changes = Vec::new();
+        let mut provider = None;
+        if let Some(ch) = self.channels.get_mut(&channel) {
+          provider = Some(ch.provider());
+          if let Some(t) = ch.transient_mut() {
+            // the first status after connecting is not a change
+            if let Some(previous) = t.status.as_ref() && let Some(status) = status.as_ref() {
+              changes = status.changes_since(previous);
+            }
+            t.status = status;
+          }
+        }
+        if let Some(provider) = provider {
+          self.handle_status_changes(&channel, &provider, changes);
         }
       },
       IncomingMessage::MsgEmotes { provider, emote_ids } => {
         if let Some(p) = self.providers.get_mut(&provider) {
           for (id, name) in emote_ids {
-            match provider {
-              ProviderName::Twitch => if !p.emotes.contains_key(&name) {
-                p.emotes.insert(name.to_owned(), Emote { name, id, url: "".to_owned(), path: "twitch/".to_owned(), source: EmoteSource::Twitch, ..Default::default() });
-              },
-              ProviderName::YouTube => if !p.emotes.contains_key(&name) {
-                p.emotes.insert(id.to_owned(), Emote { id: id.to_owned(), name: id, url: name.to_owned(), path: "youtube/".to_owned(), source: EmoteSource::Youtube, ..Default::default() });
-              },
-              _ => ()
+            if let Some(emote) = provider.info().message_emote(id, name) && !p.emotes.contains_key(&emote.name) {
+              p.emotes.insert(emote.name.to_owned(), emote);
             }
           }
         }
       },
       IncomingMessage::RoomId { channel, room_id } => {
-        if let Some(sco) = self.channels.get_mut(&channel) && let Channel::Twitch { twitch, shared } = sco {
+        if let Some(sco) = self.channels.get_mut(&channel) && let Channel::Twitch { twitch, shared: _ } = sco {
           twitch.room_id = Some(room_id.to_owned());
-          match self.emote_loader.tx.try_send(EmoteRequest::TwitchBadgeEmoteListRequest { 
-            channel_id: room_id, 
-            channel_name: shared.channel_name.to_owned(),
-            token: self.auth_tokens.twitch_auth_token.to_owned(), 
-            force_redownload: false
-          }) {
-            Ok(_) => {},
-            Err(e) => warn!("Failed to request channel badge and emote list for {} due to error: {:?}", &channel, e)
-          };
+          if let Some(chat_mgr) = self.chat_managers.get(&ProviderName::Twitch) {
+            chat_mgr.request_channel_emotes(sco, &self.emote_loader, false);
+          }
           //t.badge_emotes = emotes::twitch_get_channel_badges(&self.auth_tokens.twitch_auth_token, &sco.roomid, &self.emote_loader.base_path, true);
           //info!("loaded channel badges for {}:{}", channel, sco.roomid);
         }
@@ -530,15 +683,157 @@ impl TemplateApp {
           for (msg, _) in history.iter_mut() {
             if msg.username == username {
               msg.is_removed = Some("<message deleted>".to_string());
+              if let Some(archive) = self.chat_archive.as_ref() {
+                archive.append(msg);
+              }
             }
           }
         }
-      }
-        IncomingMessage::VoteStart {  } => {},
-        IncomingMessage::VoteStop {  } => {},
+      },
+      IncomingMessage::MessageDeleted { channel, id } => {
+        if let Some(history) = self.chat_histories.get_mut(&channel) 
+          && let Some((msg, _)) = history.iter_mut().rev().find(|(msg, _)| msg.id == id) {
+          msg.is_removed = Some("<message deleted>".to_string());
+          if let Some(archive) = self.chat_archive.as_ref() {
+            archive.append(msg);
+          }
+        }
+      },
+      IncomingMessage::ChatCleared { channel } => {
+        if let Some(history) = self.chat_histories.get_mut(&channel) {
+          for (msg, _) in history.iter_mut().filter(|(msg, _)| msg.msg_type == MessageType::Chat && msg.is_removed.is_none()) {
+            msg.is_removed = Some("<message deleted>".to_string());
+            if let Some(archive) = self.chat_archive.as_ref() {
+              archive.append(msg);
+            }
+          }
+        }
+      },
+      IncomingMessage::ChatModes { channel, modes } => {
+        if let Some(t) = self.channels.get_mut(&channel).and_then(|f| f.transient_mut()) {
+          t.chat_modes = modes;
+        }
+      },
+      IncomingMessage::ModeratorStatus { channel, is_moderator } => {
+        if let Some(t) = self.channels.get_mut(&channel).and_then(|f| f.transient_mut()) {
+          t.is_moderator = is_moderator;
+        }
+      },
+      IncomingMessage::ChannelBadges { channel, badges } => {
+        if let Some(sco) = self.channels.get_mut(&channel) {
+          sco.set_badges(Ok(badges));
+        }
+      },
+      IncomingMessage::ChannelEvent { provider, channel, event } => {
+        self.handle_incoming_message(IncomingMessage::PrivMsg { message: ChatMessage {
+          provider,
+          channel,
+          message: event.message(),
+          msg_type: MessageType::Announcement,
+          ..Default::default()
+        }});
+      },
+      IncomingMessage::Whisper { conversation, message } => {
+        self.add_whisper(conversation, message);
+      },
+      IncomingMessage::WhisperHistory { provider, conversation, messages, unread_ids } => {
+        self.add_whisper_history(provider, conversation, messages, unread_ids);
+      },
+      IncomingMessage::VoteStart { channel, poll } => {
+        self.polls.insert(channel, poll);
+      },
+      IncomingMessage::VoteCast { channel, option, quantity } => {
+        if let Some(poll) = self.polls.get_mut(&channel) {
+          poll.add_vote(option, quantity);
+        }
+      },
+      IncomingMessage::VoteCounted { channel, option } => {
+        if let Some(poll) = self.polls.get_mut(&channel) {
+          poll.my_vote = Some(option);
+        }
+      },
+      IncomingMessage::VoteStop { channel, poll } => {
+        self.polls.remove(&channel);
+        if let Some(provider) = self.channels.get(&channel).map(|c| c.provider()) {
+          self.handle_incoming_message(IncomingMessage::ChannelEvent { provider, channel, event: poll.end_event() });
+        }
+      },
+      IncomingMessage::ArchiveHistory { channel, messages } => {
+        self.add_restored_history(&channel, messages);
+      },
+      IncomingMessage::ArchiveSearchResults { search_id, messages } => {
+        self.add_archive_search_results(search_id, messages);
+      },
     };
   }
 
+  /// Backfills an empty channel tab with its most recent messages from the chat log archive.
+  /// The messages are read in the background and added by `add_restored_history`.
+  pub fn restore_chat_history(&mut self, channel: &str) {
+    let Some(archive) = self.chat_archive.as_ref() else { return; };
+    let Some(sco) = self.channels.get(channel) else { return; };
+    if self.chat_histories.get(channel).is_some_and(|history| !history.is_empty()) {
+      return;
+    }
+    archive.load_recent(&sco.provider(), channel, ARCHIVE_RESTORE_MESSAGE_COUNT.min(self.chat_history_limit));
+  }
+
+  /// Puts restored messages in front of anything received while they were being read
+  fn add_restored_history(&mut self, channel: &str, messages: Vec<ChatMessage>) {
+    let Some(sco) = self.channels.get_mut(channel) else { return; };
+    let received = self.chat_histories.remove(channel).unwrap_or_default();
+    // already restored, e.g. the channel was reopened before the first read finished
+    if received.iter().any(|(msg, _)| msg.from_archive) {
+      self.chat_histories.insert(channel.to_owned(), received);
+      return;
+    }
+    // live messages are logged too, so drop any the read picked up
+    let messages = messages.into_iter().filter(|msg| !received.iter().any(|(received, _)| received.id == msg.id)).collect_vec();
+    if messages.is_empty() {
+      self.chat_histories.insert(channel.to_owned(), received);
+      return;
+    }
+
+    let chat_history = self.chat_histories.entry(channel.to_owned()).or_default();
+    let provider = sco.provider();
+    let restored_count = messages.len();
+    let provider_emotes = self.providers.get(&provider).map(|f| &f.emotes);
+    for message in messages {
+      if message.msg_type == MessageType::Chat && !message.username.is_empty() {
+        sco.shared_mut().users.entry(message.username.to_lowercase()).or_insert(ChannelUser {
+          username: message.username.to_owned(),
+          display_name: message.profile.display_name.as_ref().unwrap_or(&message.username).to_owned(),
+          is_active: false
+        });
+      }
+      push_history(
+        chat_history, 
+        message,
+        provider_emotes, 
+        sco.transient().and_then(|f| f.channel_emotes.as_ref()),
+        &self.global_emotes,
+        &self.ignored_users,
+        &self.message_filters,
+        self.hidden_message_counts.entry(channel.to_owned()).or_default());
+    }
+    push_history(
+      chat_history, 
+      ChatMessage {
+        provider,
+        channel: channel.to_owned(),
+        message: format!("Restored {restored_count} messages from chat log"),
+        msg_type: MessageType::Information,
+        ..Default::default()
+      },
+      provider_emotes, 
+      sco.transient().and_then(|f| f.channel_emotes.as_ref()),
+      &self.global_emotes,
+      &self.ignored_users,
+      &self.message_filters,
+      self.hidden_message_counts.entry(channel.to_owned()).or_default());
+    chat_history.extend(received);
+  }
+
   pub fn get_possible_emotes(&mut self, selected_channel: Option<&String>, word: Option<&String>, ctx: &Context) -> Option<Vec<(String, Option<OverlayItem>)>> {
     let emote_loader = &mut self.emote_loader;
     if let Some(input_str) = word {
@@ -578,8 +873,7 @@ impl TemplateApp {
             }
           }
         }
-        // Global emotes, only if not DGG
-        if channel.provider() != ProviderName::DGG {
+        if channel.provider().info().uses_global_emotes() {
           for (name, emote) in &self.global_emotes { 
             let name_l = name.to_lowercase();
             if name_l.starts_with(word_lower) || name_l.contains(word_lower) {
@@ -615,7 +909,8 @@ impl TemplateApp {
       let mut contains_users : HashMap<String, Option<OverlayItem>> = Default::default();
       
       if let Some(channel_name) = selected_channel && let Some(channel) = self.channels.get(channel_name) {
-        for (name_lower, user) in channel.shared().users.iter().filter(|(_k, v)| v.is_active) {
+        let ignored = self.ignored_users.get(&channel.provider());
+        for (name_lower, user) in channel.shared().users.iter().filter(|(_k, v)| v.is_active && !ignored.is_some_and(|i| i.contains(&v.username.to_lowercase()))) {
           if name_lower.starts_with(word_lower) || name_lower.contains(word_lower) {
             _ = match name_lower.starts_with(word_lower) {
               true => starts_with_users.try_insert(user.display_name.to_owned(), None),
@@ -27,7 +27,10 @@ impl TemplateApp {
       selected_user,
       selected_msg,
       selected_emote: _,
-      selected_emote_input: _
+      selected_emote_input: _,
+      scroll_to_message: _,
+      highlighted_message: _,
+      reply_to
     } = chat_panel;
 
     let rect = area.to_owned()
@@ -69,7 +72,9 @@ impl TemplateApp {
               }
             }
 
-            set_selected_message(set_selected_msg, ui, selected_msg);
+            if let Some(action) = set_selected_message(set_selected_msg, ui, selected_msg, &self.channels) {
+              handle_message_action(action, reply_to, &mut self.chat_managers, &mut self.ignored_users);
+            }
           }
         });
       });
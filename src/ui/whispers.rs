@@ -0,0 +1,200 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use egui::{Color32, Key, RichText, TextStyle};
+
+use crate::provider::{ChatMessage, MessageType, ProviderName};
+use crate::sounds::AlertEvent;
+
+use super::TemplateApp;
+use super::addtl_functions::{convert_color, is_user_ignored};
+use super::consts::{DEFAULT_USER_COLOR, WHISPER_HISTORY_LIMIT};
+use super::models::*;
+
+impl TemplateApp {
+  /// Adds a sent or received whisper to the conversation with `conversation` (the other user's name)
+  pub fn add_whisper(&mut self, conversation: String, message: ChatMessage) {
+    let incoming = message.username.to_lowercase() == conversation;
+    if incoming && is_user_ignored(&self.ignored_users, &message) {
+      return;
+    }
+    let provider = message.provider.to_owned();
+    let mut convo = match self.whispers.iter().position(|c| c.provider == provider && c.username == conversation) {
+      Some(ix) => self.whispers.remove(ix),
+      None => WhisperConversation::new(&provider, &conversation)
+    };
+
+    let viewing = self.show_whispers_window && self.window_focused
+      && self.whisper_menu.selected.as_ref().is_some_and(|(p, u)| *p == convo.provider && *u == convo.username);
+    if incoming {
+      if message.profile.display_name.is_some() {
+        convo.display_name = message.profile.display_name.to_owned();
+      }
+      if !viewing {
+        convo.unread += 1;
+      }
+      if !message.id.is_empty() {
+        convo.unread_ids.push(message.id.to_owned());
+      }
+    }
+    let notification = incoming.then(|| (format!("Whisper from {}", convo.name()), message.message.to_owned()));
+
+    convo.messages.push(message);
+    limit_whisper_history(&mut convo);
+    // most recently active conversation is listed first
+    self.whispers.insert(0, convo);
+
+    if let Some((title, body)) = notification {
+      self.sound_player.play_alert(&self.sound_settings, AlertEvent::Mention);
+      if self.enable_notifications && !self.window_focused {
+        self.notifications.notify(&title, &body, chrono::Utc::now());
+      }
+    }
+  }
+
+  /// Merge earlier whispers loaded from the provider into the conversation
+  pub fn add_whisper_history(&mut self, provider: ProviderName, conversation: String, messages: Vec<ChatMessage>, unread_ids: Vec<String>) {
+    let convo = match self.whispers.iter_mut().position(|c| c.provider == provider && c.username == conversation) {
+      Some(ix) => &mut self.whispers[ix],
+      None => {
+        self.whispers.push(WhisperConversation::new(&provider, &conversation));
+        self.whispers.last_mut().unwrap()
+      }
+    };
+    convo.add_history(messages, unread_ids);
+    limit_whisper_history(convo);
+    // conversations with unread messages are listed first
+    self.whispers.sort_by_key(|c| c.unread == 0);
+  }
+
+  /// Select the conversation with the user in the whispers window, starting a new one if needed
+  pub fn open_whisper(&mut self, provider: &ProviderName, username: &str) {
+    let username = username.to_lowercase();
+    if !self.whispers.iter().any(|c| c.provider == *provider && c.username == username) {
+      self.whispers.insert(0, WhisperConversation::new(provider, &username));
+    }
+    self.whisper_menu.selected = Some((provider.to_owned(), username));
+    self.show_whispers_window = true;
+  }
+
+  pub fn unread_whispers(&self) -> usize {
+    self.whispers.iter().map(|c| c.unread).sum()
+  }
+
+  pub fn ui_whispers_window(&mut self, ctx: &egui::Context) {
+    if !self.show_whispers_window {
+      return;
+    }
+
+    let mut open = true;
+    let mut send : Option<(ProviderName, String, String)> = None;
+    let mut load_history : Option<(ProviderName, String)> = None;
+    let mut mark_read : Vec<(ProviderName, Vec<String>)> = Vec::new();
+    let window_focused = self.window_focused;
+    egui::Window::new("Whispers").open(&mut open).collapsible(false).default_width(550.).show(ctx, |ui| {
+      ui.scope(|ui| {
+        let fontid = TextStyle::Button.resolve(ui.style().as_ref());
+        ui.style_mut().text_styles.insert(TextStyle::Body, fontid);
+
+        if self.whispers.is_empty() {
+          ui.label("No whispers yet. Use /w <username> <message> in a chat that supports it to start a conversation.");
+          return;
+        }
+
+        if self.whispers.iter().any(|c| c.unread > 0) && ui.button("Mark all read").clicked() {
+          for convo in self.whispers.iter_mut() {
+            convo.unread = 0;
+            mark_read.push((convo.provider.to_owned(), std::mem::take(&mut convo.unread_ids)));
+          }
+        }
+
+        let menu = &mut self.whisper_menu;
+        ui.horizontal_top(|ui| {
+          egui::ScrollArea::vertical().id_salt("whisper_conversations").max_height(400.).max_width(150.).auto_shrink([false, true]).show(ui, |ui| {
+            for convo in &self.whispers {
+              let selected = menu.selected.as_ref().is_some_and(|(p, u)| *p == convo.provider && *u == convo.username);
+              let label = match convo.unread {
+                0 => RichText::new(convo.name()),
+                unread => RichText::new(format!("{} ({unread})", convo.name())).strong()
+              };
+              if ui.selectable_label(selected, label).on_hover_text(convo.provider.display_name()).clicked() {
+                menu.selected = Some((convo.provider.to_owned(), convo.username.to_owned()));
+              }
+            }
+          });
+          ui.separator();
+
+          let Some(convo) = self.whispers.iter_mut().find(|c| menu.selected.as_ref().is_some_and(|(p, u)| *p == c.provider && *u == c.username)) else {
+            ui.label("Select a conversation");
+            return;
+          };
+          // only counts as read while the app is in the foreground
+          if window_focused {
+            convo.unread = 0;
+            if !convo.unread_ids.is_empty() {
+              mark_read.push((convo.provider.to_owned(), std::mem::take(&mut convo.unread_ids)));
+            }
+          }
+          if !convo.history_requested {
+            convo.history_requested = true;
+            load_history = Some((convo.provider.to_owned(), convo.username.to_owned()));
+          }
+          ui.vertical(|ui| {
+            ui.strong(format!("{} ({})", convo.name(), convo.provider.display_name()));
+            egui::ScrollArea::vertical().id_salt("whisper_messages").max_height(350.).auto_shrink([false, true]).stick_to_bottom(true).show(ui, |ui| {
+              for msg in &convo.messages {
+                ui.horizontal_wrapped(|ui| {
+                  ui.label(RichText::new(msg.timestamp.with_timezone(&chrono::Local).format("%H:%M").to_string()).weak());
+                  if msg.msg_type == MessageType::Error {
+                    ui.colored_label(Color32::RED, &msg.message);
+                  } else {
+                    let color = convert_color(msg.profile.color.as_ref().unwrap_or(&DEFAULT_USER_COLOR));
+                    ui.colored_label(color, format!("{}:", msg.profile.display_name.as_ref().unwrap_or(&msg.username)));
+                    ui.label(&msg.message);
+                  }
+                });
+              }
+            });
+            let resp = ui.add(egui::TextEdit::singleline(&mut menu.draft_message).desired_width(f32::INFINITY).hint_text("Send a whisper"));
+            if resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+              if !menu.draft_message.trim().is_empty() {
+                send = Some((convo.provider.to_owned(), convo.username.to_owned(), std::mem::take(&mut menu.draft_message)));
+              }
+              resp.request_focus();
+            }
+          });
+        });
+      });
+    });
+
+    if let Some((provider, username)) = load_history && let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+      chat_mgr.request_whisper_history(&username);
+    }
+    for (provider, ids) in mark_read {
+      if !ids.is_empty() && let Some(chat_mgr) = self.chat_managers.get_mut(&provider) {
+        chat_mgr.mark_whispers_read(ids);
+      }
+    }
+    if let Some((provider, username, message)) = send {
+      let result = match self.chat_managers.get_mut(&provider) {
+        Some(chat_mgr) => chat_mgr.send_whisper(&username, message.trim()).map_err(|e| e.to_string()),
+        None => Err(format!("not connected to {}", provider.display_name()))
+      };
+      if let Err(e) = result {
+        self.add_whisper(username.to_owned(), ChatMessage { provider, message: format!("Failed to whisper {username}: {e}"), msg_type: MessageType::Error, ..Default::default() });
+      }
+    }
+    if !open {
+      self.show_whispers_window = false;
+    }
+  }
+}
+
+fn limit_whisper_history(convo: &mut WhisperConversation) {
+  if convo.messages.len() > WHISPER_HISTORY_LIMIT {
+    convo.messages.drain(..convo.messages.len() - WHISPER_HISTORY_LIMIT);
+  }
+}